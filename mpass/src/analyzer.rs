@@ -0,0 +1,585 @@
+//! Estimate how easy an arbitrary password is to guess.
+//!
+//! The approach follows zxcvbn: the password is broken into overlapping
+//! matches against known patterns (dictionary words, keyboard walks, repeats,
+//! sequences, dates), each match is assigned a number of guesses, and the
+//! cheapest sequence of matches covering the whole password gives the estimate.
+//!
+//! Examples:
+//!     Analyzer::new().analyze("p@ssword1").score => 1
+//!     Analyzer::new().analyze("Medium test 123!").score => 4
+
+pub mod keyboard;
+pub mod matching;
+
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::rtg::default_lists::get_simpleton_words;
+
+/// Guess count added per extra match, so that a password made of many
+/// small matches is not rated weaker than one made of a few large ones.
+const MIN_GUESSES_BEFORE_GROWING_SEQUENCE: f64 = 10000.0;
+const MIN_SUBMATCH_GUESSES_SINGLE_CHAR: f64 = 10.0;
+const MIN_SUBMATCH_GUESSES_MULTI_CHAR: f64 = 50.0;
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+
+/// Characters searched for patterns, as in zxcvbn. Matching takes more than
+/// linear time, so anything after this is scored as brute force.
+pub const MAX_ANALYZED_LENGTH: usize = 100;
+/// Upper bound on a guess estimate, far beyond any attack, so very long
+/// passwords don't overflow to infinity.
+pub const MAX_GUESSES: f64 = 1e100;
+
+/// The type of pattern a [`Match`] was found by.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+    Dictionary { dictionary: String, word: String, rank: usize, reversed: bool, l33t: bool },
+    Spatial { turns: usize, shifted: usize },
+    Repeat { base: String, count: usize },
+    Sequence { ascending: bool },
+    Date { year: i32, separator: bool },
+    Year { year: i32 },
+    BruteForce
+}
+
+/// A section of the password, covering characters `i` through `j` inclusive.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Match {
+    pub i: usize,
+    pub j: usize,
+    pub token: String,
+    pub pattern: Pattern,
+    pub guesses: f64
+}
+
+/// Estimated time to crack a password, in seconds, for several attack scenarios.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CrackTimes {
+    /// Online attack against a service that limits attempts (100 per hour).
+    pub online_throttled: f64,
+    /// Online attack against a service with no limits (10 per second).
+    pub online_unthrottled: f64,
+    /// Offline attack against a slow hash such as bcrypt (10k per second).
+    pub offline_slow_hash: f64,
+    /// Offline attack against a fast hash such as SHA-1 (10 billion per second).
+    pub offline_fast_hash: f64
+}
+
+/// Advice on how to improve a weak password.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Feedback {
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>
+}
+
+/// The result of analyzing a password.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Analysis {
+    /// Estimated number of guesses needed to find the password.
+    pub guesses: f64,
+    /// Score from 0 (too guessable) to 4 (very unguessable).
+    pub score: u8,
+    pub crack_times: CrackTimes,
+    pub feedback: Feedback,
+    /// The matches that make up the cheapest way to guess the password.
+    pub sequence: Vec<Match>
+}
+
+impl Analysis {
+    /// Base 10 logarithm of the guess estimate.
+    pub fn guesses_log10(&self) -> f64 {
+        self.guesses.log10()
+    }
+}
+
+/// A ranked word list. Lower ranks are assumed to be guessed first.
+pub struct Dictionary {
+    name: String,
+    ranks: HashMap<String, usize>
+}
+
+/// Estimates the guessability of passwords using a set of ranked dictionaries.
+pub struct Analyzer {
+    dictionaries: Vec<Dictionary>,
+    reference_year: i32
+}
+
+impl Analyzer {
+    /// Create an Analyzer using the built-in common password list and the
+    /// default word list.
+    pub fn new() -> Self {
+        let mut analyzer = Self::empty();
+        analyzer.add_dictionary("passwords", get_common_passwords());
+        analyzer.add_dictionary("words", get_simpleton_words());
+        analyzer
+    }
+
+    /// Create an Analyzer with no dictionaries.
+    pub fn empty() -> Self {
+        Analyzer {
+            dictionaries: Vec::new(),
+            reference_year: current_year()
+        }
+    }
+
+    /// Add a word list. Words are ranked by their position in the list,
+    /// so the most common words should come first.
+    pub fn add_dictionary(&mut self, name: &str, words: Vec<impl ToString>) {
+        let mut ranks = HashMap::new();
+        for (index, word) in words.iter().enumerate() {
+            ranks.entry(word.to_string().to_lowercase()).or_insert(index + 1);
+        }
+
+        self.dictionaries.push(Dictionary {
+            name: name.to_string(),
+            ranks
+        });
+    }
+
+    /// Set the year that dates and years are compared against.
+    /// Defaults to the current year.
+    pub fn set_reference_year(&mut self, year: i32) {
+        self.reference_year = year;
+    }
+
+    /// Estimate how easy the password is to guess. Only the first
+    /// [`MAX_ANALYZED_LENGTH`] characters are searched for patterns.
+    pub fn analyze(&self, password: &str) -> Analysis {
+        let chars: Vec<char> = password.chars().collect();
+        let analyzed = chars.len().min(MAX_ANALYZED_LENGTH);
+        let (mut guesses, mut sequence) = self.most_guessable(&chars[..analyzed]);
+        if analyzed < chars.len() {
+            let rest = chars.len() - analyzed;
+            let rest_guesses = BRUTEFORCE_CARDINALITY.powi(rest.min(i32::MAX as usize) as i32);
+            guesses *= rest_guesses;
+            sequence.push(Match {
+                i: analyzed,
+                j: chars.len() - 1,
+                token: chars[analyzed..].iter().collect(),
+                pattern: Pattern::BruteForce,
+                guesses: rest_guesses.min(MAX_GUESSES)
+            });
+        }
+        let guesses = guesses.min(MAX_GUESSES);
+        let score = score(guesses);
+
+        Analysis {
+            guesses,
+            score,
+            crack_times: CrackTimes {
+                online_throttled: guesses / (100.0 / 3600.0),
+                online_unthrottled: guesses / 10.0,
+                offline_slow_hash: guesses / 1e4,
+                offline_fast_hash: guesses / 1e10
+            },
+            feedback: feedback(score, &sequence),
+            sequence
+        }
+    }
+
+    /// Find the sequence of matches covering the password that needs the fewest guesses.
+    fn most_guessable(&self, chars: &[char]) -> (f64, Vec<Match>) {
+        let n = chars.len();
+        if n == 0 {
+            return (1.0, Vec::new());
+        }
+
+        let mut by_end: Vec<Vec<Match>> = vec![Vec::new(); n];
+        for m in matching::omnimatch(self, chars) {
+            by_end[m.j].push(m);
+        }
+
+        // best[k][l] holds the cheapest (product, match) covering chars 0..=k using l matches
+        let mut best: Vec<HashMap<usize, (f64, Match)>> = vec![HashMap::new(); n];
+
+        for k in 0..n {
+            let mut candidates = std::mem::take(&mut by_end[k]);
+            for i in 0..=k {
+                candidates.push(Match {
+                    i,
+                    j: k,
+                    token: chars[i..=k].iter().collect(),
+                    pattern: Pattern::BruteForce,
+                    guesses: BRUTEFORCE_CARDINALITY.powi((k - i + 1) as i32)
+                });
+            }
+
+            for mut m in candidates {
+                m.guesses = m.guesses.max(min_guesses(&m, n));
+
+                let previous: Vec<(usize, f64)> = if m.i == 0 {
+                    vec![(0, 1.0)]
+                }
+                else {
+                    best[m.i - 1].iter().map(|(l, (pi, _))| (*l, *pi)).collect()
+                };
+
+                for (l, pi) in previous {
+                    let product = pi * m.guesses;
+                    let length = l + 1;
+                    let better = match best[k].get(&length) {
+                        Some((existing, _)) => product < *existing,
+                        None => true
+                    };
+                    if better {
+                        best[k].insert(length, (product, m.clone()));
+                    }
+                }
+            }
+        }
+
+        let (mut length, mut guesses) = (0, f64::INFINITY);
+        for (l, (pi, _)) in best[n - 1].iter() {
+            let g = total_guesses(*l, *pi);
+            if g < guesses || (g == guesses && *l < length) {
+                length = *l;
+                guesses = g;
+            }
+        }
+
+        let mut sequence = Vec::with_capacity(length);
+        let mut k = n - 1;
+        while length > 0 {
+            let m = best[k][&length].1.clone();
+            length -= 1;
+            let start = m.i;
+            sequence.push(m);
+            if start == 0 {
+                break;
+            }
+            k = start - 1;
+        }
+        sequence.reverse();
+
+        (guesses, sequence)
+    }
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Total guesses for a sequence of `length` matches whose guesses multiply to `product`.
+fn total_guesses(length: usize, product: f64) -> f64 {
+    let factorial: f64 = (1..=length).map(|x| x as f64).product();
+    factorial * product + MIN_GUESSES_BEFORE_GROWING_SEQUENCE.powi(length as i32 - 1)
+}
+
+fn min_guesses(m: &Match, password_len: usize) -> f64 {
+    let len = m.j - m.i + 1;
+    let min = if len == password_len {
+        1.0
+    }
+    else if len == 1 {
+        MIN_SUBMATCH_GUESSES_SINGLE_CHAR
+    }
+    else {
+        MIN_SUBMATCH_GUESSES_MULTI_CHAR
+    };
+
+    if m.pattern == Pattern::BruteForce {
+        min + 1.0
+    }
+    else {
+        min
+    }
+}
+
+fn score(guesses: f64) -> u8 {
+    const DELTA: f64 = 5.0;
+    if guesses < 1e3 + DELTA {
+        0
+    }
+    else if guesses < 1e6 + DELTA {
+        1
+    }
+    else if guesses < 1e8 + DELTA {
+        2
+    }
+    else if guesses < 1e10 + DELTA {
+        3
+    }
+    else {
+        4
+    }
+}
+
+fn feedback(score: u8, sequence: &[Match]) -> Feedback {
+    if sequence.is_empty() {
+        return Feedback {
+            warning: None,
+            suggestions: vec![
+                "Use a few words, avoid common phrases.".to_string(),
+                "No need for symbols, digits, or uppercase letters.".to_string()
+            ]
+        };
+    }
+    if score > 2 {
+        return Feedback::default();
+    }
+
+    let longest = sequence.iter()
+                          .max_by_key(|m| m.j - m.i)
+                          .unwrap_or(&sequence[0]);
+    let mut feedback = match_feedback(longest, sequence.len() == 1);
+    feedback.suggestions.insert(0, "Add another word or two. Uncommon words are better.".to_string());
+    feedback
+}
+
+fn match_feedback(m: &Match, sole_match: bool) -> Feedback {
+    let mut warning = None;
+    let mut suggestions = Vec::new();
+
+    match &m.pattern {
+        Pattern::Dictionary { dictionary, rank, reversed, l33t, .. } => {
+            if dictionary == "passwords" {
+                warning = if sole_match && !l33t && !reversed {
+                    if *rank <= 10 {
+                        Some("This is a top-10 common password.")
+                    }
+                    else if *rank <= 100 {
+                        Some("This is a top-100 common password.")
+                    }
+                    else {
+                        Some("This is a very common password.")
+                    }
+                }
+                else {
+                    Some("This is similar to a commonly used password.")
+                };
+            }
+            else if sole_match {
+                warning = Some("A word by itself is easy to guess.");
+            }
+
+            let has_upper = m.token.chars().any(|c| c.is_uppercase());
+            let has_lower = m.token.chars().any(|c| c.is_lowercase());
+            if has_upper && !has_lower {
+                suggestions.push("All-uppercase is almost as easy to guess as all-lowercase.");
+            }
+            else if m.token.chars().next().is_some_and(|c| c.is_uppercase()) {
+                suggestions.push("Capitalization doesn't help very much.");
+            }
+            if *reversed {
+                suggestions.push("Reversed words aren't much harder to guess.");
+            }
+            if *l33t {
+                suggestions.push("Predictable substitutions like '@' instead of 'a' don't help very much.");
+            }
+        }
+        Pattern::Spatial { turns, .. } => {
+            warning = if *turns == 1 {
+                Some("Straight rows of keys are easy to guess.")
+            }
+            else {
+                Some("Short keyboard patterns are easy to guess.")
+            };
+            suggestions.push("Use a longer keyboard pattern with more turns.");
+        }
+        Pattern::Repeat { base, .. } => {
+            warning = if base.chars().count() == 1 {
+                Some("Repeats like \"aaa\" are easy to guess.")
+            }
+            else {
+                Some("Repeats like \"abcabcabc\" are only slightly harder to guess than \"abc\".")
+            };
+            suggestions.push("Avoid repeated words and characters.");
+        }
+        Pattern::Sequence { .. } => {
+            warning = Some("Sequences like \"abc\" or \"6543\" are easy to guess.");
+            suggestions.push("Avoid sequences.");
+        }
+        Pattern::Date { .. } => {
+            warning = Some("Dates are often easy to guess.");
+            suggestions.push("Avoid dates and years that are associated with you.");
+        }
+        Pattern::Year { .. } => {
+            warning = Some("Recent years are easy to guess.");
+            suggestions.push("Avoid recent years.");
+            suggestions.push("Avoid years that are associated with you.");
+        }
+        Pattern::BruteForce => {}
+    }
+
+    Feedback {
+        warning: warning.map(|w| w.to_string()),
+        suggestions: suggestions.iter().map(|s| s.to_string()).collect()
+    }
+}
+
+/// Describe a duration in seconds in rough human terms, such as "3 hours" or "centuries".
+pub fn display_time(seconds: f64) -> String {
+    const MINUTE: f64 = 60.0;
+    const HOUR: f64 = MINUTE * 60.0;
+    const DAY: f64 = HOUR * 24.0;
+    const MONTH: f64 = DAY * 31.0;
+    const YEAR: f64 = MONTH * 12.0;
+    const CENTURY: f64 = YEAR * 100.0;
+
+    let (value, unit) = if seconds < 1.0 {
+        return "less than a second".to_string();
+    }
+    else if seconds < MINUTE {
+        (seconds, "second")
+    }
+    else if seconds < HOUR {
+        (seconds / MINUTE, "minute")
+    }
+    else if seconds < DAY {
+        (seconds / HOUR, "hour")
+    }
+    else if seconds < MONTH {
+        (seconds / DAY, "day")
+    }
+    else if seconds < YEAR {
+        (seconds / MONTH, "month")
+    }
+    else if seconds < CENTURY {
+        (seconds / YEAR, "year")
+    }
+    else {
+        return "centuries".to_string();
+    };
+
+    let value = value.round() as u64;
+    if value == 1 {
+        format!("1 {}", unit)
+    }
+    else {
+        format!("{} {}s", value, unit)
+    }
+}
+
+fn current_year() -> i32 {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH)
+                                   .map(|d| d.as_secs())
+                                   .unwrap_or(0);
+    1970 + (seconds / 31_556_952) as i32
+}
+
+/// Get a short list of the most common passwords, most common first.
+pub fn get_common_passwords() -> Vec<&'static str> {
+    vec![
+        "123456", "password", "12345678", "qwerty", "123456789", "12345", "1234", "111111",
+        "1234567", "dragon", "123123", "baseball", "abc123", "football", "monkey", "letmein",
+        "696969", "shadow", "master", "666666", "qwertyuiop", "123321", "mustang", "1234567890",
+        "michael", "654321", "superman", "1qaz2wsx", "7777777", "121212", "000000", "qazwsx",
+        "123qwe", "killer", "trustno1", "jordan", "jennifer", "zxcvbnm", "asdfgh", "hunter",
+        "buster", "soccer", "harley", "batman", "andrew", "tigger", "sunshine", "iloveyou",
+        "2000", "charlie", "robert", "thomas", "hockey", "ranger", "daniel", "starwars",
+        "klaster", "112233", "george", "computer", "michelle", "jessica", "pepper", "1111",
+        "zxcvbn", "555555", "11111111", "131313", "freedom", "777777", "pass", "maggie",
+        "159753", "aaaaaa", "ginger", "princess", "joshua", "cheese", "amanda", "summer",
+        "love", "ashley", "nicole", "chelsea", "biteme", "matthew", "access", "yankees",
+        "987654321", "dallas", "austin", "thunder", "taylor", "matrix", "welcome", "admin",
+        "login", "passw0rd", "hello", "secret", "solo", "princess1", "qwerty123", "password1"
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analyzer() -> Analyzer {
+        let mut analyzer = Analyzer::new();
+        analyzer.set_reference_year(2024);
+        analyzer
+    }
+
+    #[test]
+    fn common_passwords_score_zero() {
+        let analyzer = analyzer();
+        for p in ["password", "123456", "qwerty", "P@ssw0rd", "drowssap", "aaaaaaaa", "abcdefg", "1991"] {
+            let a = analyzer.analyze(p);
+            assert_eq!(a.score, 0, "{} scored {} ({:?})", p, a.score, a.sequence);
+            assert!(a.feedback.warning.is_some(), "{} has no warning", p);
+        }
+    }
+
+    #[test]
+    fn finds_patterns() {
+        let analyzer = analyzer();
+
+        let a = analyzer.analyze("qwertyuiop");
+        assert!(matches!(a.sequence[0].pattern, Pattern::Dictionary { .. } | Pattern::Spatial { .. }));
+
+        let a = analyzer.analyze("zxcvfr");
+        assert_eq!(a.sequence.len(), 1);
+        assert!(matches!(a.sequence[0].pattern, Pattern::Spatial { turns: 2, .. }));
+
+        let a = analyzer.analyze("13/05/1989");
+        assert_eq!(a.sequence[0].pattern, Pattern::Date { year: 1989, separator: true });
+
+        let a = analyzer.analyze("g4rd3n");
+        assert!(matches!(&a.sequence[0].pattern, Pattern::Dictionary { word, l33t: true, .. } if word == "garden"));
+        assert!(a.feedback.suggestions.iter().any(|s| s.contains("substitutions")));
+
+        let a = analyzer.analyze("gardengardengarden");
+        assert!(matches!(&a.sequence[0].pattern, Pattern::Repeat { base, count: 3 } if base == "garden"));
+
+        let a = analyzer.analyze("Castle");
+        assert!(matches!(&a.sequence[0].pattern, Pattern::Dictionary { dictionary, word, .. }
+                         if dictionary == "words" && word == "castle"));
+        assert!(a.feedback.suggestions.iter().any(|s| s.contains("Capitalization")));
+    }
+
+    #[test]
+    fn generated_passphrases_score_well() {
+        let analyzer = analyzer();
+
+        for p in ["Medium test phrase 123!", "correct horse battery staple", "t7Eq#r!Kp2$v"] {
+            let a = analyzer.analyze(p);
+            assert!(a.score >= 3, "{} scored {} ({:?})", p, a.score, a.sequence);
+            assert_eq!(a.feedback, Feedback::default());
+        }
+    }
+
+    #[test]
+    fn sequence_covers_password() {
+        let analyzer = analyzer();
+        let password = "xX1991abc!!!!qazwsx";
+        let a = analyzer.analyze(password);
+
+        let rebuilt: String = a.sequence.iter().map(|m| m.token.as_str()).collect();
+        assert_eq!(rebuilt, password);
+        for pair in a.sequence.windows(2) {
+            assert_eq!(pair[0].j + 1, pair[1].i);
+        }
+    }
+
+    #[test]
+    fn long_passwords_are_capped() {
+        let analyzer = analyzer();
+        let password: String = (0..1600u32).map(|i| char::from(b'!' + (i * 7919 % 94) as u8)).collect();
+        let start = std::time::Instant::now();
+        let a = analyzer.analyze(&password);
+
+        assert!(start.elapsed().as_secs() < 5);
+        assert_eq!(a.guesses, MAX_GUESSES);
+        assert_eq!(a.guesses_log10(), 100.0);
+        assert_eq!(a.score, 4);
+        let last = a.sequence.last().unwrap();
+        assert_eq!((last.i, last.j, &last.pattern), (MAX_ANALYZED_LENGTH, 1599, &Pattern::BruteForce));
+
+        let a = analyzer.analyze(&format!("{}password", "a".repeat(MAX_ANALYZED_LENGTH)));
+        assert!(a.guesses.is_finite() && a.guesses > 1e8);
+    }
+
+    #[test]
+    fn empty_password() {
+        let a = analyzer().analyze("");
+        assert_eq!(a.score, 0);
+        assert!(a.sequence.is_empty());
+        assert!(!a.feedback.suggestions.is_empty());
+    }
+
+    #[test]
+    fn display_time_works() {
+        assert_eq!(display_time(0.5), "less than a second");
+        assert_eq!(display_time(1.0), "1 second");
+        assert_eq!(display_time(7200.0), "2 hours");
+        assert_eq!(display_time(1e12), "centuries");
+    }
+}
@@ -0,0 +1,68 @@
+//! QWERTY adjacency graph used to find keyboard walks.
+
+/// Rows of a US QWERTY keyboard. Each key lists its unshifted then shifted character.
+/// Every row is offset half a key to the right of the row above it.
+const QWERTY_ROWS: [&[&str]; 4] = [
+    &["`~", "1!", "2@", "3#", "4$", "5%", "6^", "7&", "8*", "9(", "0)", "-_", "=+"],
+    &["qQ", "wW", "eE", "rR", "tT", "yY", "uU", "iI", "oO", "pP", "[{", "]}", "\\|"],
+    &["aA", "sS", "dD", "fF", "gG", "hH", "jJ", "kK", "lL", ";:", "'\""],
+    &["zZ", "xX", "cC", "vV", "bB", "nN", "mM", ",<", ".>", "/?"],
+];
+
+/// Neighbour offsets (row, column) in a fixed order so a walk can
+/// tell when it changes direction.
+const DIRECTIONS: [(isize, isize); 6] = [(0, -1), (-1, 0), (-1, 1), (0, 1), (1, 0), (1, -1)];
+
+/// Location of a character on the keyboard.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Key {
+    pub row: usize,
+    pub col: usize,
+    pub shifted: bool
+}
+
+/// Find a character on the keyboard.
+pub fn locate(ch: char) -> Option<Key> {
+    for (row, keys) in QWERTY_ROWS.iter().enumerate() {
+        for (col, key) in keys.iter().enumerate() {
+            let mut chars = key.chars();
+            if chars.next() == Some(ch) {
+                return Some(Key { row, col, shifted: false });
+            }
+            if chars.next() == Some(ch) {
+                return Some(Key { row, col, shifted: true });
+            }
+        }
+    }
+    None
+}
+
+/// Returns the direction index leading from one key to another, if they are adjacent.
+pub fn direction(from: Key, to: Key) -> Option<usize> {
+    DIRECTIONS.iter().position(|(dr, dc)| {
+        from.row as isize + dr == to.row as isize && from.col as isize + dc == to.col as isize
+    })
+}
+
+/// Number of keys on the keyboard.
+pub fn starting_positions() -> usize {
+    QWERTY_ROWS.iter().map(|r| r.len()).sum()
+}
+
+/// Average number of neighbours per key.
+pub fn average_degree() -> f64 {
+    let mut total = 0;
+    for (row, keys) in QWERTY_ROWS.iter().enumerate() {
+        for col in 0..keys.len() {
+            total += DIRECTIONS.iter()
+                               .filter(|(dr, dc)| {
+                                   let r = row as isize + dr;
+                                   let c = col as isize + dc;
+                                   r >= 0 && (r as usize) < QWERTY_ROWS.len()
+                                       && c >= 0 && (c as usize) < QWERTY_ROWS[r as usize].len()
+                               })
+                               .count();
+        }
+    }
+    total as f64 / starting_positions() as f64
+}
@@ -0,0 +1,568 @@
+//! Pattern matchers used by the [`crate::analyzer::Analyzer`].
+//!
+//! Each matcher returns every match it can find, overlapping or not,
+//! along with a guess estimate for the match on its own.
+
+use std::collections::HashMap;
+use crate::analyzer::{keyboard, Analyzer, Match, Pattern};
+
+const MIN_YEAR_SPACE: i32 = 20;
+const MAX_SEQUENCE_DELTA: i64 = 5;
+
+/// Common l33t substitutions, as (original letter, substitutes).
+const L33T_TABLE: [(char, &str); 12] = [
+    ('a', "4@"), ('b', "8"), ('c', "({[<"), ('e', "3"), ('g', "69"), ('i', "1!|"),
+    ('l', "1|7"), ('o', "0"), ('s', "$5"), ('t', "+7"), ('x', "%"), ('z', "2")
+];
+
+/// Upper bound on the l33t translations tried for one password.
+const MAX_L33T_TRANSLATIONS: usize = 64;
+
+/// Run every matcher against the password.
+pub(crate) fn omnimatch(analyzer: &Analyzer, chars: &[char]) -> Vec<Match> {
+    let mut matches = dictionary_matches(analyzer, chars);
+    matches.extend(reverse_dictionary_matches(analyzer, chars));
+    matches.extend(l33t_matches(analyzer, chars));
+    matches.extend(spatial_matches(chars));
+    matches.extend(repeat_matches(analyzer, chars));
+    matches.extend(sequence_matches(chars));
+    matches.extend(date_matches(chars, analyzer.reference_year));
+    matches
+}
+
+fn token(chars: &[char], i: usize, j: usize) -> String {
+    chars[i..=j].iter().collect()
+}
+
+/// Binomial coefficient as a float, since guess counts quickly overflow integers.
+fn n_ck(n: usize, k: usize) -> f64 {
+    if k > n {
+        return 0.0;
+    }
+    let mut r = 1.0;
+    for d in 1..=k {
+        r *= (n - k + d) as f64;
+        r /= d as f64;
+    }
+    r
+}
+
+/// Find dictionary words in the (already lowercased and translated) characters.
+/// Returns (i, j, dictionary name, word, rank).
+fn raw_dictionary_matches<'a>(analyzer: &'a Analyzer, lower: &[char]) -> Vec<(usize, usize, &'a str, String, usize)> {
+    let mut found = Vec::new();
+    for i in 0..lower.len() {
+        let mut word = String::new();
+        for (j, c) in lower.iter().enumerate().skip(i) {
+            word.push(*c);
+            for dict in analyzer.dictionaries.iter() {
+                if let Some(rank) = dict.ranks.get(&word) {
+                    found.push((i, j, dict.name.as_str(), word.clone(), *rank));
+                }
+            }
+        }
+    }
+    found
+}
+
+fn lowercase(chars: &[char]) -> Vec<char> {
+    chars.iter().flat_map(|c| c.to_lowercase()).collect()
+}
+
+fn dictionary_matches(analyzer: &Analyzer, chars: &[char]) -> Vec<Match> {
+    let lower = lowercase(chars);
+    if lower.len() != chars.len() {
+        return Vec::new();
+    }
+
+    raw_dictionary_matches(analyzer, &lower).into_iter()
+        .map(|(i, j, dictionary, word, rank)| {
+            let token = token(chars, i, j);
+            Match {
+                i,
+                j,
+                guesses: rank as f64 * uppercase_variations(&token),
+                token,
+                pattern: Pattern::Dictionary { dictionary: dictionary.to_string(), word, rank, reversed: false, l33t: false }
+            }
+        })
+        .collect()
+}
+
+fn reverse_dictionary_matches(analyzer: &Analyzer, chars: &[char]) -> Vec<Match> {
+    let reversed: Vec<char> = chars.iter().rev().copied().collect();
+    let n = chars.len();
+
+    dictionary_matches(analyzer, &reversed).into_iter()
+        .filter(|m| m.j > m.i)
+        .map(|mut m| {
+            let (i, j) = (n - 1 - m.j, n - 1 - m.i);
+            m.i = i;
+            m.j = j;
+            m.token = token(chars, i, j);
+            m.guesses *= 2.0;
+            if let Pattern::Dictionary { reversed, .. } = &mut m.pattern {
+                *reversed = true;
+            }
+            m
+        })
+        .collect()
+}
+
+fn l33t_matches(analyzer: &Analyzer, chars: &[char]) -> Vec<Match> {
+    let lower = lowercase(chars);
+    if lower.len() != chars.len() {
+        return Vec::new();
+    }
+
+    // every substitute character present, with the letters it could stand for
+    let mut options: Vec<(char, Vec<char>)> = Vec::new();
+    for c in lower.iter() {
+        if options.iter().any(|(s, _)| s == c) {
+            continue;
+        }
+        let letters: Vec<char> = L33T_TABLE.iter()
+                                           .filter(|(_, subs)| subs.contains(*c))
+                                           .map(|(l, _)| *l)
+                                           .collect();
+        if !letters.is_empty() {
+            options.push((*c, letters));
+        }
+    }
+    if options.is_empty() {
+        return Vec::new();
+    }
+
+    let mut translations: Vec<HashMap<char, char>> = vec![HashMap::new()];
+    for (sub, letters) in options.iter() {
+        let mut next = Vec::new();
+        for t in translations.iter() {
+            for letter in letters {
+                let mut t = t.clone();
+                t.insert(*sub, *letter);
+                next.push(t);
+            }
+        }
+        next.truncate(MAX_L33T_TRANSLATIONS);
+        translations = next;
+    }
+
+    let mut best: HashMap<(usize, usize, String), Match> = HashMap::new();
+    for table in translations {
+        let translated: Vec<char> = lower.iter().map(|c| *table.get(c).unwrap_or(c)).collect();
+
+        for (i, j, dictionary, word, rank) in raw_dictionary_matches(analyzer, &translated) {
+            let subbed: Vec<(char, char)> = table.iter()
+                                                 .filter(|(s, _)| lower[i..=j].contains(s))
+                                                 .map(|(s, l)| (*s, *l))
+                                                 .collect();
+            // single characters such as "1" => "i" are too noisy to count as words
+            if subbed.is_empty() || i == j {
+                continue;
+            }
+
+            let token = token(chars, i, j);
+            let guesses = rank as f64 * uppercase_variations(&token) * l33t_variations(&lower[i..=j], &subbed);
+            let key = (i, j, dictionary.to_string());
+            if best.get(&key).is_some_and(|m| m.guesses <= guesses) {
+                continue;
+            }
+            best.insert(key, Match {
+                i,
+                j,
+                token,
+                pattern: Pattern::Dictionary { dictionary: dictionary.to_string(), word, rank, reversed: false, l33t: true },
+                guesses
+            });
+        }
+    }
+
+    best.into_values().collect()
+}
+
+/// Number of ways the capitalization of a word could have been chosen.
+fn uppercase_variations(token: &str) -> f64 {
+    let upper = token.chars().filter(|c| c.is_uppercase()).count();
+    let lower = token.chars().filter(|c| c.is_lowercase()).count();
+    if upper == 0 {
+        return 1.0;
+    }
+
+    let chars: Vec<char> = token.chars().collect();
+    let first_only = chars[0].is_uppercase() && upper == 1;
+    let last_only = chars[chars.len() - 1].is_uppercase() && upper == 1;
+    if lower == 0 || first_only || last_only {
+        return 2.0;
+    }
+
+    (1..=upper.min(lower)).map(|k| n_ck(upper + lower, k)).sum()
+}
+
+/// Number of ways the substitutions in a word could have been chosen.
+fn l33t_variations(lower: &[char], subbed: &[(char, char)]) -> f64 {
+    let mut variations = 1.0;
+    for (sub, letter) in subbed {
+        let s = lower.iter().filter(|c| *c == sub).count();
+        let u = lower.iter().filter(|c| *c == letter).count();
+        if s == 0 || u == 0 {
+            variations *= 2.0;
+        }
+        else {
+            variations *= (1..=s.min(u)).map(|k| n_ck(s + u, k)).sum::<f64>();
+        }
+    }
+    variations
+}
+
+fn spatial_matches(chars: &[char]) -> Vec<Match> {
+    let keys: Vec<Option<keyboard::Key>> = chars.iter().map(|c| keyboard::locate(*c)).collect();
+    let mut matches = Vec::new();
+    let mut i = 0;
+
+    while i + 1 < chars.len() {
+        let mut j = i + 1;
+        let mut last_direction = None;
+        let mut turns = 0;
+        let mut shifted = keys[i].map_or(0, |k| k.shifted as usize);
+
+        loop {
+            let step = match (j < chars.len(), keys[j - 1], keys.get(j).copied().flatten()) {
+                (true, Some(from), Some(to)) => keyboard::direction(from, to).map(|d| (d, to)),
+                _ => None
+            };
+
+            if let Some((direction, to)) = step {
+                if last_direction != Some(direction) {
+                    turns += 1;
+                    last_direction = Some(direction);
+                }
+                shifted += to.shifted as usize;
+                j += 1;
+            }
+            else {
+                if j - i > 2 {
+                    matches.push(Match {
+                        i,
+                        j: j - 1,
+                        token: token(chars, i, j - 1),
+                        pattern: Pattern::Spatial { turns, shifted },
+                        guesses: spatial_guesses(j - i, turns, shifted)
+                    });
+                }
+                i = j;
+                break;
+            }
+        }
+    }
+
+    matches
+}
+
+fn spatial_guesses(length: usize, turns: usize, shifted: usize) -> f64 {
+    let starts = keyboard::starting_positions() as f64;
+    let degree = keyboard::average_degree();
+    let mut guesses = 0.0;
+
+    for i in 2..=length {
+        for j in 1..=turns.min(i - 1) {
+            guesses += n_ck(i - 1, j - 1) * starts * degree.powi(j as i32);
+        }
+    }
+
+    if shifted > 0 {
+        let unshifted = length - shifted;
+        if unshifted == 0 {
+            guesses *= 2.0;
+        }
+        else {
+            guesses *= (1..=shifted.min(unshifted)).map(|k| n_ck(shifted + unshifted, k)).sum::<f64>();
+        }
+    }
+
+    guesses
+}
+
+fn repeat_matches(analyzer: &Analyzer, chars: &[char]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        // (base length, count) covering the most characters, preferring the shortest base
+        let mut found: Option<(usize, usize)> = None;
+        for base_len in 1..=(chars.len() - i) / 2 {
+            let base = &chars[i..i + base_len];
+            let mut count = 1;
+            while i + (count + 1) * base_len <= chars.len()
+                && &chars[i + count * base_len..i + (count + 1) * base_len] == base {
+                count += 1;
+            }
+            if count > 1 && found.is_none_or(|(l, c)| base_len * count > l * c) {
+                found = Some((base_len, count));
+            }
+        }
+
+        if let Some((base_len, count)) = found {
+            let (base_guesses, _) = analyzer.most_guessable(&chars[i..i + base_len]);
+            let j = i + base_len * count - 1;
+            matches.push(Match {
+                i,
+                j,
+                token: token(chars, i, j),
+                pattern: Pattern::Repeat { base: token(chars, i, i + base_len - 1), count },
+                guesses: base_guesses * count as f64
+            });
+            i = j + 1;
+        }
+        else {
+            i += 1;
+        }
+    }
+
+    matches
+}
+
+fn char_class(c: char) -> Option<u8> {
+    if c.is_ascii_lowercase() {
+        Some(0)
+    }
+    else if c.is_ascii_uppercase() {
+        Some(1)
+    }
+    else if c.is_ascii_digit() {
+        Some(2)
+    }
+    else {
+        None
+    }
+}
+
+fn sequence_matches(chars: &[char]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let mut i = 0;
+
+    while i + 1 < chars.len() {
+        let class = char_class(chars[i]);
+        let delta = chars[i + 1] as i64 - chars[i] as i64;
+        if class.is_none() || class != char_class(chars[i + 1]) || delta == 0 || delta.abs() > MAX_SEQUENCE_DELTA {
+            i += 1;
+            continue;
+        }
+
+        let mut j = i + 1;
+        while j + 1 < chars.len()
+            && char_class(chars[j + 1]) == class
+            && chars[j + 1] as i64 - chars[j] as i64 == delta {
+            j += 1;
+        }
+
+        if j - i >= 2 {
+            let first = chars[i];
+            let mut base = if "aAzZ019".contains(first) {
+                4.0
+            }
+            else if first.is_ascii_digit() {
+                10.0
+            }
+            else {
+                26.0
+            };
+            if delta < 0 {
+                base *= 2.0;
+            }
+
+            matches.push(Match {
+                i,
+                j,
+                token: token(chars, i, j),
+                pattern: Pattern::Sequence { ascending: delta > 0 },
+                guesses: base * (j - i + 1) as f64
+            });
+            i = j;
+        }
+        else {
+            i += 1;
+        }
+    }
+
+    matches
+}
+
+/// Ways to split a run of digits into day, month and year, by run length.
+fn date_splits(len: usize) -> &'static [(usize, usize)] {
+    match len {
+        4 => &[(1, 2), (2, 3)],
+        5 => &[(1, 3), (2, 3)],
+        6 => &[(1, 2), (2, 4), (4, 5)],
+        7 => &[(1, 3), (2, 3), (4, 5), (4, 6)],
+        8 => &[(2, 4), (4, 6)],
+        _ => &[]
+    }
+}
+
+fn date_matches(chars: &[char], reference_year: i32) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let year_space = |year: i32| ((year - reference_year).abs().max(MIN_YEAR_SPACE)) as f64;
+
+    for i in 0..chars.len() {
+        // recent years on their own
+        if i + 4 <= chars.len() && chars[i..i + 4].iter().all(|c| c.is_ascii_digit()) {
+            let year: i32 = token(chars, i, i + 3).parse().unwrap_or(0);
+            if (1900..=2099).contains(&year) {
+                matches.push(Match {
+                    i,
+                    j: i + 3,
+                    token: token(chars, i, i + 3),
+                    pattern: Pattern::Year { year },
+                    guesses: year_space(year)
+                });
+            }
+        }
+
+        for j in i + 3..chars.len().min(i + 10) {
+            let t = token(chars, i, j);
+            if let Some((year, separator)) = parse_date(&t, reference_year) {
+                let mut guesses = year_space(year) * 365.0;
+                if separator {
+                    guesses *= 4.0;
+                }
+                matches.push(Match {
+                    i,
+                    j,
+                    token: t,
+                    pattern: Pattern::Date { year, separator },
+                    guesses
+                });
+            }
+        }
+    }
+
+    matches
+}
+
+/// Try to read a date from the token, with or without separators.
+/// Returns the year and whether separators were used.
+fn parse_date(token: &str, reference_year: i32) -> Option<(i32, bool)> {
+    let chars: Vec<char> = token.chars().collect();
+
+    if chars.iter().all(|c| c.is_ascii_digit()) {
+        let mut best: Option<i32> = None;
+        for (k, l) in date_splits(chars.len()) {
+            let ints = [&token[..*k], &token[*k..*l], &token[*l..]].map(|s| s.parse::<i32>().unwrap_or(0));
+            if let Some(year) = map_ints_to_year(ints) {
+                if best.is_none_or(|b| (year - reference_year).abs() < (b - reference_year).abs()) {
+                    best = Some(year);
+                }
+            }
+        }
+        return best.map(|y| (y, false));
+    }
+
+    let separators = " /\\_.-";
+    let parts: Vec<&str> = token.split(|c| separators.contains(c)).collect();
+    let seps: Vec<char> = chars.iter().filter(|c| separators.contains(**c)).copied().collect();
+    if parts.len() != 3 || seps.len() != 2 || seps[0] != seps[1] {
+        return None;
+    }
+    let lengths_ok = parts[0].len() <= 4 && parts[1].len() <= 2 && parts[2].len() <= 4;
+    if !lengths_ok || parts.iter().any(|p| p.is_empty() || !p.chars().all(|c| c.is_ascii_digit())) {
+        return None;
+    }
+
+    let ints = [parts[0], parts[1], parts[2]].map(|s| s.parse::<i32>().unwrap_or(0));
+    map_ints_to_year(ints).map(|y| (y, true))
+}
+
+/// Check that three numbers form a plausible date and return its year.
+fn map_ints_to_year(ints: [i32; 3]) -> Option<i32> {
+    if ints[1] > 31 || ints[1] <= 0 {
+        return None;
+    }
+
+    let mut over_12 = 0;
+    let mut over_31 = 0;
+    let mut under_1 = 0;
+    for int in ints {
+        if (100..1000).contains(&int) || int > 2050 {
+            return None;
+        }
+        if int > 31 {
+            over_31 += 1;
+        }
+        if int > 12 {
+            over_12 += 1;
+        }
+        if int <= 0 {
+            under_1 += 1;
+        }
+    }
+    if over_31 >= 2 || over_12 == 3 || under_1 >= 2 {
+        return None;
+    }
+
+    let is_day_month = |a: i32, b: i32| ((1..=31).contains(&a) && (1..=12).contains(&b))
+                                      || ((1..=31).contains(&b) && (1..=12).contains(&a));
+    let year_splits = [(ints[2], ints[0], ints[1]), (ints[0], ints[1], ints[2])];
+
+    for (year, a, b) in year_splits {
+        if (1000..=2050).contains(&year) {
+            return if is_day_month(a, b) { Some(year) } else { None };
+        }
+    }
+    for (year, a, b) in year_splits {
+        if is_day_month(a, b) {
+            return Some(match year {
+                y if y > 99 => y,
+                y if y > 50 => 1900 + y,
+                y => 2000 + y
+            });
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chars(s: &str) -> Vec<char> {
+        s.chars().collect()
+    }
+
+    #[test]
+    fn sequences() {
+        let m = sequence_matches(&chars("xabcdy9753"));
+        assert_eq!(m.len(), 2);
+        assert_eq!((m[0].i, m[0].j, m[0].token.as_str()), (1, 4, "abcd"));
+        assert_eq!(m[1].token, "9753");
+        assert_eq!(m[1].pattern, Pattern::Sequence { ascending: false });
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(parse_date("1989", 2024), Some((2009, false)));
+        assert_eq!(parse_date("13051989", 2024), Some((1989, false)));
+        assert_eq!(parse_date("251299", 2024), Some((1999, false)));
+        assert_eq!(parse_date("2.3.2001", 2024), Some((2001, true)));
+        assert_eq!(parse_date("2.3-2001", 2024), None);
+        assert_eq!(parse_date("99999", 2024), None);
+    }
+
+    #[test]
+    fn l33t() {
+        let mut analyzer = Analyzer::empty();
+        analyzer.add_dictionary("words", vec!["pass", "slate"]);
+
+        let m = l33t_matches(&analyzer, &chars("p4$$5l473"));
+        assert!(m.iter().any(|m| m.token == "p4$$"));
+        assert!(m.iter().any(|m| m.token == "5l473"));
+    }
+
+    #[test]
+    fn uppercase() {
+        assert_eq!(uppercase_variations("word"), 1.0);
+        assert_eq!(uppercase_variations("Word"), 2.0);
+        assert_eq!(uppercase_variations("WORD"), 2.0);
+        assert_eq!(uppercase_variations("wOrD"), 6.0 + 4.0);
+    }
+}
@@ -0,0 +1,232 @@
+//! [`ApiKeyGenerator`] Struct
+//!
+//! API keys shaped like `acme_live_<random>_<checksum>`: a fixed prefix that
+//! secret scanners can match, random characters from the same alphanumeric
+//! [`RTG`] as the `x` spec token, and a CRC32 of everything before it in six
+//! base62 digits. The checksum lets scanners and services reject mistyped or
+//! made up keys offline, without a lookup.
+
+use std::fmt::{Display, Formatter};
+use rand::RngCore;
+use zeroize::Zeroizing;
+use crate::passphrase::Passphrase;
+use crate::rtg::{RandomTokenGenerator, RTG};
+use crate::rtg::default_lists::get_alphanumeric;
+
+/// The digits of the checksum, in value order.
+const BASE62: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Base62 digits needed for any CRC32.
+pub const CHECKSUM_LEN: usize = 6;
+
+/// Random characters in a key when no length is given, about 190 bits.
+pub const DEFAULT_KEY_LENGTH: usize = 32;
+
+/// Generates API keys with one prefix and length.
+pub struct ApiKeyGenerator {
+    prefix: String,
+    length: usize,
+    rtg: RTG
+}
+
+impl ApiKeyGenerator {
+    /// Create a generator for keys with `length` random characters. The
+    /// prefix may have ASCII letters, digits and inner underscores.
+    pub fn new(prefix: &str, length: usize) -> Result<Self, ApiKeyError> {
+        if !is_valid_prefix(prefix) {
+            return Err(ApiKeyError::InvalidPrefix(prefix.to_string()));
+        }
+        if length == 0 {
+            return Err(ApiKeyError::ZeroLength);
+        }
+
+        Ok(ApiKeyGenerator { prefix: prefix.to_string(), length, rtg: RTG::from(get_alphanumeric()) })
+    }
+
+    /// Length of every key in bytes.
+    pub fn key_len(&self) -> usize {
+        self.prefix.len() + 1 + self.length + 1 + CHECKSUM_LEN
+    }
+
+    /// Check a key is well formed, has a correct checksum and was made with
+    /// this prefix and length.
+    pub fn check(&self, key: &str) -> Result<(), ApiKeyError> {
+        check_prefixed(key, &self.prefix)?;
+        let found = key.len() - self.prefix.len() - 2 - CHECKSUM_LEN;
+        if found != self.length {
+            return Err(ApiKeyError::WrongLength { expected: self.length, found });
+        }
+
+        Ok(())
+    }
+}
+
+impl RandomTokenGenerator for ApiKeyGenerator {
+    fn get_token(&self, rng: &mut dyn RngCore) -> Passphrase {
+        let mut key = Zeroizing::new(format!("{}_", self.prefix));
+        for _ in 0..self.length {
+            key.push_str(self.rtg.get_token(rng).expose());
+        }
+        let checksum = checksum(&key);
+        key.push('_');
+        key.push_str(&checksum);
+
+        Passphrase::new(key.to_string())
+    }
+
+    fn entropy(&self) -> f64 {
+        self.length as f64 * self.rtg.entropy()
+    }
+
+    fn match_lengths(&self, candidate: &str) -> Vec<usize> {
+        match candidate.get(..self.key_len()) {
+            Some(key) if self.check(key).is_ok() => vec![key.len()],
+            _ => Vec::new()
+        }
+    }
+}
+
+impl Display for ApiKeyGenerator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ApiKey({}, {})", self.prefix, self.length)
+    }
+}
+
+/// Check the format and checksum of any key, and return its prefix.
+pub fn check(key: &str) -> Result<&str, ApiKeyError> {
+    //the random part and checksum have no underscores, the prefix may
+    let mut parts = key.rsplitn(3, '_');
+    let (Some(sum), Some(random), Some(prefix)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(ApiKeyError::Malformed);
+    };
+    if !is_valid_prefix(prefix) || random.is_empty() || !random.bytes().all(|b| b.is_ascii_alphanumeric())
+        || sum.len() != CHECKSUM_LEN {
+        return Err(ApiKeyError::Malformed);
+    }
+    if sum != checksum(&key[..key.len() - CHECKSUM_LEN - 1]) {
+        return Err(ApiKeyError::BadChecksum);
+    }
+
+    Ok(prefix)
+}
+
+/// Check the format and checksum of a key with a known prefix.
+pub fn check_prefixed(key: &str, prefix: &str) -> Result<(), ApiKeyError> {
+    let found = check(key)?;
+    if found != prefix {
+        return Err(ApiKeyError::WrongPrefix { expected: prefix.to_string(), found: found.to_string() });
+    }
+
+    Ok(())
+}
+
+/// CRC32 of the text in six base62 digits, zero padded.
+pub fn checksum(text: &str) -> String {
+    let mut crc = crc32fast::hash(text.as_bytes());
+    let mut digits = [b'0'; CHECKSUM_LEN];
+    for digit in digits.iter_mut().rev() {
+        *digit = BASE62[(crc % 62) as usize];
+        crc /= 62;
+    }
+
+    digits.iter().map(|&d| d as char).collect()
+}
+
+fn is_valid_prefix(prefix: &str) -> bool {
+    !prefix.is_empty() && !prefix.starts_with('_') && !prefix.ends_with('_')
+        && prefix.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ApiKeyError {
+    InvalidPrefix(String),
+    /// A generator for keys without random characters.
+    ZeroLength,
+    /// Not `prefix_random_checksum`.
+    Malformed,
+    BadChecksum,
+    WrongPrefix { expected: String, found: String },
+    /// The number of random characters.
+    WrongLength { expected: usize, found: usize }
+}
+
+impl Display for ApiKeyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApiKeyError::InvalidPrefix(prefix) => {
+                write!(f, "invalid prefix '{}': use letters, digits and inner underscores", prefix)
+            }
+            ApiKeyError::ZeroLength => write!(f, "keys need at least one random character"),
+            ApiKeyError::Malformed => write!(f, "not a key of the form prefix_random_checksum"),
+            ApiKeyError::BadChecksum => write!(f, "the checksum does not match"),
+            ApiKeyError::WrongPrefix { expected, found } => write!(f, "prefix is '{}', not '{}'", found, expected),
+            ApiKeyError::WrongLength { expected, found } => {
+                write!(f, "{} random characters, not {}", found, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ApiKeyError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn test_checksum() {
+        //CRC32 check value, 0xCBF43926
+        assert_eq!(checksum("123456789"), "3jZRME");
+        assert_eq!(checksum(""), "000000");
+        assert_eq!(check("acme_live_abc_3mOLik"), Ok("acme_live"));
+    }
+
+    #[test]
+    fn test_generate() {
+        let generator = ApiKeyGenerator::new("acme_live", 24).unwrap();
+        let mut rng = ChaCha20Rng::seed_from_u64(7);
+        let key = generator.get_token(&mut rng);
+        let key = key.expose();
+
+        assert_eq!(key.len(), generator.key_len());
+        assert!(key.starts_with("acme_live_"));
+        assert_eq!(check(key), Ok("acme_live"));
+        assert_eq!(generator.check(key), Ok(()));
+        assert_ne!(generator.get_token(&mut rng).expose(), key);
+        assert_eq!(generator.entropy(), 24.0 * 62f64.log2());
+        assert_eq!(generator.to_string(), "ApiKey(acme_live, 24)");
+
+        assert_eq!(generator.match_lengths(&format!("{} trailing", key)), vec![key.len()]);
+        assert!(generator.match_lengths(&key[1..]).is_empty());
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        let generator = ApiKeyGenerator::new("acme", 8).unwrap();
+        let key = generator.get_token(&mut ChaCha20Rng::seed_from_u64(1));
+        let key = key.expose();
+
+        //one changed character of the random part
+        let mut typo = key.to_string().into_bytes();
+        typo[6] = if typo[6] == b'a' { b'b' } else { b'a' };
+        assert_eq!(check(&String::from_utf8(typo).unwrap()), Err(ApiKeyError::BadChecksum));
+        assert_eq!(check(&key.replacen("acme", "acmf", 1)), Err(ApiKeyError::BadChecksum));
+        assert_eq!(check("acme_abc"), Err(ApiKeyError::Malformed));
+        assert_eq!(check("_abc_3mOLik"), Err(ApiKeyError::Malformed));
+        assert_eq!(check("acme_a-c_3mOLik"), Err(ApiKeyError::Malformed));
+
+        let other = ApiKeyGenerator::new("acme_test", 8).unwrap().get_token(&mut ChaCha20Rng::seed_from_u64(1));
+        assert!(matches!(generator.check(other.expose()), Err(ApiKeyError::WrongPrefix { .. })));
+        assert_eq!(check_prefixed(other.expose(), "acme_test"), Ok(()));
+        assert_eq!(check_prefixed(other.expose(), "test").unwrap_err().to_string(), "prefix is 'acme_test', not 'test'");
+        let longer = ApiKeyGenerator::new("acme", 9).unwrap().get_token(&mut ChaCha20Rng::seed_from_u64(1));
+        assert_eq!(generator.check(longer.expose()), Err(ApiKeyError::WrongLength { expected: 8, found: 9 }));
+
+        assert!(ApiKeyGenerator::new("acme live", 8).is_err());
+        assert!(ApiKeyGenerator::new("acme_", 8).is_err());
+        assert_eq!(ApiKeyGenerator::new("acme", 0).err().map(|e| e.to_string()),
+                   Some("keys need at least one random character".to_string()));
+    }
+}
@@ -0,0 +1,22 @@
+//! [`PassphraseCheck`] trait
+//!
+//! Checks are run against every passphrase a [`crate::Specifier`] produces.
+//! A passphrase rejected by any check is discarded and regenerated.
+pub mod blocklist;
+pub mod denylist;
+
+pub use blocklist::HashFileBlocklist;
+pub use denylist::SubstringDenylist;
+
+/// The shared trait for all passphrase checks.
+pub trait PassphraseCheck {
+    /// Returns true if the passphrase must not be handed out.
+    fn is_rejected(&self, passphrase: &str) -> bool;
+
+    /// Like is_rejected, but returns an Err with a message when the check
+    /// could not be made, such as when a list could not be read. Defaults to
+    /// is_rejected, for checks that can't fail.
+    fn try_is_rejected(&self, passphrase: &str) -> Result<bool, String> {
+        Ok(self.is_rejected(passphrase))
+    }
+}
@@ -0,0 +1,162 @@
+//! [`HashFileBlocklist`] Struct
+
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::Path;
+use sha1::{Digest, Sha1};
+use crate::check::PassphraseCheck;
+
+/// A blocklist backed by a local HIBP-style SHA-1 hash file.
+///
+/// The file must contain one uppercase hex SHA-1 hash per line, sorted in
+/// ascending order, optionally followed by `:count` as in the files published
+/// by Have I Been Pwned. Lookups binary search the file on disk, so even the
+/// full multi-gigabyte list can be used without loading it into memory.
+pub struct HashFileBlocklist {
+    file: File,
+    len: u64
+}
+
+impl HashFileBlocklist {
+    /// Open a sorted hash file.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+
+        Ok(HashFileBlocklist {
+            file,
+            len
+        })
+    }
+
+    /// Returns true if the SHA-1 hash of the passphrase is listed in the file.
+    pub fn contains(&self, passphrase: &str) -> io::Result<bool> {
+        let digest = Sha1::digest(passphrase.as_bytes());
+        let target: String = digest.iter().map(|b| format!("{:02X}", b)).collect();
+        self.contains_hash(&target)
+    }
+
+    /// Returns true if the given hex SHA-1 hash is listed in the file.
+    pub fn contains_hash(&self, hash: &str) -> io::Result<bool> {
+        let target = hash.to_ascii_uppercase();
+        let mut reader = BufReader::new(&self.file);
+        let mut lo: u64 = 0;
+        let mut hi: u64 = self.len;
+        let mut line = String::new();
+
+        // Search over line start offsets. Each probe looks at the first line
+        // that starts at or after the midpoint.
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let mut start = mid;
+
+            if mid > 0 {
+                reader.seek(SeekFrom::Start(mid - 1))?;
+                line.clear();
+                start = mid - 1 + reader.read_line(&mut line)? as u64;
+            }
+            else {
+                reader.seek(SeekFrom::Start(0))?;
+            }
+
+            line.clear();
+            let read = reader.read_line(&mut line)? as u64;
+            if read == 0 {
+                hi = mid;
+                continue;
+            }
+
+            let entry = line.trim_end()
+                            .split(':')
+                            .next()
+                            .unwrap_or("")
+                            .to_ascii_uppercase();
+
+            match entry.as_str().cmp(target.as_str()) {
+                std::cmp::Ordering::Equal => return Ok(true),
+                std::cmp::Ordering::Less => lo = start + read,
+                std::cmp::Ordering::Greater => hi = mid
+            }
+        }
+
+        Ok(false)
+    }
+}
+
+impl PassphraseCheck for HashFileBlocklist {
+    /// A passphrase is rejected if it is listed. Read failures also reject the
+    /// passphrase, since it could not be verified as safe.
+    fn is_rejected(&self, passphrase: &str) -> bool {
+        self.contains(passphrase).unwrap_or(true)
+    }
+
+    /// Read failures are reported rather than rejecting every passphrase.
+    fn try_is_rejected(&self, passphrase: &str) -> Result<bool, String> {
+        self.contains(passphrase).map_err(|e| format!("hash file: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::rc::Rc;
+    use crate::Specifier;
+    use crate::specifier::specifier_error::SpecifierError;
+
+    fn sha1_hex(s: &str) -> String {
+        Sha1::digest(s.as_bytes()).iter().map(|b| format!("{:02X}", b)).collect()
+    }
+
+    fn write_list(name: &str, passwords: &[&str]) -> std::path::PathBuf {
+        let mut hashes: Vec<String> = passwords.iter().map(|p| sha1_hex(p)).collect();
+        hashes.sort();
+
+        let path = std::env::temp_dir().join(format!("mpass-blocklist-{}-{}", std::process::id(), name));
+        let mut f = File::create(&path).unwrap();
+        for (i, h) in hashes.iter().enumerate() {
+            writeln!(f, "{}:{}", h, i + 1).unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn finds_listed_values() {
+        let listed = vec!["password", "123456", "letmein", "Test123!", "qwerty", "dragon", "monkey"];
+        let path = write_list("finds", &listed);
+        let blocklist = HashFileBlocklist::open(&path).unwrap();
+
+        for p in &listed {
+            assert!(blocklist.contains(p).unwrap(), "{} should be listed", p);
+        }
+        for p in ["correct horse", "Password", "", "zzzzzz"] {
+            assert!(!blocklist.contains(p).unwrap(), "{} should not be listed", p);
+        }
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn empty_file_lists_nothing() {
+        let path = write_list("empty", &[]);
+        let blocklist = HashFileBlocklist::open(&path).unwrap();
+
+        assert!(!blocklist.contains("password").unwrap());
+        assert!(!blocklist.is_rejected("password"));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn read_errors_are_reported() {
+        //a directory opens, but can't be read
+        let blocklist = HashFileBlocklist::open(std::env::temp_dir()).unwrap();
+        assert!(blocklist.contains("password").is_err());
+        assert!(blocklist.try_is_rejected("password").is_err());
+
+        let mut specifier = Specifier::try_parse("w").unwrap();
+        specifier.add_check(Rc::new(blocklist));
+        assert!(matches!(specifier.get_passphrase(), Err(SpecifierError::CheckFailed(_))));
+    }
+}
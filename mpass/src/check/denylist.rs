@@ -0,0 +1,122 @@
+//! [`SubstringDenylist`] Struct
+
+use zeroize::Zeroizing;
+use crate::check::PassphraseCheck;
+
+/// A check that rejects passphrases containing any denied substring.
+///
+/// Matching is case-insensitive and is done twice: once against the
+/// passphrase with spaces removed and once against only its letters. This
+/// catches words formed across token boundaries, such as two clean words
+/// joined by `i` tokens or separated by a digit.
+pub struct SubstringDenylist {
+    entries: Vec<String>
+}
+
+impl SubstringDenylist {
+    /// Create a new SubstringDenylist from a Vec of anything that implements to_string().
+    /// Empty entries are ignored.
+    pub fn new(entries: Vec<impl ToString>) -> Self {
+        SubstringDenylist {
+            entries: entries.iter()
+                            .map(|e| e.to_string().to_lowercase())
+                            .filter(|e| !e.is_empty())
+                            .collect()
+        }
+    }
+
+    /// Create a SubstringDenylist using the built-in default list.
+    #[cfg(feature = "default-denylist")]
+    pub fn with_defaults() -> Self {
+        Self::new(get_default_denylist())
+    }
+
+    /// Add an entry to the denylist.
+    pub fn add(&mut self, entry: impl ToString) {
+        let entry = entry.to_string().to_lowercase();
+        if !entry.is_empty() {
+            self.entries.push(entry);
+        }
+    }
+
+    /// Returns the first denied entry found in the passphrase, if any.
+    pub fn find(&self, passphrase: &str) -> Option<&str> {
+        let lower = Zeroizing::new(passphrase.to_lowercase());
+        let compact: Zeroizing<String> = Zeroizing::new(lower.chars().filter(|c| !c.is_whitespace()).collect());
+        let letters: Zeroizing<String> = Zeroizing::new(lower.chars().filter(|c| c.is_alphabetic()).collect());
+
+        self.entries.iter()
+                    .find(|e| compact.contains(e.as_str()) || letters.contains(e.as_str()))
+                    .map(|e| e.as_str())
+    }
+}
+
+impl PassphraseCheck for SubstringDenylist {
+    fn is_rejected(&self, passphrase: &str) -> bool {
+        self.find(passphrase).is_some()
+    }
+}
+
+/// Get the built-in list of denied substrings.
+///
+/// The list is kept short and avoids entries that appear inside any word
+/// from [`crate::rtg::default_lists::get_simpleton_words`], so that it only
+/// rejects unlucky combinations and never a single default word. Entries
+/// inside common words, such as "butt" in button or "sex" in Essex, are left
+/// out too, so custom word lists aren't rejected for no visible reason.
+#[cfg(feature = "default-denylist")]
+pub fn get_default_denylist() -> Vec<&'static str> {
+    vec![
+        "fuck", "shit", "cunt", "bitch", "slut", "whore", "twat", "piss", "dick", "penis",
+        "vagina", "tits", "boob", "porn", "nazi", "hitler", "kkk", "1488", "666", "murder",
+        "suicide", "bomb", "jihad", "nigg", "fag", "retard", "chink", "kike", "dyke",
+        "nude", "naked", "horny", "bastard", "dildo", "pussy", "poop", "turd", "idiot"
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_across_boundaries() {
+        let denylist = SubstringDenylist::new(vec!["tramp"]);
+
+        assert_eq!(denylist.find("StartRamp"), Some("tramp"));
+        assert_eq!(denylist.find("start ramp"), Some("tramp"));
+        assert_eq!(denylist.find("start4ramp"), Some("tramp"));
+        assert_eq!(denylist.find("start lamp"), None);
+    }
+
+    #[test]
+    fn add_and_reject() {
+        let mut denylist = SubstringDenylist::new(Vec::<String>::new());
+        assert!(!denylist.is_rejected("Queen Bee 13"));
+
+        denylist.add("");
+        denylist.add("BEE1");
+        assert!(denylist.is_rejected("Queen Bee 13"));
+    }
+
+    #[cfg(feature = "default-denylist")]
+    #[test]
+    fn defaults_allow_every_default_word() {
+        use crate::rtg::default_lists::get_simpleton_words;
+
+        let denylist = SubstringDenylist::with_defaults();
+        for w in get_simpleton_words() {
+            assert_eq!(denylist.find(w), None, "{} is rejected", w);
+        }
+        assert!(denylist.is_rejected("Class Hitlist"));
+    }
+
+    #[cfg(feature = "default-denylist")]
+    #[test]
+    fn defaults_allow_common_words() {
+        let denylist = SubstringDenylist::with_defaults();
+        for w in ["button", "canal", "analysis", "spice", "homonym", "Essex", "Janus", "scrap", "peacock",
+                  "grape", "therapist", "basement", "swank"] {
+            assert_eq!(denylist.find(w), None, "{} is rejected", w);
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! [`Derivation`] Struct
+//!
+//! Stateless site passwords, like LessPass: the same master secret, site,
+//! login and counter always give the same passphrase from a spec, so nothing
+//! has to be stored or synced. Argon2id stretches the master secret, salted
+//! with the site and login; HKDF-SHA256 expands the result with the counter
+//! into the seed of a ChaCha20 stream, which the [`Specifier`](crate::Specifier)
+//! uses as its RNG.
+//!
+//! The passphrase also depends on the spec and the word and symbol lists,
+//! which have to stay the same for it to be derived again.
+
+use std::fmt::{Display, Formatter};
+use argon2::{Algorithm, Argon2, Params, Version};
+use hkdf::Hkdf;
+use rand_chacha::ChaCha20Rng;
+use rand::SeedableRng;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+/// Argon2id memory in KiB.
+pub const DEFAULT_MEMORY_KIB: u32 = 64 * 1024;
+/// Argon2id passes over the memory.
+pub const DEFAULT_ITERATIONS: u32 = 3;
+/// Argon2id lanes.
+pub const DEFAULT_PARALLELISM: u32 = 1;
+
+/// Separates these keys from any other use of the same master secret.
+const DOMAIN: &[u8] = b"mpass-derive-v1";
+
+/// The Argon2id cost of a derivation. Changing it changes every derived
+/// passphrase, so the defaults should only be changed from the start.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Derivation {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32
+}
+
+impl Default for Derivation {
+    fn default() -> Self {
+        Derivation { memory_kib: DEFAULT_MEMORY_KIB, iterations: DEFAULT_ITERATIONS, parallelism: DEFAULT_PARALLELISM }
+    }
+}
+
+impl Derivation {
+    /// Derive the 32 byte RNG seed for a site. The site is trimmed and
+    /// lowercased, as domain names are case insensitive; the login is used
+    /// as given.
+    pub fn seed(&self, master: &str, site: &str, login: &str, counter: u32) -> Result<Zeroizing<[u8; 32]>, DeriveError> {
+        if master.is_empty() {
+            return Err(DeriveError::EmptyMaster);
+        }
+        let site = normalize_site(site);
+        if site.is_empty() {
+            return Err(DeriveError::EmptySite);
+        }
+
+        //length prefixes keep ("ab", "c") and ("a", "bc") apart
+        let mut salt = Sha256::new();
+        salt.update(DOMAIN);
+        for field in [site.as_bytes(), login.as_bytes()] {
+            salt.update((field.len() as u32).to_be_bytes());
+            salt.update(field);
+        }
+
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| DeriveError::Params(e.to_string()))?;
+        let mut key = Zeroizing::new([0u8; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(master.as_bytes(), &salt.finalize(), key.as_mut())
+            .map_err(|e| DeriveError::Params(e.to_string()))?;
+
+        let mut info = DOMAIN.to_vec();
+        info.extend(b" seed");
+        info.extend(counter.to_be_bytes());
+        let mut seed = Zeroizing::new([0u8; 32]);
+        Hkdf::<Sha256>::new(None, key.as_ref())
+            .expand(&info, seed.as_mut())
+            .expect("32 bytes is a valid HKDF-SHA256 length");
+
+        Ok(seed)
+    }
+
+    /// The RNG to set on a Specifier for a site.
+    pub fn rng(&self, master: &str, site: &str, login: &str, counter: u32) -> Result<ChaCha20Rng, DeriveError> {
+        self.seed(master, site, login, counter).map(|seed| ChaCha20Rng::from_seed(*seed))
+    }
+}
+
+/// The site as it goes into the derivation.
+pub fn normalize_site(site: &str) -> String {
+    site.trim().to_lowercase()
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum DeriveError {
+    EmptyMaster,
+    EmptySite,
+    /// The Argon2id parameters are out of range.
+    Params(String)
+}
+
+impl Display for DeriveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeriveError::EmptyMaster => write!(f, "the master secret is empty"),
+            DeriveError::EmptySite => write!(f, "the site is empty"),
+            DeriveError::Params(message) => write!(f, "derivation parameters: {}", message)
+        }
+    }
+}
+
+impl std::error::Error for DeriveError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Specifier;
+
+    /// Cheap parameters, to keep the tests fast.
+    const FAST: Derivation = Derivation { memory_kib: 1024, iterations: 1, parallelism: 1 };
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn derive(derivation: Derivation, spec: &str, site: &str, login: &str, counter: u32) -> String {
+        let mut specifier = Specifier::try_parse(spec).unwrap();
+        specifier.set_rng(derivation.rng("correct horse battery staple", site, login, counter).unwrap());
+        specifier.get_passphrase().unwrap().expose().to_string()
+    }
+
+    #[test]
+    fn test_vectors() {
+        let seed = FAST.seed("correct horse battery staple", "example.com", "alice", 1).unwrap();
+        assert_eq!(hex(seed.as_ref()), "b613854b93764a5cf9c35d40ed02dc054f44a845cd63877cb7b4bb8ebabc091d");
+        assert_eq!(derive(FAST, "i w w ##$", "example.com", "alice", 1), "Care fund base 40&");
+        assert_eq!(derive(FAST, "xxxxxxxxxxxxxxxx", "example.com", "alice", 2), "iVKBQjYPWROSu1D9");
+        assert_eq!(derive(FAST, "?WwaA$#z", "example.org", "", 1), "3Xscope$DOGzs");
+
+        let seed = Derivation::default().seed("correct horse battery staple", "example.com", "alice", 1).unwrap();
+        assert_eq!(hex(seed.as_ref()), "18f92100dd0069b747b4f578b9c4f161a3bb71b22376078c97ce56673f5693c9");
+        let mut specifier = Specifier::try_parse("i w w ##$").unwrap();
+        specifier.set_rng(ChaCha20Rng::from_seed(*seed));
+        assert_eq!(specifier.get_passphrase().unwrap().expose(), "Arm crime issue 80#");
+    }
+
+    #[test]
+    fn test_inputs_change_seed() {
+        let seed = |site: &str, login: &str, counter: u32| *FAST.seed("master", site, login, counter).unwrap();
+        let base = seed("example.com", "alice", 1);
+
+        assert_eq!(seed("  Example.COM ", "alice", 1), base);
+        assert_ne!(seed("example.com", "Alice", 1), base);
+        assert_ne!(seed("example.com", "alice", 2), base);
+        assert_ne!(seed("example.co", "malice", 1), base);
+        assert_ne!(*FAST.seed("master2", "example.com", "alice", 1).unwrap(), base);
+        assert_ne!(*Derivation { iterations: 2, ..FAST }.seed("master", "example.com", "alice", 1).unwrap(), base);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(FAST.seed("", "example.com", "alice", 1).err(), Some(DeriveError::EmptyMaster));
+        assert_eq!(FAST.seed("master", " ", "alice", 1).err(), Some(DeriveError::EmptySite));
+        assert!(matches!(Derivation { memory_kib: 1, ..FAST }.seed("master", "a", "b", 1), Err(DeriveError::Params(_))));
+    }
+}
@@ -0,0 +1,189 @@
+//! [`Filter`] trait
+//! [`FilterRegistry`] Struct
+//!
+//! Filters transform a generated passphrase into another representation,
+//! such as a hash or an encoding, for use in templates and config files.
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use sha2::{Digest, Sha256, Sha512};
+use crate::hash::HashAlgorithm;
+use crate::passphrase::Passphrase;
+use crate::spoken::Speller;
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum FilterError {
+    UnknownFilter(String),
+    Failed { filter: String, message: String }
+}
+
+impl Display for FilterError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FilterError::UnknownFilter(name) => write!(f, "unknown filter '{}'", name),
+            FilterError::Failed { filter, message } => write!(f, "filter '{}' failed: {}", filter, message)
+        }
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+/// The shared trait for all filters.
+pub trait Filter {
+    /// Transform the value. Errors are reported as a message, which the
+    /// registry wraps with the filter name.
+    fn apply(&self, value: &str) -> Result<Passphrase, String>;
+}
+
+impl<F: Fn(&str) -> Result<Passphrase, String>> Filter for F {
+    fn apply(&self, value: &str) -> Result<Passphrase, String> {
+        self(value)
+    }
+}
+
+/// A set of named filters.
+#[derive(Clone, Default)]
+pub struct FilterRegistry {
+    filters: HashMap<String, Rc<dyn Filter>>
+}
+
+impl FilterRegistry {
+    /// Create a registry with no filters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a registry with the built-in filters:
+    /// `upper`, `lower`, `hex`, `base64`, `base64url`, `url`, `json`,
+    /// `sha256`, `sha512`, the password hashes `argon2id`, `bcrypt`,
+    /// `sha512crypt` and `pbkdf2` (see [`HashAlgorithm`]), and `spell`, which
+    /// spells the value out in English (see [`Speller`]).
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        registry.register("upper", Rc::new(|v: &str| Ok(Passphrase::new(v.to_uppercase()))));
+        registry.register("lower", Rc::new(|v: &str| Ok(Passphrase::new(v.to_lowercase()))));
+        registry.register("hex", Rc::new(|v: &str| Ok(Passphrase::new(hex(v.as_bytes())))));
+        registry.register("base64", Rc::new(|v: &str| Ok(Passphrase::new(STANDARD.encode(v)))));
+        registry.register("base64url", Rc::new(|v: &str| Ok(Passphrase::new(URL_SAFE_NO_PAD.encode(v)))));
+        registry.register("url", Rc::new(|v: &str| Ok(Passphrase::new(url_encode(v)))));
+        registry.register("json", Rc::new(|v: &str| Ok(Passphrase::new(json_string(v)))));
+        registry.register("sha256", Rc::new(|v: &str| Ok(Passphrase::new(hex(&Sha256::digest(v))))));
+        registry.register("sha512", Rc::new(|v: &str| Ok(Passphrase::new(hex(&Sha512::digest(v))))));
+        for algorithm in HashAlgorithm::ALL {
+            registry.register(algorithm.name(), Rc::new(move |v: &str| {
+                algorithm.hash(v).map(Passphrase::new).map_err(|e| e.message)
+            }));
+        }
+        registry.register("spell", Rc::new(Speller::default()));
+        registry
+    }
+
+    /// Add a filter, replacing any existing filter with the same name.
+    pub fn register(&mut self, name: &str, filter: Rc<dyn Filter>) {
+        self.filters.insert(name.to_string(), filter);
+    }
+
+    /// Returns true if a filter with this name is registered.
+    pub fn contains(&self, name: &str) -> bool {
+        self.filters.contains_key(name)
+    }
+
+    /// Apply the named filter to a value.
+    pub fn apply(&self, name: &str, value: &str) -> Result<Passphrase, FilterError> {
+        let filter = self.filters.get(name)
+                                 .ok_or_else(|| FilterError::UnknownFilter(name.to_string()))?;
+        filter.apply(value)
+              .map_err(|message| FilterError::Failed { filter: name.to_string(), message })
+    }
+
+    /// Apply a chain of filters, left to right.
+    pub fn apply_all(&self, names: &[impl AsRef<str>], value: &str) -> Result<Passphrase, FilterError> {
+        let mut current = Passphrase::from(value);
+        for name in names {
+            current = self.apply(name.as_ref(), current.expose())?;
+        }
+        Ok(current)
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Percent-encode everything except the RFC 3986 unreserved characters.
+pub(crate) fn url_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len() * 3);
+    for b in value.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            encoded.push(b as char);
+        }
+        else {
+            encoded.push_str(&format!("%{:02X}", b));
+        }
+    }
+    encoded
+}
+
+/// Quote a value as a JSON string literal.
+fn json_string(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len() + 2);
+    encoded.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => encoded.push_str("\\\""),
+            '\\' => encoded.push_str("\\\\"),
+            '\n' => encoded.push_str("\\n"),
+            '\r' => encoded.push_str("\\r"),
+            '\t' => encoded.push_str("\\t"),
+            c if (c as u32) < 0x20 => encoded.push_str(&format!("\\u{:04x}", c as u32)),
+            c => encoded.push(c)
+        }
+    }
+    encoded.push('"');
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::DEFAULT_BCRYPT_COST;
+
+    #[test]
+    fn default_filters() {
+        let registry = FilterRegistry::with_defaults();
+        let apply = |name: &str, value: &str| registry.apply(name, value).unwrap().expose().to_string();
+
+        assert_eq!(apply("upper", "Medium test"), "MEDIUM TEST");
+        assert_eq!(apply("base64", "Medium test 123!"), "TWVkaXVtIHRlc3QgMTIzIQ==");
+        assert_eq!(apply("base64url", "??>"), "Pz8-");
+        assert_eq!(apply("hex", "a!"), "6121");
+        assert_eq!(apply("url", "a b&c=d/~"), "a%20b%26c%3Dd%2F~");
+        assert_eq!(apply("json", "say \"hi\"\\\n"), "\"say \\\"hi\\\"\\\\\\n\"");
+        assert_eq!(apply("sha256", "abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert!(apply("sha512", "abc").starts_with("ddaf35a193617aba"));
+        assert_eq!(apply("spell", "Q1"), "capital Quebec, one");
+    }
+
+    #[test]
+    fn bcrypt_verifies() {
+        let hashed = FilterRegistry::with_defaults().apply("bcrypt", "Medium test 123!").unwrap();
+        assert!(hashed.expose().starts_with(&format!("$2b${}$", DEFAULT_BCRYPT_COST)));
+        assert!(bcrypt::verify("Medium test 123!", hashed.expose()).unwrap());
+        assert!(!bcrypt::verify("Medium test 124!", hashed.expose()).unwrap());
+    }
+
+    #[test]
+    fn chains_and_errors() {
+        let mut registry = FilterRegistry::with_defaults();
+        registry.register("fail", Rc::new(|_: &str| Err("nope".to_string())));
+
+        assert_eq!(registry.apply_all(&["upper", "base64"], "ab").unwrap().expose(), "QUI=");
+        assert_eq!(registry.apply_all(&[] as &[&str], "ab").unwrap().expose(), "ab");
+        assert_eq!(registry.apply("rot13", "ab"), Err(FilterError::UnknownFilter("rot13".to_string())));
+        assert_eq!(registry.apply("fail", "ab").unwrap_err().to_string(), "filter 'fail' failed: nope");
+        assert!(registry.contains("bcrypt"));
+    }
+}
@@ -0,0 +1,264 @@
+//! [`HashAlgorithm`] Enum
+//!
+//! Hash passphrases into the standard encodings that systems store, so
+//! configuration only ever has to hold the hash:
+//! Argon2id and PBKDF2-SHA256 as PHC strings, bcrypt as `$2b$` and SHA-512
+//! crypt as `$6$`, as used in `/etc/shadow`.
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use argon2::{Argon2, PasswordHasher, PasswordVerifier};
+use argon2::password_hash::{PasswordHash, SaltString};
+use pbkdf2::Pbkdf2;
+use rand::{thread_rng, RngCore};
+use sha_crypt::Sha512Params;
+
+pub mod legacy;
+
+/// Salt length in bytes for Argon2id and PBKDF2.
+const SALT_LEN: usize = 16;
+
+/// The bcrypt cost used when none is given, and by the `bcrypt` filter.
+pub const DEFAULT_BCRYPT_COST: u32 = 12;
+
+/// The longest passphrase in bytes bcrypt hashes in full. It ignores the
+/// rest, so longer passphrases are refused rather than silently weakened.
+pub const MAX_BCRYPT_LENGTH: usize = 71;
+
+/// The password hash encodings that can be produced.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HashAlgorithm {
+    /// Argon2id PHC string, `$argon2id$v=19$m=...`.
+    Argon2id,
+    /// bcrypt, `$2b$12$...`.
+    Bcrypt,
+    /// SHA-512 crypt, `$6$...`.
+    Sha512Crypt,
+    /// PBKDF2-HMAC-SHA256 PHC string, `$pbkdf2-sha256$i=...`.
+    Pbkdf2
+}
+
+impl HashAlgorithm {
+    pub const ALL: [HashAlgorithm; 4] = [HashAlgorithm::Argon2id, HashAlgorithm::Bcrypt,
+                                         HashAlgorithm::Sha512Crypt, HashAlgorithm::Pbkdf2];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Argon2id => "argon2id",
+            HashAlgorithm::Bcrypt => "bcrypt",
+            HashAlgorithm::Sha512Crypt => "sha512crypt",
+            HashAlgorithm::Pbkdf2 => "pbkdf2"
+        }
+    }
+
+    /// The work factor used by [`HashAlgorithm::hash`]: Argon2id passes,
+    /// the bcrypt cost, SHA-512 crypt rounds or PBKDF2 iterations.
+    pub fn default_cost(self) -> u32 {
+        match self {
+            HashAlgorithm::Argon2id => argon2::Params::DEFAULT_T_COST,
+            HashAlgorithm::Bcrypt => DEFAULT_BCRYPT_COST,
+            HashAlgorithm::Sha512Crypt => sha_crypt::ROUNDS_DEFAULT as u32,
+            HashAlgorithm::Pbkdf2 => pbkdf2::Params::RECOMMENDED_ROUNDS as u32
+        }
+    }
+
+    /// Hash a passphrase with a random salt and the default cost.
+    pub fn hash(self, passphrase: &str) -> Result<String, HashError> {
+        self.hash_with_cost(passphrase, self.default_cost())
+    }
+
+    /// Hash a passphrase with a random salt and the given work factor.
+    pub fn hash_with_cost(self, passphrase: &str, cost: u32) -> Result<String, HashError> {
+        let error = |message: String| HashError { algorithm: self, message };
+        let mut salt = [0u8; SALT_LEN];
+        thread_rng().fill_bytes(&mut salt);
+
+        match self {
+            HashAlgorithm::Argon2id => {
+                let params = argon2::Params::new(argon2::Params::DEFAULT_M_COST, cost,
+                                                 argon2::Params::DEFAULT_P_COST, None)
+                    .map_err(|e| error(e.to_string()))?;
+                let salt = SaltString::encode_b64(&salt).map_err(|e| error(e.to_string()))?;
+                Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+                    .hash_password(passphrase.as_bytes(), &salt)
+                    .map(|h| h.to_string())
+                    .map_err(|e| error(e.to_string()))
+            }
+            HashAlgorithm::Bcrypt => {
+                bcrypt::non_truncating_hash_with_salt(passphrase, cost, salt)
+                    .map(|h| h.format_for_version(bcrypt::Version::TwoB))
+                    .map_err(|e| match e {
+                        bcrypt::BcryptError::Truncation(_) => {
+                            error(format!("the passphrase is {} bytes, but bcrypt only uses the first {}",
+                                          passphrase.len(), MAX_BCRYPT_LENGTH))
+                        }
+                        e => error(e.to_string())
+                    })
+            }
+            HashAlgorithm::Sha512Crypt => {
+                let params = Sha512Params::new(cost as usize).map_err(|e| error(format!("{:?}", e)))?;
+                sha_crypt::sha512_simple(passphrase, &params).map_err(|e| error(format!("{:?}", e)))
+            }
+            HashAlgorithm::Pbkdf2 => {
+                let params = pbkdf2::Params { rounds: cost, output_length: 32 };
+                let salt = SaltString::encode_b64(&salt).map_err(|e| error(e.to_string()))?;
+                Pbkdf2.hash_password_customized(passphrase.as_bytes(), Some(pbkdf2::Algorithm::PBKDF2_SHA256_IDENT),
+                                                None, params, &salt)
+                      .map(|h| h.to_string())
+                      .map_err(|e| error(e.to_string()))
+            }
+        }
+    }
+
+    /// Check a passphrase against a hash produced by this algorithm.
+    /// Returns an Err if the hash is malformed.
+    pub fn verify(self, passphrase: &str, hash: &str) -> Result<bool, HashError> {
+        let error = |message: String| HashError { algorithm: self, message };
+
+        match self {
+            HashAlgorithm::Argon2id | HashAlgorithm::Pbkdf2 => {
+                let parsed = PasswordHash::new(hash).map_err(|e| error(e.to_string()))?;
+                let result = match self {
+                    HashAlgorithm::Argon2id => Argon2::default().verify_password(passphrase.as_bytes(), &parsed),
+                    _ => Pbkdf2.verify_password(passphrase.as_bytes(), &parsed)
+                };
+                match result {
+                    Ok(()) => Ok(true),
+                    Err(argon2::password_hash::Error::Password) => Ok(false),
+                    Err(e) => Err(error(e.to_string()))
+                }
+            }
+            HashAlgorithm::Bcrypt => bcrypt::verify(passphrase, hash).map_err(|e| error(e.to_string())),
+            HashAlgorithm::Sha512Crypt => {
+                let (params, salt, expected) = parse_sha512_crypt(hash)
+                    .ok_or_else(|| error("malformed hash".to_string()))?;
+                let actual = sha_crypt::sha512_crypt_b64(passphrase.as_bytes(), salt.as_bytes(), &params)
+                    .map_err(|e| error(format!("{:?}", e)))?;
+                Ok(actual == expected)
+            }
+        }
+    }
+}
+
+/// Split a `$6$[rounds=N$]salt$hash` string into its parameters, salt and hash.
+fn parse_sha512_crypt(hash: &str) -> Option<(Sha512Params, &str, &str)> {
+    let rest = hash.strip_prefix("$6$")?;
+    let (rounds, rest) = match rest.strip_prefix("rounds=") {
+        Some(rest) => {
+            let (rounds, rest) = rest.split_once('$')?;
+            (rounds.parse().ok()?, rest)
+        }
+        None => (sha_crypt::ROUNDS_DEFAULT, rest)
+    };
+    let (salt, expected) = rest.split_once('$')?;
+
+    Some((Sha512Params::new(rounds).ok()?, salt, expected))
+}
+
+impl Display for HashAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct UnknownAlgorithm(pub String);
+
+impl Display for UnknownAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let names: Vec<&str> = HashAlgorithm::ALL.iter().map(|a| a.name()).collect();
+        write!(f, "unknown hash algorithm '{}', expected one of {}", self.0, names.join(", "))
+    }
+}
+
+impl std::error::Error for UnknownAlgorithm {}
+
+impl FromStr for HashAlgorithm {
+    type Err = UnknownAlgorithm;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        HashAlgorithm::ALL.into_iter()
+                          .find(|a| a.name().eq_ignore_ascii_case(s))
+                          .ok_or_else(|| UnknownAlgorithm(s.to_string()))
+    }
+}
+
+/// A passphrase could not be hashed or verified, for example because the
+/// cost is out of range for the algorithm.
+#[derive(Debug, Eq, PartialEq)]
+pub struct HashError {
+    pub algorithm: HashAlgorithm,
+    pub message: String
+}
+
+impl Display for HashError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.algorithm, self.message)
+    }
+}
+
+impl std::error::Error for HashError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The lowest cost each algorithm accepts, to keep the tests fast.
+    fn low_cost(algorithm: HashAlgorithm) -> u32 {
+        match algorithm {
+            HashAlgorithm::Argon2id => 1,
+            HashAlgorithm::Bcrypt => 4,
+            HashAlgorithm::Sha512Crypt => 1000,
+            HashAlgorithm::Pbkdf2 => 1000
+        }
+    }
+
+    #[test]
+    fn hashes_and_verifies() {
+        for algorithm in HashAlgorithm::ALL {
+            let hash = algorithm.hash_with_cost("Medium test 12!", low_cost(algorithm)).unwrap();
+
+            assert!(algorithm.verify("Medium test 12!", &hash).unwrap(), "{}", hash);
+            assert!(!algorithm.verify("Medium test 13!", &hash).unwrap(), "{}", hash);
+            assert_ne!(hash, algorithm.hash_with_cost("Medium test 12!", low_cost(algorithm)).unwrap());
+        }
+    }
+
+    #[test]
+    fn encodings() {
+        let hash = |a: HashAlgorithm| a.hash_with_cost("pw", low_cost(a)).unwrap();
+
+        assert!(hash(HashAlgorithm::Argon2id).starts_with("$argon2id$v=19$m=19456,t=1,p=1$"));
+        assert!(hash(HashAlgorithm::Bcrypt).starts_with("$2b$04$"));
+        assert!(hash(HashAlgorithm::Sha512Crypt).starts_with("$6$rounds=1000$"));
+        assert!(hash(HashAlgorithm::Pbkdf2).starts_with("$pbkdf2-sha256$i=1000,l=32$"));
+    }
+
+    #[test]
+    fn known_hashes() {
+        // from the SHA-crypt specification
+        assert!(HashAlgorithm::Sha512Crypt.verify("Hello world!", "$6$saltstring$svn8UoSVapNtMuq1ukKS4tPQd8iKwSMHWjl/O8\
+                                                                   17G3uBnIFNjnQJuesI68u4OTLiBFdcbYEdFCoEOfaS35inz1")
+                                          .unwrap());
+        assert!(HashAlgorithm::Sha512Crypt.verify("Hello world!", "$6$rounds=10000$saltstringsaltst$OW1/O6BYHV6BcXZu8QVe\
+                                                                   XbDWra3Oeqh0sbHbbMCVNSnCM/UrjmM0Dp8vOuZeHBy/YTBmSK6H9qs/\
+                                                                   y3RnOaw5v.")
+                                          .unwrap());
+        // from the passlib test suite
+        assert!(HashAlgorithm::Bcrypt.verify("U*U", "$2a$05$CCCCCCCCCCCCCCCCCCCCC.E5YPO9kmyuRGyh0XouQYb4YMJKvyOeW")
+                                     .unwrap());
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!("SHA512CRYPT".parse(), Ok(HashAlgorithm::Sha512Crypt));
+        assert!("md5".parse::<HashAlgorithm>().is_err());
+        assert!(HashAlgorithm::Bcrypt.hash_with_cost("pw", 99).is_err());
+        let longest = "a".repeat(MAX_BCRYPT_LENGTH);
+        assert!(HashAlgorithm::Bcrypt.hash_with_cost(&longest, 4).is_ok());
+        let e = HashAlgorithm::Bcrypt.hash_with_cost(&"a".repeat(73), 4).unwrap_err();
+        assert_eq!(e.message, "the passphrase is 73 bytes, but bcrypt only uses the first 71");
+        assert!(HashAlgorithm::Argon2id.verify("pw", "not a hash").is_err());
+        assert!(HashAlgorithm::Sha512Crypt.verify("pw", "$6$rounds=x$salt$hash").is_err());
+    }
+}
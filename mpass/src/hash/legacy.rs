@@ -0,0 +1,100 @@
+//! Legacy password hashes still found in Apache htpasswd files.
+//!
+//! These are weak and only provided for compatibility with servers that
+//! can't check bcrypt; prefer [`HashAlgorithm`](super::HashAlgorithm).
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use md5::{Digest, Md5};
+use rand::{thread_rng, Rng};
+use sha1::Sha1;
+
+/// The alphabet of crypt salts and hashes.
+const CRYPT_ALPHABET: &[u8; 64] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+const APR1_PREFIX: &str = "$apr1$";
+const APR1_SALT_LEN: usize = 8;
+
+/// Hash a passphrase as Apache's MD5 crypt, `$apr1$salt$hash`, with a random salt.
+pub fn apr1(passphrase: &str) -> String {
+    let mut rng = thread_rng();
+    let salt: String = (0..APR1_SALT_LEN).map(|_| CRYPT_ALPHABET[rng.gen_range(0..64)] as char).collect();
+
+    apr1_with_salt(passphrase, &salt)
+}
+
+/// Hash a passphrase as Apache's MD5 crypt with the given salt, of which at
+/// most 8 characters are used.
+pub fn apr1_with_salt(passphrase: &str, salt: &str) -> String {
+    let password = passphrase.as_bytes();
+    let salt = &salt.as_bytes()[..salt.len().min(APR1_SALT_LEN)];
+
+    let alternate = Md5::new().chain_update(password).chain_update(salt).chain_update(password).finalize();
+    let mut context = Md5::new().chain_update(password).chain_update(APR1_PREFIX).chain_update(salt);
+    for chunk in password.chunks(16) {
+        context.update(&alternate[..chunk.len()]);
+    }
+    let mut length = password.len();
+    while length > 0 {
+        if length & 1 == 1 {
+            context.update([0]);
+        }
+        else {
+            context.update(&password[..1]);
+        }
+        length >>= 1;
+    }
+    let mut digest = context.finalize();
+
+    //deliberately slow the hash down
+    for round in 0..1000 {
+        let mut context = Md5::new();
+        if round % 2 == 1 { context.update(password) } else { context.update(digest) }
+        if round % 3 != 0 { context.update(salt) }
+        if round % 7 != 0 { context.update(password) }
+        if round % 2 == 1 { context.update(digest) } else { context.update(password) }
+        digest = context.finalize();
+    }
+
+    let mut encoded = String::with_capacity(22);
+    for (a, b, c) in [(0, 6, 12), (1, 7, 13), (2, 8, 14), (3, 9, 15), (4, 10, 5)] {
+        let value = (digest[a] as u32) << 16 | (digest[b] as u32) << 8 | digest[c] as u32;
+        push_crypt64(&mut encoded, value, 4);
+    }
+    push_crypt64(&mut encoded, digest[11] as u32, 2);
+
+    format!("{}{}${}", APR1_PREFIX, String::from_utf8_lossy(salt), encoded)
+}
+
+/// Hash a passphrase as htpasswd's unsalted `{SHA}` base64 SHA-1 digest.
+pub fn htpasswd_sha1(passphrase: &str) -> String {
+    format!("{{SHA}}{}", STANDARD.encode(Sha1::digest(passphrase)))
+}
+
+fn push_crypt64(out: &mut String, mut value: u32, chars: usize) {
+    for _ in 0..chars {
+        out.push(CRYPT_ALPHABET[(value & 0x3f) as usize] as char);
+        value >>= 6;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apr1_known_hashes() {
+        // from the Apache documentation and openssl passwd -apr1
+        assert_eq!(apr1_with_salt("myPassword", "r31....."), "$apr1$r31.....$HqJZimcKQFAMYayBlzkrA/");
+        assert_eq!(apr1_with_salt("password", "saltsaltsalt"), "$apr1$saltsalt$yAAkm4libquA.ZWLHbSBq/");
+
+        let hash = apr1("pw");
+        assert_eq!(hash.len(), 6 + 8 + 1 + 22);
+        assert_eq!(apr1_with_salt("pw", &hash[6..14]), hash);
+    }
+
+    #[test]
+    fn sha1() {
+        assert_eq!(htpasswd_sha1("password"), "{SHA}W6ph5Mm5Pz8GgiULbPgzG37mj9g=");
+    }
+}
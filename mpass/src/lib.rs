@@ -4,11 +4,22 @@
 //! word lists, numbers, symbols, and other tokens.
 
 
+pub mod analyzer;
+pub mod apikey;
+pub mod check;
+pub mod derive;
+pub mod filter;
+pub mod hash;
+pub mod passphrase;
 pub mod rtg;
 pub mod specifier;
+pub mod spoken;
+pub mod totp;
 
+pub use passphrase::Passphrase;
 pub use specifier::Specifier;
 pub use rtg::RandomTokenGenerator;
+pub use spoken::Speller;
 
 
 
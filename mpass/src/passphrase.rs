@@ -0,0 +1,76 @@
+//! [`Passphrase`] Struct
+
+use std::fmt::{Debug, Formatter};
+use zeroize::Zeroize;
+
+/// A generated secret.
+///
+/// The contents are wiped from memory when the Passphrase is dropped and are
+/// never shown by Debug. Use [`Passphrase::expose`] to read the value.
+#[derive(Clone, Default, Eq, PartialEq)]
+pub struct Passphrase {
+    secret: String
+}
+
+impl Passphrase {
+    /// Create a new Passphrase, taking ownership of the String.
+    pub fn new(secret: String) -> Self {
+        Passphrase {
+            secret
+        }
+    }
+
+    /// Get the secret value.
+    pub fn expose(&self) -> &str {
+        &self.secret
+    }
+
+    /// Length of the secret in bytes.
+    pub fn len(&self) -> usize {
+        self.secret.len()
+    }
+
+    /// Returns true if the secret is empty.
+    pub fn is_empty(&self) -> bool {
+        self.secret.is_empty()
+    }
+}
+
+impl From<String> for Passphrase {
+    fn from(value: String) -> Self {
+        Passphrase::new(value)
+    }
+}
+
+impl From<&str> for Passphrase {
+    fn from(value: &str) -> Self {
+        Passphrase::new(value.to_string())
+    }
+}
+
+impl Debug for Passphrase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Passphrase(REDACTED)")
+    }
+}
+
+impl Drop for Passphrase {
+    fn drop(&mut self) {
+        self.secret.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debug_is_redacted() {
+        let p = Passphrase::from("Medium test 123!");
+
+        assert_eq!(format!("{:?}", p), "Passphrase(REDACTED)");
+        assert_eq!(format!("{:?}", Some(p.clone())), "Some(Passphrase(REDACTED))");
+        assert_eq!(p.expose(), "Medium test 123!");
+        assert_eq!(p.len(), 16);
+    }
+}
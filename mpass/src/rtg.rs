@@ -1,17 +1,32 @@
 //! [`RandomTokenGenerator`] trait
 //! [`RTG`] Struct
+pub mod bytes;
 pub mod default_lists;
 pub mod randomcap;
 pub use randomcap::RandomCapWordGenerator;
 
 use std::fmt::{Display, Formatter};
-use rand::{Rng, thread_rng};
+use rand::{Rng, RngCore};
+use crate::passphrase::Passphrase;
 
 /// The shared trait for all token generators.
 pub trait RandomTokenGenerator : Display {
-    /// Gets a token. The rules for the provided token are
-    /// dependent on the trait implementor.
-    fn get_token(&self) -> String;
+    /// Gets a token using the provided random number generator. The rules
+    /// for the provided token are dependent on the trait implementor.
+    fn get_token(&self, rng: &mut dyn RngCore) -> Passphrase;
+
+    /// Entropy of a single token in bits. Defaults to 0, which leaves the
+    /// token out of entropy estimates.
+    fn entropy(&self) -> f64 {
+        0.0
+    }
+
+    /// Lengths in bytes of every token this generator could produce that
+    /// the candidate string starts with. Defaults to none, so passphrases
+    /// are never decomposed into or matched against the token.
+    fn match_lengths(&self, _candidate: &str) -> Vec<usize> {
+        Vec::new()
+    }
 }
 
 /// Handy implementation of RandomTokenGenerator
@@ -41,10 +56,24 @@ impl From<Vec<String>> for RTG {
 }
 
 impl RandomTokenGenerator for RTG {
-    fn get_token(&self) -> String {
-        let idx: usize = thread_rng().gen_range(0 .. self.token_list.len());
+    fn get_token(&self, rng: &mut dyn RngCore) -> Passphrase {
+        let idx: usize = rng.gen_range(0 .. self.token_list.len());
         let tok = self.token_list.get(idx).unwrap_or(&String::from("")).clone();
-        tok
+        Passphrase::new(tok)
+    }
+
+    fn entropy(&self) -> f64 {
+        (self.token_list.len() as f64).log2()
+    }
+
+    fn match_lengths(&self, candidate: &str) -> Vec<usize> {
+        let mut lengths: Vec<usize> = self.token_list.iter()
+                                          .filter(|t| !t.is_empty() && candidate.starts_with(t.as_str()))
+                                          .map(|t| t.len())
+                                          .collect();
+        lengths.sort();
+        lengths.dedup();
+        lengths
     }
 }
 
@@ -59,6 +88,7 @@ impl Display for RTG {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::thread_rng;
 
     #[test]
     fn retrieval_works() {
@@ -68,7 +98,7 @@ mod tests {
         println!("RTG: {}", tester);
 
         for _ in 0..100 {
-            assert!(tokens.contains(&tester.get_token().as_str()));
+            assert!(tokens.contains(&tester.get_token(&mut thread_rng()).expose()));
         }
     }
 
@@ -81,4 +111,32 @@ mod tests {
 
         assert_eq!(disp, "RTG(4)");
     }
+
+    #[test]
+    fn matching_works() {
+        let tester = RTG::new(vec!["car", "cart", "art"]);
+
+        assert_eq!(tester.match_lengths("cartoon"), vec![3, 4]);
+        assert_eq!(tester.match_lengths("artist"), vec![3]);
+        assert!(tester.match_lengths("ca").is_empty());
+        assert_eq!(tester.entropy(), 3f64.log2());
+    }
+
+    #[test]
+    fn get_token_is_enough() {
+        struct Fixed;
+        impl Display for Fixed {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                write!(f, "Fixed")
+            }
+        }
+        impl RandomTokenGenerator for Fixed {
+            fn get_token(&self, _rng: &mut dyn RngCore) -> Passphrase {
+                Passphrase::new("fixed".to_string())
+            }
+        }
+
+        assert_eq!(Fixed.entropy(), 0.0);
+        assert!(Fixed.match_lengths("fixed").is_empty());
+    }
 }
@@ -0,0 +1,228 @@
+//! [`ByteGenerator`] Struct
+//! [`UuidGenerator`] Struct
+//!
+//! Raw random keys rather than passphrases: a number of random bytes in one
+//! of the usual text encodings, or a version 4 UUID.
+
+use std::fmt::{Display, Formatter};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use rand::RngCore;
+use zeroize::Zeroizing;
+use crate::passphrase::Passphrase;
+use crate::rtg::RandomTokenGenerator;
+
+/// Bytes in a byte token without a count, 128 bits.
+pub const DEFAULT_BYTES: usize = 16;
+
+/// Most bytes a single byte token can produce.
+pub const MAX_BYTES: usize = 1024;
+
+const HEX: &[u8; 16] = b"0123456789abcdef";
+const RFC4648_BASE32: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const CROCKFORD_BASE32: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const BASE64URL: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Text encodings for random bytes. None of them are padded.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum ByteEncoding {
+    /// Lowercase hex.
+    Hex,
+    /// RFC 4648 base32, `A-Z2-7`, as used for TOTP secrets.
+    Base32,
+    /// Crockford base32, `0-9A-Z` without `I`, `L`, `O` and `U`.
+    Crockford,
+    /// RFC 4648 base64 with the URL and filename safe alphabet.
+    Base64Url
+}
+
+impl ByteEncoding {
+    /// Encode bytes, without padding.
+    pub fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            ByteEncoding::Hex => {
+                bytes.iter().flat_map(|b| [HEX[(b >> 4) as usize], HEX[(b & 0xf) as usize]]).map(char::from).collect()
+            }
+            ByteEncoding::Base32 => base32(bytes, RFC4648_BASE32),
+            ByteEncoding::Crockford => base32(bytes, CROCKFORD_BASE32),
+            ByteEncoding::Base64Url => URL_SAFE_NO_PAD.encode(bytes)
+        }
+    }
+
+    /// Length of the encoding of `bytes` bytes.
+    pub fn encoded_len(self, bytes: usize) -> usize {
+        match self {
+            ByteEncoding::Hex => bytes * 2,
+            ByteEncoding::Base32 | ByteEncoding::Crockford => (bytes * 8).div_ceil(5),
+            ByteEncoding::Base64Url => (bytes * 8).div_ceil(6)
+        }
+    }
+
+    fn alphabet(self) -> &'static [u8] {
+        match self {
+            ByteEncoding::Hex => HEX,
+            ByteEncoding::Base32 => RFC4648_BASE32,
+            ByteEncoding::Crockford => CROCKFORD_BASE32,
+            ByteEncoding::Base64Url => BASE64URL
+        }
+    }
+}
+
+/// Big-endian groups of five bits, the last one filled with zero bits.
+fn base32(bytes: &[u8], alphabet: &[u8; 32]) -> String {
+    let mut text = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let (mut buffer, mut bits) = (0u16, 0);
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u16;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            text.push(alphabet[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        text.push(alphabet[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    text
+}
+
+/// Produces a fixed number of random bytes in an encoding.
+pub struct ByteGenerator {
+    encoding: ByteEncoding,
+    bytes: usize
+}
+
+impl ByteGenerator {
+    pub fn new(encoding: ByteEncoding, bytes: usize) -> Self {
+        ByteGenerator { encoding, bytes }
+    }
+}
+
+impl RandomTokenGenerator for ByteGenerator {
+    fn get_token(&self, rng: &mut dyn RngCore) -> Passphrase {
+        let mut bytes = Zeroizing::new(vec![0u8; self.bytes]);
+        rng.fill_bytes(&mut bytes);
+        Passphrase::new(self.encoding.encode(&bytes))
+    }
+
+    fn entropy(&self) -> f64 {
+        self.bytes as f64 * 8.0
+    }
+
+    fn match_lengths(&self, candidate: &str) -> Vec<usize> {
+        let len = self.encoding.encoded_len(self.bytes);
+        match candidate.as_bytes().get(..len) {
+            Some(text) if text.iter().all(|b| self.encoding.alphabet().contains(b)) => vec![len],
+            _ => Vec::new()
+        }
+    }
+}
+
+impl Display for ByteGenerator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Bytes({:?}, {})", self.encoding, self.bytes)
+    }
+}
+
+/// Produces RFC 4122 version 4 UUIDs, `xxxxxxxx-xxxx-4xxx-Vxxx-xxxxxxxxxxxx`
+/// with lowercase hex digits.
+pub struct UuidGenerator;
+
+/// Offsets of the dashes in a UUID.
+const UUID_DASHES: [usize; 4] = [8, 13, 18, 23];
+const UUID_LEN: usize = 36;
+
+impl RandomTokenGenerator for UuidGenerator {
+    fn get_token(&self, rng: &mut dyn RngCore) -> Passphrase {
+        let mut bytes = Zeroizing::new([0u8; 16]);
+        rng.fill_bytes(bytes.as_mut());
+        //version 4, variant 10xx
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+        let hex = Zeroizing::new(ByteEncoding::Hex.encode(bytes.as_ref()));
+        Passphrase::new(format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..]))
+    }
+
+    fn entropy(&self) -> f64 {
+        122.0
+    }
+
+    fn match_lengths(&self, candidate: &str) -> Vec<usize> {
+        let Some(text) = candidate.as_bytes().get(..UUID_LEN) else {
+            return Vec::new();
+        };
+        let valid = text.iter().enumerate().all(|(i, b)| match i {
+            _ if UUID_DASHES.contains(&i) => *b == b'-',
+            14 => *b == b'4',
+            19 => matches!(b, b'8' | b'9' | b'a' | b'b'),
+            _ => HEX.contains(b)
+        });
+
+        if valid { vec![UUID_LEN] } else { Vec::new() }
+    }
+}
+
+impl Display for UuidGenerator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "UUIDv4")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn encodings() {
+        //RFC 4648 test vectors, without padding
+        for (input, base32, base64) in [("", "", ""), ("f", "MY", "Zg"), ("fo", "MZXQ", "Zm8"),
+                                        ("foo", "MZXW6", "Zm9v"), ("foob", "MZXW6YQ", "Zm9vYg"),
+                                        ("fooba", "MZXW6YTB", "Zm9vYmE"), ("foobar", "MZXW6YTBOI", "Zm9vYmFy")] {
+            assert_eq!(ByteEncoding::Base32.encode(input.as_bytes()), base32);
+            assert_eq!(ByteEncoding::Base64Url.encode(input.as_bytes()), base64);
+            assert_eq!(ByteEncoding::Base32.encoded_len(input.len()), base32.len());
+            assert_eq!(ByteEncoding::Base64Url.encoded_len(input.len()), base64.len());
+        }
+        assert_eq!(ByteEncoding::Hex.encode(&[0x00, 0x9f, 0xff]), "009fff");
+        assert_eq!(ByteEncoding::Crockford.encode(&[0xff; 5]), "ZZZZZZZZ");
+        assert_eq!(ByteEncoding::Crockford.encode(b"foobar"), "CSQPYRK1E8");
+        assert_eq!(ByteEncoding::Base64Url.encode(&[0xfb, 0xff]), "-_8");
+    }
+
+    #[test]
+    fn byte_tokens() {
+        let mut rng = ChaCha20Rng::seed_from_u64(9);
+        let generator = ByteGenerator::new(ByteEncoding::Base64Url, 32);
+        let token = generator.get_token(&mut rng);
+        assert_eq!(token.len(), 43);
+        assert_eq!(URL_SAFE_NO_PAD.decode(token.expose()).unwrap().len(), 32);
+        assert_eq!(generator.entropy(), 256.0);
+        assert_eq!(generator.match_lengths(token.expose()), vec![43]);
+        assert!(generator.match_lengths(&token.expose().replacen(|_| true, "+", 1)).is_empty());
+
+        let hex = ByteGenerator::new(ByteEncoding::Hex, 4).get_token(&mut rng);
+        assert!(hex.len() == 8 && hex.expose().bytes().all(|b| HEX.contains(&b)));
+        let crockford = ByteGenerator::new(ByteEncoding::Crockford, 20).get_token(&mut rng);
+        assert_eq!(crockford.len(), 32);
+        assert!(!crockford.expose().contains(['I', 'L', 'O', 'U']));
+    }
+
+    #[test]
+    fn uuids() {
+        let mut rng = ChaCha20Rng::seed_from_u64(9);
+        for _ in 0..20 {
+            let uuid = UuidGenerator.get_token(&mut rng);
+            let uuid = uuid.expose();
+            assert_eq!(UuidGenerator.match_lengths(uuid), vec![36]);
+            assert_eq!(uuid.chars().filter(|&c| c == '-').count(), 4);
+        }
+        assert!(UuidGenerator.match_lengths("f47ac10b-58cc-4372-a567-0e02b2c3d479").len() == 1);
+        assert!(UuidGenerator.match_lengths("f47ac10b-58cc-1372-a567-0e02b2c3d479").is_empty());
+        assert!(UuidGenerator.match_lengths("f47ac10b-58cc-4372-c567-0e02b2c3d479").is_empty());
+    }
+}
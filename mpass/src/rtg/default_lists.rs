@@ -96,3 +96,11 @@ pub fn get_numbers() -> Vec<u8> {
     vec![0,1,2,3,4,5,6,7,8,9]
 }
 
+/// Get a list of upper and lowercase letters and digits, as used by the
+/// `x` spec token
+pub fn get_alphanumeric() -> Vec<String> {
+    let mut alphanum: Vec<String> = get_alphabet().iter().map(|s| s.to_string()).collect();
+    alphanum.extend(get_numbers().iter().map(|s| s.to_string()));
+    alphanum
+}
+
@@ -1,7 +1,9 @@
 //! [`RTG`] Struct
 
 use std::fmt::{Display, Formatter};
-use rand::{Rng, thread_rng};
+use rand::{Rng, RngCore};
+use zeroize::Zeroizing;
+use crate::passphrase::Passphrase;
 use crate::rtg::RandomTokenGenerator;
 
 /// A random token generator that produces a word
@@ -31,15 +33,40 @@ impl From<Vec<String>> for RandomCapWordGenerator {
 }
 
 impl RandomTokenGenerator for RandomCapWordGenerator {
-    fn get_token(&self) -> String {
-        let word_idx: usize = thread_rng().gen_range(0 .. self.token_list.len());
-        let mut tok_ch: Vec<char> = self.token_list.get(word_idx).unwrap_or(&String::from("_")).clone()
-                                 .chars().collect();
+    fn get_token(&self, rng: &mut dyn RngCore) -> Passphrase {
+        let word_idx: usize = rng.gen_range(0 .. self.token_list.len());
+        let mut tok_ch: Zeroizing<Vec<char>> = Zeroizing::new(self.token_list.get(word_idx).unwrap_or(&String::from("_")).clone()
+                                 .chars().collect());
 
-        let char_idx: usize = thread_rng().gen_range(0 .. tok_ch.len());
+        let char_idx: usize = rng.gen_range(0 .. tok_ch.len());
         tok_ch[char_idx] = tok_ch[char_idx].to_ascii_uppercase();
 
-        tok_ch.into_iter().collect()
+        Passphrase::new(tok_ch.iter().collect())
+    }
+
+    fn entropy(&self) -> f64 {
+        //any word, then any letter within it
+        let choices: usize = self.token_list.iter().map(|t| t.chars().count()).sum();
+        (choices as f64).log2()
+    }
+
+    fn match_lengths(&self, candidate: &str) -> Vec<usize> {
+        let mut lengths: Vec<usize> = self.token_list.iter()
+            .filter(|t| {
+                let Some(prefix) = candidate.get(..t.len()) else {
+                    return false;
+                };
+                if t.is_empty() || prefix.to_ascii_lowercase() != **t {
+                    return false;
+                }
+                let capitals = prefix.chars().zip(t.chars()).filter(|(p, w)| p != w).count();
+                capitals == 1 || (capitals == 0 && t.chars().any(|c| !c.is_ascii_lowercase()))
+            })
+            .map(|t| t.len())
+            .collect();
+        lengths.sort();
+        lengths.dedup();
+        lengths
     }
 }
 
@@ -1,43 +1,105 @@
 //! Generate passphrases based on specification strings.
 
+pub mod breakdown;
+pub mod decompose;
 pub mod spectoken;
 pub mod specifier_error;
 
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::rc::Rc;
-use rand::thread_rng;
+use rand::{thread_rng, RngCore};
 use rand::seq::SliceRandom;
+use crate::check::PassphraseCheck;
+use crate::passphrase::Passphrase;
+use crate::specifier::breakdown::Breakdown;
 use crate::specifier::specifier_error::SpecifierError;
 use crate::specifier::spectoken::SpecToken;
 use crate::rtg::{RandomCapWordGenerator, RTG};
+use crate::rtg::bytes::{ByteGenerator, UuidGenerator};
 use crate::rtg::RandomTokenGenerator;
-use crate::rtg::default_lists::{get_alphabet, get_ez_ascii_symbols, get_lowercase, get_numbers, get_simpleton_words, get_uppercase};
+use crate::rtg::default_lists::{get_alphanumeric, get_ez_ascii_symbols, get_lowercase, get_numbers, get_simpleton_words, get_uppercase};
+
+/// The default number of times a rejected passphrase is regenerated
+/// before giving up.
+pub const DEFAULT_MAX_ATTEMPTS: usize = 100;
 
 /// Maintains the specifier token list as well as the RandomTokenGenerators
 /// uses to produce passphrases on demand.
 pub struct Specifier {
     shuffle: bool,
-    spec_tokens: Vec<Rc<dyn RandomTokenGenerator>>,
-    rtgs: HashMap<SpecToken, Rc<dyn RandomTokenGenerator>>
+    spec_tokens: Vec<SpecToken>,
+    rtgs: HashMap<SpecToken, Rc<dyn RandomTokenGenerator>>,
+    checks: Vec<Rc<dyn PassphraseCheck>>,
+    max_attempts: usize,
+    rng: RefCell<Box<dyn RngCore>>
 }
 
 
 impl Specifier {
     /// Use the rules encoded in the spec string to produce a passphrase.
-    pub fn get_passphrase(&self) -> String {
-        let mut p: Vec<String> = vec![];
+    /// Passphrases rejected by any added check are regenerated; an Err is
+    /// returned if no acceptable passphrase is found within the attempt limit.
+    pub fn get_passphrase(&self) -> Result<Passphrase, SpecifierError> {
+        self.get_breakdown().map(|b| b.passphrase)
+    }
+
+    /// Produce a passphrase like get_passphrase, along with the text
+    /// produced by each token.
+    pub fn get_breakdown(&self) -> Result<Breakdown, SpecifierError> {
+        for _ in 0..self.max_attempts {
+            let candidate = self.generate();
+            if !self.is_rejected(candidate.passphrase.expose())? {
+                return Ok(candidate);
+            }
+        }
+
+        Err(SpecifierError::AttemptsExhausted(self.max_attempts))
+    }
+
+    fn is_rejected(&self, passphrase: &str) -> Result<bool, SpecifierError> {
+        for check in self.checks.iter() {
+            if check.try_is_rejected(passphrase).map_err(SpecifierError::CheckFailed)? {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Add a check that every produced passphrase must pass.
+    pub fn add_check(&mut self, check: Rc<dyn PassphraseCheck>) {
+        self.checks.push(check);
+    }
+
+    /// Set how many times a rejected passphrase is regenerated before
+    /// get_passphrase gives up.
+    pub fn set_max_attempts(&mut self, max_attempts: usize) {
+        self.max_attempts = max_attempts;
+    }
+
+    /// Replace the random number generator used to produce passphrases.
+    /// By default the thread-local generator from rand is used; a seeded
+    /// generator makes the output reproducible.
+    pub fn set_rng(&mut self, rng: impl RngCore + 'static) {
+        self.rng = RefCell::new(Box::new(rng));
+    }
 
-        for r in self.spec_tokens.iter() {
-            p.push(r.get_token());
+    fn generate(&self) -> Breakdown {
+        let mut rng = self.rng.borrow_mut();
+        //each token wipes itself when the buffer is dropped
+        let mut p: Vec<(SpecToken, Passphrase)> = vec![];
+
+        for t in self.spec_tokens.iter() {
+            p.push((*t, self.rtgs[t].get_token(rng.as_mut())));
         }
 
         if self.shuffle {
-            let mut rng = thread_rng();
-            p.shuffle(&mut rng);
+            p.shuffle(rng.as_mut());
         }
 
-        p.join("")
+        Breakdown::from_parts(p)
     }
 
     /// Try to parse a spec string a build a Specifier using the default word
@@ -53,7 +115,7 @@ impl Specifier {
     /// and symbol lists.
     /// Returns a Result containing a new Specifier or an Error with failure details.
     pub fn try_parse_custom(spec_string: &str, word_list: Vec<String>, symbol_list: Vec<String>) -> Result<Self, SpecifierError> {
-        if word_list.is_empty() || symbol_list.is_empty() {
+        if word_list.is_empty() {
             return Err(SpecifierError::EmptyWordList);
         }
         if symbol_list.is_empty() {
@@ -97,25 +159,29 @@ impl Specifier {
                     Rc::new(RTG::new(get_lowercase())));
         rtgs.insert(SpecToken::UppercaseLetter,
                     Rc::new(RTG::new(get_uppercase())));
-        let mut alphanum: Vec<String> = get_alphabet().iter().map(|s| s.to_string()).collect();
-        alphanum.extend(get_numbers().iter().map(|s| s.to_string()));
+        let alphanum = get_alphanumeric();
         let mut alphanumsym = alphanum.clone();
         alphanumsym.extend(symbol_list.clone());
         rtgs.insert(SpecToken::AlphaNumChar,
                     Rc::new(RTG::new(alphanum)));
         rtgs.insert(SpecToken::AnyChar,
                     Rc::new(RTG::new(alphanumsym)));
+        rtgs.insert(SpecToken::Uuid, Rc::new(UuidGenerator));
         //add a dummy RTG for the shuffle token
         rtgs.insert(SpecToken::Shuffle, Rc::new(RTG::new(vec![true])));
 
 
-        let spec_tokens = Self::tokenize(spec_string, &rtgs)?;
+        let spec_tokens = Self::tokenize(spec_string)?;
         let shuffle = spec_string.contains("?");
+        Self::add_byte_rtgs(&mut rtgs, &spec_tokens);
 
         Ok(Specifier {
             shuffle,
             spec_tokens,
-            rtgs
+            rtgs,
+            checks: Vec::new(),
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            rng: RefCell::new(Box::new(thread_rng()))
         })
     }
 
@@ -124,18 +190,8 @@ impl Specifier {
     /// invalid character. If the string is empty or only has shuffle tokens,
     /// it will return a value equal to the length of the spec string.
     pub fn check_spec_string(spec_string: &str) -> Result<(), usize> {
-        let allowed = "wWaAirxz#$ ?";
-        let mut shuffle_token_count: usize = 0;
-        for (index, ch) in spec_string.char_indices() {
-            if !allowed.contains(ch) {
-                return Err(index);
-            }
-            if ch == '?' {
-                shuffle_token_count += 1;
-            }
-        }
-
-        if spec_string.len() - shuffle_token_count < 1 {
+        let tokens = SpecToken::parse(spec_string)?;
+        if tokens.iter().all(|t| *t == SpecToken::Shuffle) {
             return Err(spec_string.len());
         }
 
@@ -146,31 +202,62 @@ impl Specifier {
     /// Returns a Result with either Ok or an Err containing the offset of the first
     /// invalid character. The original spec string is not changed on failure.
     pub fn try_change_spec_string(&mut self, spec_string: &str) -> Result<(), SpecifierError>{
-        self.spec_tokens = Self::tokenize(spec_string, &self.rtgs)?;
+        self.spec_tokens = Self::tokenize(spec_string)?;
+        self.shuffle = spec_string.contains("?");
+        Self::add_byte_rtgs(&mut self.rtgs, &self.spec_tokens);
         Ok(())
     }
 
+    /// Get the tokens of the current spec string, in order, without shuffle tokens.
+    pub fn spec_tokens(&self) -> &[SpecToken] {
+        &self.spec_tokens
+    }
 
-    fn tokenize(spec_string: &str, rtgs: &HashMap<SpecToken, Rc<dyn RandomTokenGenerator>>) -> Result<Vec<Rc<dyn RandomTokenGenerator>>, SpecifierError> {
-        let mut spec_tokens: Vec<Rc<dyn RandomTokenGenerator>> = Vec::new();
+    /// Estimate the entropy in bits of passphrases produced by this Specifier.
+    /// Shuffling adds the number of distinct orderings of the token types.
+    pub fn entropy(&self) -> f64 {
+        let mut bits: f64 = self.spec_tokens.iter().map(|t| self.rtgs[t].entropy()).sum();
 
-        for (index, ch) in spec_string.char_indices() {
-            if let Ok(tok) = SpecToken::try_from(ch){
-                spec_tokens.push(rtgs[&tok].clone());
-            }
-            else {
-                return Err(SpecifierError::UnrecognizedChar(index))
+        if self.shuffle {
+            let mut counts: HashMap<SpecToken, usize> = HashMap::new();
+            for t in self.spec_tokens.iter() {
+                *counts.entry(*t).or_insert(0) += 1;
             }
+            let log2_factorial = |n: usize| (2..=n).map(|x| (x as f64).log2()).sum::<f64>();
+            bits += log2_factorial(self.spec_tokens.len());
+            bits -= counts.values().map(|c| log2_factorial(*c)).sum::<f64>();
         }
 
+        bits
+    }
+
+    /// Returns true if the spec string contains a shuffle token.
+    pub fn is_shuffled(&self) -> bool {
+        self.shuffle
+    }
+
+    fn tokenize(spec_string: &str) -> Result<Vec<SpecToken>, SpecifierError> {
+        let mut spec_tokens = SpecToken::parse(spec_string).map_err(SpecifierError::UnrecognizedChar)?;
+        //the shuffle token only sets a flag and produces no output
+        spec_tokens.retain(|t| *t != SpecToken::Shuffle);
+
         Ok(spec_tokens)
     }
+
+    /// Byte tokens carry their count, so each count used needs its own generator.
+    fn add_byte_rtgs(rtgs: &mut HashMap<SpecToken, Rc<dyn RandomTokenGenerator>>, spec_tokens: &[SpecToken]) {
+        for t in spec_tokens {
+            if let SpecToken::Bytes(encoding, bytes) = *t {
+                rtgs.entry(*t).or_insert_with(|| Rc::new(ByteGenerator::new(encoding, bytes)));
+            }
+        }
+    }
 }
 
 impl Display for Specifier {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         for s in &self.spec_tokens {
-            write!(f, "{} ", s)?
+            write!(f, "{} ", self.rtgs[s])?
         }
         Ok(())
     }
@@ -191,11 +278,17 @@ impl Display for Specifier {
 //     # - digit, 0-9
 //     $ - symbol
 //     (space) - space character
+//     h - 16 random bytes in hex; a count before it sets the number of bytes (32h)
+//     B - random bytes in RFC 4648 base32, 16 or the count before it
+//     c - random bytes in Crockford base32, 16 or the count before it
+//     b - random bytes in base64url, 16 or the count before it
+//     u - random version 4 UUID
 //     ? - shuffle the sequence (if present, the token order will be randomized)
 // Examples:
 //     "i w w ###$" => "Medium test phrase 123!"
 //     "ii##$" => "TestPhrase11#"
 //     "zzzzzz" => "t7Eq#r"
+//     "32b" => "q3R0eJ3m7cWjz3yE-Mg2oV0b1KcGf5lWl9rC2cYbXn4"
 
 #[cfg(test)]
 mod tests {
@@ -212,12 +305,123 @@ mod tests {
     fn test_passphrase() {
         let tester = Specifier::try_parse("w W i r a A $ # x z").unwrap();
 
-        println!("Passphrase: {}", tester.get_passphrase());
+        println!("Passphrase: {}", tester.get_passphrase().unwrap().expose());
+    }
+
+    #[test]
+    fn test_seeded_rng() {
+        use rand::SeedableRng;
+        use rand::rngs::StdRng;
+
+        let mut first = Specifier::try_parse("?i w r a A $ # x z").unwrap();
+        let mut second = Specifier::try_parse("?i w r a A $ # x z").unwrap();
+        first.set_rng(StdRng::seed_from_u64(42));
+        second.set_rng(StdRng::seed_from_u64(42));
+
+        for _ in 0..10 {
+            assert_eq!(first.get_passphrase().unwrap(), second.get_passphrase().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_byte_tokens() {
+        use base64::Engine;
+        use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+
+        let mut tester = Specifier::try_parse("32b u h").unwrap();
+        assert_eq!(tester.entropy(), 256.0 + 122.0 + 128.0);
+        let breakdown = tester.get_breakdown().unwrap();
+        assert_eq!(breakdown.spec_string(), "32b u 16h");
+        assert_eq!(URL_SAFE_NO_PAD.decode(breakdown.parts[0].1.expose()).unwrap().len(), 32);
+        assert_eq!(breakdown.passphrase.len(), 43 + 1 + 36 + 1 + 32);
+
+        tester.try_change_spec_string("20B").unwrap();
+        assert_eq!(tester.get_passphrase().unwrap().len(), 32);
+        assert_eq!(Specifier::check_spec_string("i 20c"), Ok(()));
+        assert_eq!(Specifier::check_spec_string("i 20#"), Err(2));
+        assert_eq!(Specifier::try_parse("ww0h").err(), Some(SpecifierError::UnrecognizedChar(2)));
+    }
+
+    #[test]
+    fn test_empty_symbol_list() {
+        let words = vec!["test".to_string()];
+        assert_eq!(Specifier::try_parse_custom("w", words.clone(), vec![]).err(), Some(SpecifierError::EmptySymbolList));
+        assert_eq!(Specifier::try_parse_custom("w", vec![], words).err(), Some(SpecifierError::EmptyWordList));
     }
 
     #[test]
     fn test_shuffle() {
         let tester = Specifier::try_parse("?aaa###").unwrap();
-        todo!()
+        let mut reordered = false;
+
+        for _ in 0..100 {
+            let p = tester.get_passphrase().unwrap();
+            let p = p.expose();
+            assert_eq!(p.len(), 6);
+            assert_eq!(p.chars().filter(|c| c.is_ascii_lowercase()).count(), 3);
+            assert_eq!(p.chars().filter(|c| c.is_ascii_digit()).count(), 3);
+
+            let in_order = p.chars().take(3).all(|c| c.is_ascii_lowercase());
+            reordered |= !in_order;
+        }
+
+        assert!(reordered);
+    }
+
+    #[test]
+    fn test_breakdown() {
+        let tester = Specifier::try_parse("?i##$").unwrap();
+
+        for _ in 0..20 {
+            let breakdown = tester.get_breakdown().unwrap();
+            let joined: String = breakdown.parts.iter().map(|(_, t)| t.expose()).collect();
+            assert_eq!(joined, breakdown.passphrase.expose());
+
+            let mut spec: Vec<char> = breakdown.spec_string().chars().collect();
+            spec.sort();
+            assert_eq!(spec, vec!['#', '#', '$', 'i']);
+            for (token, text) in &breakdown.parts {
+                assert_eq!(*token == SpecToken::PropercaseWord, text.len() > 1);
+            }
+        }
+    }
+
+    struct RejectDigits;
+
+    impl PassphraseCheck for RejectDigits {
+        fn is_rejected(&self, passphrase: &str) -> bool {
+            passphrase.contains(|c: char| c.is_ascii_digit() && c != '7')
+        }
+    }
+
+    struct RejectAll;
+
+    impl PassphraseCheck for RejectAll {
+        fn is_rejected(&self, _passphrase: &str) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_check_regenerates() {
+        let mut tester = Specifier::try_parse("#").unwrap();
+        tester.add_check(Rc::new(RejectDigits));
+        tester.set_max_attempts(10_000);
+
+        for _ in 0..10 {
+            assert_eq!(tester.get_passphrase().unwrap().expose(), "7");
+        }
+    }
+
+    #[test]
+    fn test_check_exhausted() {
+        let mut tester = Specifier::try_parse("aa").unwrap();
+        tester.add_check(Rc::new(RejectDigits));
+        assert!(tester.get_passphrase().is_ok());
+
+        let mut tester = Specifier::try_parse("##").unwrap();
+        tester.add_check(Rc::new(RejectAll));
+        tester.set_max_attempts(5);
+        assert_eq!(tester.get_passphrase(), Err(SpecifierError::AttemptsExhausted(5)));
     }
 }
@@ -0,0 +1,31 @@
+//! Generated passphrases together with the [`SpecToken`]s that produced them.
+
+use crate::passphrase::Passphrase;
+use crate::specifier::spectoken::SpecToken;
+
+/// A generated passphrase split into the part produced by each token.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Breakdown {
+    pub passphrase: Passphrase,
+    /// Each token and the text it produced, in passphrase order. With a
+    /// shuffled spec this differs from the spec order.
+    pub parts: Vec<(SpecToken, Passphrase)>
+}
+
+impl Breakdown {
+    /// Join the parts into a breakdown.
+    pub(crate) fn from_parts(parts: Vec<(SpecToken, Passphrase)>) -> Self {
+        //reserve the full length up front so no partial copies are left behind by reallocation
+        let mut joined = String::with_capacity(parts.iter().map(|(_, t)| t.len()).sum());
+        for (_, t) in parts.iter() {
+            joined.push_str(t.expose());
+        }
+
+        Breakdown { passphrase: Passphrase::new(joined), parts }
+    }
+
+    /// Get the spec string of the tokens in passphrase order.
+    pub fn spec_string(&self) -> String {
+        self.parts.iter().map(|(t, _)| t.to_string()).collect()
+    }
+}
@@ -0,0 +1,293 @@
+//! Reverse-parse passphrases into the [`SpecToken`]s that could have produced them.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use crate::specifier::Specifier;
+use crate::specifier::spectoken::SpecToken;
+
+/// Upper bound on the number of decompositions returned for one passphrase.
+pub const MAX_DECOMPOSITIONS: usize = 1000;
+
+/// Tokens tried when decomposing a passphrase. The catch-all `x` and `z`
+/// tokens are left out since every character they match is already matched
+/// by a narrower token; use [`Specifier::could_generate`] to test them.
+const DECOMPOSE_TOKENS: [SpecToken; 9] = [
+    SpecToken::LowercaseWord,
+    SpecToken::UppercaseWord,
+    SpecToken::PropercaseWord,
+    SpecToken::RandomCapitalWord,
+    SpecToken::LowercaseLetter,
+    SpecToken::UppercaseLetter,
+    SpecToken::Digit,
+    SpecToken::Symbol,
+    SpecToken::Space,
+];
+
+/// One way of splitting a passphrase into spec tokens.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Decomposition {
+    pub tokens: Vec<SpecToken>,
+    /// Entropy in bits of a passphrase generated by these tokens.
+    pub entropy: f64
+}
+
+impl Decomposition {
+    /// Get the spec string for this decomposition.
+    pub fn spec_string(&self) -> String {
+        self.tokens.iter().map(|t| t.as_char()).collect()
+    }
+}
+
+/// A split of the start of a passphrase, waiting in the search queue.
+struct Partial {
+    /// Entropy of the tokens so far plus the lowest entropy of the rest.
+    bound: f64,
+    /// Push order, so equal bounds come out first in first out.
+    order: usize,
+    offset: usize,
+    entropy: f64,
+    tokens: Vec<SpecToken>
+}
+
+impl Ord for Partial {
+    //reversed, BinaryHeap pops the greatest
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.bound.total_cmp(&self.bound).then(other.order.cmp(&self.order))
+    }
+}
+
+impl PartialOrd for Partial {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Partial {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Partial {}
+
+impl Specifier {
+    /// Find every way the passphrase could be split into spec tokens using
+    /// this Specifier's token lists. Results are sorted by entropy, so the most
+    /// likely spec comes first. Only the [`MAX_DECOMPOSITIONS`] with the lowest
+    /// entropy are returned.
+    ///
+    /// Examples (default lists):
+    ///     "Castle test 123!" => "i w ###$", "r w ###$", "Aaaaaa w ###$", ...
+    pub fn decompose(&self, passphrase: &str) -> Vec<Decomposition> {
+        // every token that can start at each byte offset, with its length and entropy
+        let end = passphrase.len();
+        let mut edges: HashMap<usize, Vec<(SpecToken, usize, f64)>> = HashMap::new();
+        for (offset, _) in passphrase.char_indices() {
+            let rest = &passphrase[offset..];
+            let e = edges.entry(offset).or_default();
+            for tok in DECOMPOSE_TOKENS {
+                let entropy = self.rtgs[&tok].entropy();
+                for len in self.rtgs[&tok].match_lengths(rest) {
+                    e.push((tok, len, entropy));
+                }
+            }
+        }
+
+        // the lowest entropy from each offset to the end, infinite for dead ends
+        let mut rest_entropy = vec![f64::INFINITY; end + 1];
+        rest_entropy[end] = 0.0;
+        for (offset, _) in passphrase.char_indices().rev() {
+            rest_entropy[offset] = edges[&offset].iter()
+                                                 .map(|(_, len, entropy)| entropy + rest_entropy[offset + len])
+                                                 .fold(f64::INFINITY, f64::min);
+        }
+
+        // best first: a partial split is ordered by the lowest entropy it can
+        // still reach, so complete splits come out lowest entropy first
+        let mut results = Vec::new();
+        let mut queue = BinaryHeap::new();
+        let mut order = 0;
+        if rest_entropy[0].is_finite() {
+            queue.push(Partial { bound: rest_entropy[0], order, offset: 0, entropy: 0.0, tokens: Vec::new() });
+        }
+        while let Some(partial) = queue.pop() {
+            if partial.offset == end {
+                results.push(Decomposition { tokens: partial.tokens, entropy: partial.entropy });
+                if results.len() == MAX_DECOMPOSITIONS {
+                    break;
+                }
+                continue;
+            }
+            for (tok, len, entropy) in &edges[&partial.offset] {
+                let offset = partial.offset + len;
+                if rest_entropy[offset].is_infinite() {
+                    continue;
+                }
+                let mut tokens = partial.tokens.clone();
+                tokens.push(*tok);
+                order += 1;
+                queue.push(Partial {
+                    bound: partial.entropy + entropy + rest_entropy[offset],
+                    order,
+                    offset,
+                    entropy: partial.entropy + entropy,
+                    tokens
+                });
+            }
+        }
+
+        //bounds are sums in a different order, so smooth over rounding
+        results.sort_by(|a: &Decomposition, b| a.entropy.total_cmp(&b.entropy));
+        results
+    }
+
+    /// Returns true if this Specifier's current spec string could have
+    /// produced the passphrase. Shuffled specs match any token order.
+    pub fn could_generate(&self, passphrase: &str) -> bool {
+        if self.shuffle {
+            let mut remaining: Vec<(SpecToken, usize)> = Vec::new();
+            for t in self.spec_tokens.iter() {
+                match remaining.iter_mut().find(|(r, _)| r == t) {
+                    Some((_, count)) => *count += 1,
+                    None => remaining.push((*t, 1))
+                }
+            }
+            let mut failed = HashSet::new();
+            self.match_any_order(passphrase, 0, &mut remaining, &mut failed)
+        }
+        else {
+            let mut failed = HashSet::new();
+            self.match_in_order(passphrase, 0, 0, &mut failed)
+        }
+    }
+
+    fn match_in_order(&self, passphrase: &str, offset: usize, index: usize,
+                      failed: &mut HashSet<(usize, usize)>) -> bool {
+        if index == self.spec_tokens.len() {
+            return offset == passphrase.len();
+        }
+        if failed.contains(&(offset, index)) {
+            return false;
+        }
+
+        let rtg = &self.rtgs[&self.spec_tokens[index]];
+        for len in rtg.match_lengths(&passphrase[offset..]) {
+            if self.match_in_order(passphrase, offset + len, index + 1, failed) {
+                return true;
+            }
+        }
+
+        failed.insert((offset, index));
+        false
+    }
+
+    fn match_any_order(&self, passphrase: &str, offset: usize,
+                       remaining: &mut Vec<(SpecToken, usize)>,
+                       failed: &mut HashSet<(usize, Vec<usize>)>) -> bool {
+        if remaining.iter().all(|(_, count)| *count == 0) {
+            return offset == passphrase.len();
+        }
+        let state = (offset, remaining.iter().map(|(_, count)| *count).collect::<Vec<usize>>());
+        if failed.contains(&state) {
+            return false;
+        }
+
+        for i in 0..remaining.len() {
+            if remaining[i].1 == 0 {
+                continue;
+            }
+            let rtg = self.rtgs[&remaining[i].0].clone();
+            for len in rtg.match_lengths(&passphrase[offset..]) {
+                remaining[i].1 -= 1;
+                let matched = self.match_any_order(passphrase, offset + len, remaining, failed);
+                remaining[i].1 += 1;
+                if matched {
+                    return true;
+                }
+            }
+        }
+
+        failed.insert(state);
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decompose_finds_spec() {
+        let tester = Specifier::try_parse("w").unwrap();
+        let parses = tester.decompose("Medium test 123!");
+        let specs: Vec<String> = parses.iter().map(|d| d.spec_string()).collect();
+
+        assert!(specs.contains(&"Aaaaaa w ###$".to_string()));
+        assert!(specs.contains(&"Aaaaaa aaaa ###$".to_string()));
+        assert!(!specs.iter().any(|s| s.starts_with('i')));
+        assert!(specs.iter().all(|s| s.len() >= 8));
+        for pair in parses.windows(2) {
+            assert!(pair[0].entropy <= pair[1].entropy);
+        }
+
+        let parses = tester.decompose("Castle test 123!");
+        assert_eq!(parses[0].spec_string(), "i w ###$");
+        assert!(parses.iter().any(|d| d.spec_string() == "r w ###$"));
+    }
+
+    #[test]
+    fn decompose_no_match() {
+        let tester = Specifier::try_parse("w").unwrap();
+
+        assert!(tester.decompose("tab\there").is_empty());
+        assert!(tester.decompose("").len() == 1);
+    }
+
+    #[test]
+    fn decompose_keeps_lowest_entropy() {
+        //each word is a word token or four letter tokens, 1024 splits in all
+        let tester = Specifier::try_parse("w").unwrap();
+        let passphrase = ["test"; 10].join(" ");
+        let parses = tester.decompose(&passphrase);
+        assert_eq!(parses.len(), MAX_DECOMPOSITIONS);
+        assert_eq!(parses[0].spec_string(), ["w"; 10].join(" "));
+        for pair in parses.windows(2) {
+            assert!(pair[0].entropy <= pair[1].entropy);
+        }
+
+        //the cut off ones all have more entropy than any returned
+        let words_only = tester.rtgs[&SpecToken::LowercaseWord].entropy() * 10.0
+            + tester.rtgs[&SpecToken::Space].entropy() * 9.0;
+        assert_eq!(parses[0].entropy, words_only);
+        let letters_only = ["aaaa"; 10].join(" ");
+        assert!(!parses.iter().any(|d| d.spec_string() == letters_only));
+    }
+
+    #[test]
+    fn could_generate() {
+        let tester = Specifier::try_parse("i w ###$").unwrap();
+        assert!(tester.could_generate("Castle test 123!"));
+        assert!(!tester.could_generate("Castle test 12!"));
+        assert!(!tester.could_generate("castle test 123!"));
+
+        let tester = Specifier::try_parse("zzzz").unwrap();
+        assert!(tester.could_generate("a7#Q"));
+        assert!(!tester.could_generate("a7#"));
+
+        let tester = Specifier::try_parse("?ww##").unwrap();
+        assert!(tester.could_generate("4car7tree"));
+        assert!(tester.could_generate("cartree47"));
+        assert!(!tester.could_generate("car4tree"));
+    }
+
+    #[test]
+    fn generated_passphrases_decompose() {
+        let tester = Specifier::try_parse("?i w r a A $ # x z").unwrap();
+
+        for _ in 0..20 {
+            let p = tester.get_passphrase().unwrap();
+            assert!(tester.could_generate(p.expose()), "{} not matched", p.expose());
+            assert!(!tester.decompose(p.expose()).is_empty());
+        }
+    }
+}
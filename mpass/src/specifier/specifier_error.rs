@@ -1,3 +1,4 @@
+use std::fmt::{Display, Formatter};
 use crate::specifier::spectoken::SpecTokenError;
 
 #[derive(Debug, Eq, PartialEq)]
@@ -5,7 +6,10 @@ pub enum SpecifierError {
     SpecTokenError(SpecTokenError),
     EmptySymbolList,
     EmptyWordList,
-    UnrecognizedChar(usize)
+    UnrecognizedChar(usize),
+    AttemptsExhausted(usize),
+    /// A check could not be made, with the reason.
+    CheckFailed(String)
 }
 
 
@@ -14,3 +18,18 @@ impl From<SpecTokenError> for SpecifierError {
         SpecifierError::SpecTokenError(value)
     }
 }
+
+impl Display for SpecifierError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpecifierError::SpecTokenError(e) => write!(f, "{}", e),
+            SpecifierError::EmptySymbolList => write!(f, "the symbol list is empty"),
+            SpecifierError::EmptyWordList => write!(f, "the word list is empty"),
+            SpecifierError::UnrecognizedChar(offset) => write!(f, "unrecognized character at offset {}", offset),
+            SpecifierError::AttemptsExhausted(attempts) => write!(f, "no acceptable passphrase found after {} attempts", attempts),
+            SpecifierError::CheckFailed(message) => write!(f, "passphrase check failed: {}", message)
+        }
+    }
+}
+
+impl std::error::Error for SpecifierError {}
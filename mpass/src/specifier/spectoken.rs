@@ -1,5 +1,7 @@
+use std::fmt::{Display, Formatter};
+use crate::rtg::bytes::{ByteEncoding, DEFAULT_BYTES, MAX_BYTES};
 
-#[derive(Eq, PartialEq, Hash, Debug)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
 pub enum SpecToken {
     LowercaseWord,
     UppercaseWord,
@@ -12,11 +14,79 @@ pub enum SpecToken {
     Digit,
     Symbol,
     Space,
+    /// A number of random bytes in an encoding.
+    Bytes(ByteEncoding, usize),
+    Uuid,
     Shuffle,
 }
 
 impl SpecToken {
+    /// Get the spec string character for this token.
+    pub fn as_char(&self) -> char {
+        match self {
+            SpecToken::LowercaseWord => 'w',
+            SpecToken::UppercaseWord => 'W',
+            SpecToken::PropercaseWord => 'i',
+            SpecToken::RandomCapitalWord => 'r',
+            SpecToken::LowercaseLetter => 'a',
+            SpecToken::UppercaseLetter => 'A',
+            SpecToken::AlphaNumChar => 'x',
+            SpecToken::AnyChar => 'z',
+            SpecToken::Digit => '#',
+            SpecToken::Symbol => '$',
+            SpecToken::Space => ' ',
+            SpecToken::Bytes(ByteEncoding::Hex, _) => 'h',
+            SpecToken::Bytes(ByteEncoding::Base32, _) => 'B',
+            SpecToken::Bytes(ByteEncoding::Crockford, _) => 'c',
+            SpecToken::Bytes(ByteEncoding::Base64Url, _) => 'b',
+            SpecToken::Uuid => 'u',
+            SpecToken::Shuffle => '?',
+        }
+    }
+
+    /// Split a spec string into tokens, including shuffle tokens. Byte
+    /// tokens may have a count of bytes before them, as in `32h`.
+    /// Returns the offset of the first invalid character on failure; for a
+    /// bad count, that of its first digit.
+    pub fn parse(spec_string: &str) -> Result<Vec<SpecToken>, usize> {
+        let mut tokens = Vec::new();
+        let mut count: Option<(usize, usize)> = None;
+
+        for (index, ch) in spec_string.char_indices() {
+            if let Some(digit) = ch.to_digit(10) {
+                let (start, n) = count.unwrap_or((index, 0));
+                count = Some((start, (n * 10 + digit as usize).min(MAX_BYTES + 1)));
+                continue;
+            }
 
+            let tok = SpecToken::try_from(ch).map_err(|_| index)?;
+            match (tok, count.take()) {
+                (SpecToken::Bytes(encoding, _), Some((start, n))) => {
+                    if n == 0 || n > MAX_BYTES {
+                        return Err(start);
+                    }
+                    tokens.push(SpecToken::Bytes(encoding, n));
+                }
+                (_, Some((start, _))) => return Err(start),
+                (tok, None) => tokens.push(tok)
+            }
+        }
+
+        match count {
+            Some((start, _)) => Err(start),
+            None => Ok(tokens)
+        }
+    }
+}
+
+impl Display for SpecToken {
+    /// The token as it is written in a spec string, with the count for byte tokens.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpecToken::Bytes(_, bytes) => write!(f, "{}{}", bytes, self.as_char()),
+            _ => write!(f, "{}", self.as_char())
+        }
+    }
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -24,6 +94,16 @@ pub enum SpecTokenError {
     UnrecognizedToken(char)
 }
 
+impl Display for SpecTokenError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpecTokenError::UnrecognizedToken(c) => write!(f, "unrecognized token '{}'", c)
+        }
+    }
+}
+
+impl std::error::Error for SpecTokenError {}
+
 impl TryFrom<char> for SpecToken {
     type Error = SpecTokenError;
 
@@ -40,8 +120,43 @@ impl TryFrom<char> for SpecToken {
             '#' => Ok(SpecToken::Digit),
             '$' => Ok(SpecToken::Symbol),
             ' ' => Ok(SpecToken::Space),
+            'h' => Ok(SpecToken::Bytes(ByteEncoding::Hex, DEFAULT_BYTES)),
+            'B' => Ok(SpecToken::Bytes(ByteEncoding::Base32, DEFAULT_BYTES)),
+            'c' => Ok(SpecToken::Bytes(ByteEncoding::Crockford, DEFAULT_BYTES)),
+            'b' => Ok(SpecToken::Bytes(ByteEncoding::Base64Url, DEFAULT_BYTES)),
+            'u' => Ok(SpecToken::Uuid),
             '?' => Ok(SpecToken::Shuffle),
-            _ => return Err(SpecTokenError::UnrecognizedToken(value))
+            _ => Err(SpecTokenError::UnrecognizedToken(value))
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_counts() {
+        assert_eq!(SpecToken::parse("?i 32b u"), Ok(vec![SpecToken::Shuffle, SpecToken::PropercaseWord, SpecToken::Space,
+                                                       SpecToken::Bytes(ByteEncoding::Base64Url, 32),
+                                                       SpecToken::Space, SpecToken::Uuid]));
+        assert_eq!(SpecToken::parse("h20B"), Ok(vec![SpecToken::Bytes(ByteEncoding::Hex, DEFAULT_BYTES),
+                                                    SpecToken::Bytes(ByteEncoding::Base32, 20)]));
+        assert_eq!(SpecToken::parse("1024c"), Ok(vec![SpecToken::Bytes(ByteEncoding::Crockford, 1024)]));
+
+        assert_eq!(SpecToken::parse("w 3#"), Err(2));
+        assert_eq!(SpecToken::parse("w0h"), Err(1));
+        assert_eq!(SpecToken::parse("1025h"), Err(0));
+        assert_eq!(SpecToken::parse("99999999999999999999999h"), Err(0));
+        assert_eq!(SpecToken::parse("hh12"), Err(2));
+        assert_eq!(SpecToken::parse("w-"), Err(1));
+        assert_eq!(SpecToken::parse("4u"), Err(0));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(SpecToken::Bytes(ByteEncoding::Base64Url, 32).to_string(), "32b");
+        assert_eq!(SpecToken::Uuid.to_string(), "u");
+        assert_eq!(SpecToken::Digit.to_string(), "#");
+    }
+}
@@ -0,0 +1,261 @@
+//! [`Speller`] Struct
+//! [`Language`] Enum
+//!
+//! Spell passphrases out for reading aloud, such as over the phone.
+//!
+//! Each character becomes one spoken word from a spelling alphabet, so in
+//! English `Ab7!` is spelled "capital Alpha, bravo, seven, exclamation mark".
+//! Each language has its own alphabet and names for digits and symbols, and
+//! any word can be replaced with [`Speller::set`].
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::filter::Filter;
+use crate::passphrase::Passphrase;
+
+/// The separator between spoken words in [`Speller::speak`].
+pub const SEPARATOR: &str = ", ";
+
+/// Languages with a built-in spelling alphabet.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Language {
+    /// The NATO phonetic alphabet.
+    #[default]
+    English,
+    /// The DIN 5009 alphabet.
+    German,
+    /// The traditional French telephone alphabet.
+    French
+}
+
+impl Language {
+    pub const ALL: [Language; 3] = [Language::English, Language::German, Language::French];
+
+    /// The ISO 639-1 code of the language.
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::German => "de",
+            Language::French => "fr"
+        }
+    }
+
+    fn table(self) -> &'static Table {
+        match self {
+            Language::English => &ENGLISH,
+            Language::German => &GERMAN,
+            Language::French => &FRENCH
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub struct UnknownLanguage(pub String);
+
+impl Display for UnknownLanguage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let codes: Vec<&str> = Language::ALL.iter().map(|l| l.code()).collect();
+        write!(f, "unknown language '{}', expected one of {}", self.0, codes.join(", "))
+    }
+}
+
+impl std::error::Error for UnknownLanguage {}
+
+impl FromStr for Language {
+    type Err = UnknownLanguage;
+
+    /// Parse a language from its ISO 639-1 code.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Language::ALL.into_iter()
+                     .find(|l| l.code().eq_ignore_ascii_case(s))
+                     .ok_or_else(|| UnknownLanguage(s.to_string()))
+    }
+}
+
+/// Spells text one character at a time.
+#[derive(Clone, Debug)]
+pub struct Speller {
+    words: HashMap<char, String>,
+    capital: String
+}
+
+impl Default for Speller {
+    fn default() -> Self {
+        Self::new(Language::English)
+    }
+}
+
+impl Speller {
+    /// Create a speller with the built-in words for a language.
+    pub fn new(language: Language) -> Self {
+        let table = language.table();
+        let letters = ('a'..='z').zip(table.letters);
+        let digits = ('0'..='9').zip(table.digits);
+        let words = letters.chain(digits)
+                           .chain(SYMBOLS.chars().zip(table.symbols))
+                           .chain(table.extra.iter().copied())
+                           .map(|(c, w)| (c, w.to_string()))
+                           .collect();
+
+        Speller { words, capital: table.capital.to_string() }
+    }
+
+    /// Set the word spoken for a character. Words for lowercase letters are
+    /// also used for their capitals.
+    pub fn set(&mut self, ch: char, word: impl ToString) {
+        self.words.insert(ch, word.to_string());
+    }
+
+    /// Set how capital letters are spoken, with `{}` standing for the word
+    /// of the lowercase letter, e.g. `"capital {}"`.
+    pub fn set_capital(&mut self, pattern: impl ToString) {
+        self.capital = pattern.to_string();
+    }
+
+    /// Spell text, returning one spoken word per character. Characters
+    /// without a word are quoted as they are.
+    pub fn spell(&self, text: &str) -> Vec<String> {
+        text.chars().map(|c| self.spell_char(c)).collect()
+    }
+
+    /// Spell text as a single comma separated string.
+    pub fn speak(&self, text: &str) -> Passphrase {
+        Passphrase::new(self.spell(text).join(SEPARATOR))
+    }
+
+    /// Spell a single character.
+    pub fn spell_char(&self, ch: char) -> String {
+        if let Some(word) = self.words.get(&ch) {
+            return word.clone();
+        }
+
+        let mut lower = ch.to_lowercase();
+        match (lower.next(), lower.next()) {
+            (Some(l), None) if l != ch && self.words.contains_key(&l) => {
+                self.capital.replace("{}", &capitalize(&self.words[&l]))
+            }
+            _ => format!("\"{}\"", ch)
+        }
+    }
+}
+
+impl Filter for Speller {
+    fn apply(&self, value: &str) -> Result<Passphrase, String> {
+        Ok(self.speak(value))
+    }
+}
+
+/// Spell text with the English NATO alphabet.
+pub fn spell(text: &str) -> Vec<String> {
+    Speller::default().spell(text)
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new()
+    }
+}
+
+/// The printable ASCII symbols, in the order of [`Table::symbols`].
+const SYMBOLS: &str = " !\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+
+struct Table {
+    letters: [&'static str; 26],
+    digits: [&'static str; 10],
+    symbols: [&'static str; 33],
+    /// Words for letters outside a-z.
+    extra: &'static [(char, &'static str)],
+    capital: &'static str
+}
+
+static ENGLISH: Table = Table {
+    letters: ["alpha", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel", "india", "juliett",
+              "kilo", "lima", "mike", "november", "oscar", "papa", "quebec", "romeo", "sierra", "tango",
+              "uniform", "victor", "whiskey", "x-ray", "yankee", "zulu"],
+    digits: ["zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine"],
+    symbols: ["space", "exclamation mark", "double quote", "hash", "dollar sign", "percent sign", "ampersand",
+              "apostrophe", "open parenthesis", "close parenthesis", "asterisk", "plus sign", "comma", "hyphen",
+              "period", "slash", "colon", "semicolon", "less-than sign", "equals sign", "greater-than sign",
+              "question mark", "at sign", "open bracket", "backslash", "close bracket", "caret", "underscore",
+              "backtick", "open brace", "vertical bar", "close brace", "tilde"],
+    extra: &[],
+    capital: "capital {}"
+};
+
+static GERMAN: Table = Table {
+    letters: ["Aachen", "Berlin", "Chemnitz", "Düsseldorf", "Essen", "Frankfurt", "Goslar", "Hamburg",
+              "Ingelheim", "Jena", "Köln", "Leipzig", "München", "Nürnberg", "Offenbach", "Potsdam", "Quickborn",
+              "Rostock", "Salzwedel", "Tübingen", "Unna", "Völklingen", "Wuppertal", "Xanten", "Ypsilon",
+              "Zwickau"],
+    digits: ["null", "eins", "zwo", "drei", "vier", "fünf", "sechs", "sieben", "acht", "neun"],
+    symbols: ["Leerzeichen", "Ausrufezeichen", "Anführungszeichen", "Raute", "Dollarzeichen", "Prozentzeichen",
+              "Und-Zeichen", "Apostroph", "Klammer auf", "Klammer zu", "Stern", "Plus", "Komma", "Bindestrich",
+              "Punkt", "Schrägstrich", "Doppelpunkt", "Semikolon", "Kleiner-als-Zeichen", "Gleichheitszeichen",
+              "Größer-als-Zeichen", "Fragezeichen", "At-Zeichen", "eckige Klammer auf", "Backslash",
+              "eckige Klammer zu", "Zirkumflex", "Unterstrich", "Gravis", "geschweifte Klammer auf",
+              "senkrechter Strich", "geschweifte Klammer zu", "Tilde"],
+    extra: &[('ä', "Umlaut Aachen"), ('ö', "Umlaut Offenbach"), ('ü', "Umlaut Unna"), ('ß', "Eszett")],
+    capital: "groß {}"
+};
+
+static FRENCH: Table = Table {
+    letters: ["Anatole", "Berthe", "Célestin", "Désiré", "Eugène", "François", "Gaston", "Henri", "Irma",
+              "Joseph", "Kléber", "Louis", "Marcel", "Nicolas", "Oscar", "Pierre", "Quintal", "Raoul",
+              "Suzanne", "Thérèse", "Ursule", "Victor", "William", "Xavier", "Yvonne", "Zoé"],
+    digits: ["zéro", "un", "deux", "trois", "quatre", "cinq", "six", "sept", "huit", "neuf"],
+    symbols: ["espace", "point d'exclamation", "guillemet", "dièse", "dollar", "pour cent", "esperluette",
+              "apostrophe", "parenthèse ouvrante", "parenthèse fermante", "astérisque", "plus", "virgule",
+              "tiret", "point", "barre oblique", "deux-points", "point-virgule", "inférieur à", "égal",
+              "supérieur à", "point d'interrogation", "arobase", "crochet ouvrant", "barre oblique inverse",
+              "crochet fermant", "accent circonflexe", "tiret bas", "accent grave", "accolade ouvrante",
+              "barre verticale", "accolade fermante", "tilde"],
+    extra: &[('à', "a accent grave"), ('ç', "c cédille"), ('é', "e accent aigu"), ('è', "e accent grave"),
+             ('ê', "e accent circonflexe")],
+    capital: "{} majuscule"
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spells_each_class() {
+        assert_eq!(spell("Ab7!"), vec!["capital Alpha", "bravo", "seven", "exclamation mark"]);
+        assert_eq!(spell("x y"), vec!["x-ray", "space", "yankee"]);
+        assert_eq!(spell("é"), vec!["\"é\""]);
+        assert_eq!(*Speller::default().speak("Hi5").expose(), *"capital Hotel, india, five");
+    }
+
+    #[test]
+    fn every_ascii_symbol_named() {
+        for language in Language::ALL {
+            let speller = Speller::new(language);
+            for ch in (0x20u8..0x7f).map(char::from) {
+                assert!(!speller.spell_char(ch).starts_with('"'), "{:?} has no name in {:?}", ch, language);
+            }
+        }
+    }
+
+    #[test]
+    fn languages() {
+        assert_eq!(Speller::new(Language::German).spell("Qü2"), vec!["groß Quickborn", "Umlaut Unna", "zwo"]);
+        assert_eq!(Speller::new(Language::German).spell_char('Ä'), "groß Umlaut Aachen");
+        assert_eq!(Speller::new(Language::French).spell("Zé?"), vec!["Zoé majuscule", "e accent aigu",
+                                                                       "point d'interrogation"]);
+
+        assert_eq!("DE".parse(), Ok(Language::German));
+        assert_eq!("xx".parse::<Language>(), Err(UnknownLanguage("xx".to_string())));
+    }
+
+    #[test]
+    fn custom_words() {
+        let mut speller = Speller::default();
+        speller.set('#', "pound");
+        speller.set_capital("uppercase {}");
+
+        assert_eq!(speller.spell("#A"), vec!["pound", "uppercase Alpha"]);
+    }
+}
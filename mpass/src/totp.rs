@@ -0,0 +1,352 @@
+//! [`Totp`] Struct
+//!
+//! Time-based one-time passwords (RFC 6238), for enrolling users in two-factor
+//! authentication along with their passwords: random secrets in base32, the
+//! `otpauth://` URIs authenticator apps scan, and the codes the apps will
+//! show, so a secret can be checked before it is handed out.
+
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+use zeroize::Zeroizing;
+use crate::filter::url_encode;
+use crate::rtg::bytes::ByteEncoding;
+
+/// Secret length in bytes for new secrets, 160 bits as RFC 4226 recommends.
+pub const DEFAULT_SECRET_BYTES: usize = 20;
+/// The shortest secret RFC 4226 allows, 128 bits.
+pub const MIN_SECRET_BYTES: usize = 16;
+/// Digits in a code.
+pub const DEFAULT_DIGITS: u32 = 6;
+/// Seconds each code is valid for.
+pub const DEFAULT_PERIOD: u64 = 30;
+/// The most periods either side of now a code is accepted for. Each one
+/// more gives a guesser another code to hit.
+pub const MAX_WINDOW: u64 = 10;
+
+/// The HMAC hash of a TOTP secret. Most authenticator apps only support SHA1.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TotpAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512
+}
+
+impl TotpAlgorithm {
+    pub const ALL: [TotpAlgorithm; 3] = [TotpAlgorithm::Sha1, TotpAlgorithm::Sha256, TotpAlgorithm::Sha512];
+
+    /// The name in `otpauth://` URIs.
+    pub fn name(self) -> &'static str {
+        match self {
+            TotpAlgorithm::Sha1 => "SHA1",
+            TotpAlgorithm::Sha256 => "SHA256",
+            TotpAlgorithm::Sha512 => "SHA512"
+        }
+    }
+
+    fn hmac(self, key: &[u8], message: &[u8]) -> Zeroizing<Vec<u8>> {
+        fn mac<M: Mac + hmac::digest::KeyInit>(key: &[u8], message: &[u8]) -> Zeroizing<Vec<u8>> {
+            let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC takes keys of any length");
+            mac.update(message);
+            Zeroizing::new(mac.finalize().into_bytes().to_vec())
+        }
+
+        match self {
+            TotpAlgorithm::Sha1 => mac::<Hmac<Sha1>>(key, message),
+            TotpAlgorithm::Sha256 => mac::<Hmac<Sha256>>(key, message),
+            TotpAlgorithm::Sha512 => mac::<Hmac<Sha512>>(key, message)
+        }
+    }
+}
+
+impl FromStr for TotpAlgorithm {
+    type Err = TotpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TotpAlgorithm::ALL.into_iter()
+                          .find(|a| a.name().eq_ignore_ascii_case(s))
+                          .ok_or_else(|| TotpError::UnknownAlgorithm(s.to_string()))
+    }
+}
+
+/// A TOTP secret and the parameters codes are computed with.
+pub struct Totp {
+    secret: Zeroizing<Vec<u8>>,
+    algorithm: TotpAlgorithm,
+    digits: u32,
+    period: u64
+}
+
+impl Totp {
+    /// A secret with the default parameters: SHA1, six digits, 30 seconds.
+    pub fn new(secret: Vec<u8>) -> Result<Self, TotpError> {
+        if secret.is_empty() {
+            return Err(TotpError::EmptySecret);
+        }
+
+        Ok(Totp { secret: Zeroizing::new(secret), algorithm: TotpAlgorithm::Sha1, digits: DEFAULT_DIGITS,
+                  period: DEFAULT_PERIOD })
+    }
+
+    /// A new random secret of `bytes` bytes, at least [`MIN_SECRET_BYTES`].
+    pub fn generate(rng: &mut dyn RngCore, bytes: usize) -> Result<Self, TotpError> {
+        if bytes < MIN_SECRET_BYTES {
+            return Err(TotpError::ShortSecret(bytes));
+        }
+        let mut secret = vec![0u8; bytes];
+        rng.fill_bytes(&mut secret);
+
+        Totp::new(secret)
+    }
+
+    /// A secret in base32, as shown by services for manual entry. Case,
+    /// spaces and padding are ignored.
+    pub fn from_base32(text: &str) -> Result<Self, TotpError> {
+        Totp::new(decode_base32(text)?)
+    }
+
+    /// The secret and parameters of an `otpauth://totp/` URI.
+    pub fn from_uri(uri: &str) -> Result<Self, TotpError> {
+        let invalid = |message: &str| TotpError::InvalidUri(message.to_string());
+        let rest = uri.strip_prefix("otpauth://totp/").ok_or_else(|| invalid("not an otpauth://totp/ URI"))?;
+        let (_, query) = rest.split_once('?').ok_or_else(|| invalid("no secret"))?;
+
+        let (mut secret, mut algorithm, mut digits, mut period) = (None, TotpAlgorithm::Sha1, DEFAULT_DIGITS, DEFAULT_PERIOD);
+        for (key, value) in query.split('&').filter_map(|p| p.split_once('=')) {
+            match key {
+                "secret" => secret = Some(decode_base32(value)?),
+                "algorithm" => algorithm = value.parse()?,
+                "digits" => digits = value.parse().map_err(|_| invalid("digits is not a number"))?,
+                "period" => period = value.parse().map_err(|_| invalid("period is not a number"))?,
+                _ => {}
+            }
+        }
+
+        Totp::new(secret.ok_or_else(|| invalid("no secret"))?)?.with_params(algorithm, digits, period)
+    }
+
+    /// Change the hash, the digits in a code (6 to 8) or the seconds a code
+    /// is valid for. Authenticator apps may ignore anything but the defaults.
+    pub fn with_params(mut self, algorithm: TotpAlgorithm, digits: u32, period: u64) -> Result<Self, TotpError> {
+        if !(6..=8).contains(&digits) {
+            return Err(TotpError::InvalidDigits(digits));
+        }
+        if period == 0 {
+            return Err(TotpError::ZeroPeriod);
+        }
+        self.algorithm = algorithm;
+        self.digits = digits;
+        self.period = period;
+
+        Ok(self)
+    }
+
+    /// The secret in unpadded RFC 4648 base32.
+    pub fn secret_base32(&self) -> Zeroizing<String> {
+        Zeroizing::new(ByteEncoding::Base32.encode(&self.secret))
+    }
+
+    /// The `otpauth://` URI to enrol an authenticator app, labelled with the
+    /// issuer and account. Neither can be empty or contain a colon.
+    pub fn uri(&self, issuer: &str, account: &str) -> Result<Zeroizing<String>, TotpError> {
+        for label in [issuer, account] {
+            if label.trim().is_empty() || label.contains(':') {
+                return Err(TotpError::InvalidLabel(label.to_string()));
+            }
+        }
+        let issuer = url_encode(issuer);
+
+        Ok(Zeroizing::new(format!("otpauth://totp/{}:{}?secret={}&issuer={}&algorithm={}&digits={}&period={}",
+                                  issuer, url_encode(account), *self.secret_base32(), issuer,
+                                  self.algorithm.name(), self.digits, self.period)))
+    }
+
+    /// The code for a Unix time in seconds.
+    pub fn code_at(&self, time: u64) -> Zeroizing<String> {
+        let mac = self.algorithm.hmac(&self.secret, &(time / self.period).to_be_bytes());
+        //dynamic truncation, RFC 4226 section 5.3
+        let offset = (mac[mac.len() - 1] & 0xf) as usize;
+        let binary = u32::from_be_bytes([mac[offset] & 0x7f, mac[offset + 1], mac[offset + 2], mac[offset + 3]]);
+
+        Zeroizing::new(format!("{:0width$}", binary % 10u32.pow(self.digits), width = self.digits as usize))
+    }
+
+    /// The code an authenticator app shows now.
+    pub fn current_code(&self) -> Zeroizing<String> {
+        self.code_at(now())
+    }
+
+    /// Whether a code is valid at a time, or up to `window` periods before or
+    /// after it to allow for clock drift. No code is valid in a window wider
+    /// than [`MAX_WINDOW`].
+    pub fn verify(&self, code: &str, time: u64, window: u64) -> bool {
+        if window > MAX_WINDOW {
+            return false;
+        }
+        let step = time / self.period;
+        let mut valid = false;
+        for step in step.saturating_sub(window)..=step.saturating_add(window) {
+            //compare every byte, so the time taken doesn't depend on the code
+            let expected = self.code_at(step.saturating_mul(self.period));
+            valid |= expected.len() == code.len()
+                && expected.bytes().zip(code.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0;
+        }
+
+        valid
+    }
+}
+
+impl Display for Totp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TOTP({}, {} digits, {}s)", self.algorithm.name(), self.digits, self.period)
+    }
+}
+
+/// Seconds since the Unix epoch.
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Decode RFC 4648 base32, ignoring case, spaces and padding.
+fn decode_base32(text: &str) -> Result<Vec<u8>, TotpError> {
+    let mut bytes = Vec::with_capacity(text.len() * 5 / 8);
+    let (mut buffer, mut bits) = (0u16, 0);
+    for c in text.trim_end_matches('=').chars().filter(|c| *c != ' ') {
+        let value = match c.to_ascii_uppercase() {
+            c @ 'A'..='Z' => c as u16 - 'A' as u16,
+            c @ '2'..='7' => c as u16 - '2' as u16 + 26,
+            _ => return Err(TotpError::InvalidBase32)
+        };
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+
+    Ok(bytes)
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum TotpError {
+    EmptySecret,
+    /// A new secret shorter than [`MIN_SECRET_BYTES`].
+    ShortSecret(usize),
+    InvalidBase32,
+    InvalidDigits(u32),
+    ZeroPeriod,
+    UnknownAlgorithm(String),
+    /// An issuer or account that is empty or has a colon.
+    InvalidLabel(String),
+    InvalidUri(String)
+}
+
+impl Display for TotpError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TotpError::EmptySecret => write!(f, "the secret is empty"),
+            TotpError::ShortSecret(bytes) => {
+                write!(f, "a {} byte secret is too short, use at least {}", bytes, MIN_SECRET_BYTES)
+            }
+            TotpError::InvalidBase32 => write!(f, "the secret is not base32"),
+            TotpError::InvalidDigits(digits) => write!(f, "codes have 6 to 8 digits, not {}", digits),
+            TotpError::ZeroPeriod => write!(f, "the period must be at least one second"),
+            TotpError::UnknownAlgorithm(name) => {
+                let names: Vec<&str> = TotpAlgorithm::ALL.iter().map(|a| a.name()).collect();
+                write!(f, "unknown algorithm '{}', expected one of {}", name, names.join(", "))
+            }
+            TotpError::InvalidLabel(label) => write!(f, "invalid issuer or account '{}': it can't be empty or have a colon", label),
+            TotpError::InvalidUri(message) => write!(f, "invalid otpauth URI: {}", message)
+        }
+    }
+}
+
+impl std::error::Error for TotpError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    #[test]
+    fn rfc6238_vectors() {
+        //RFC 6238 appendix B, eight digit codes
+        let totp = |secret: &[u8], algorithm| {
+            Totp::new(secret.to_vec()).unwrap().with_params(algorithm, 8, 30).unwrap()
+        };
+        let sha1 = totp(b"12345678901234567890", TotpAlgorithm::Sha1);
+        let sha256 = totp(b"12345678901234567890123456789012", TotpAlgorithm::Sha256);
+        let sha512 = totp(b"1234567890123456789012345678901234567890123456789012345678901234", TotpAlgorithm::Sha512);
+
+        for (time, codes) in [(59, ["94287082", "46119246", "90693936"]),
+                              (1111111109, ["07081804", "68084774", "25091201"]),
+                              (1111111111, ["14050471", "67062674", "99943326"]),
+                              (1234567890, ["89005924", "91819424", "93441116"]),
+                              (2000000000, ["69279037", "90698825", "38618901"]),
+                              (20000000000, ["65353130", "77737706", "47863826"])] {
+            assert_eq!(*sha1.code_at(time), codes[0]);
+            assert_eq!(*sha256.code_at(time), codes[1]);
+            assert_eq!(*sha512.code_at(time), codes[2]);
+        }
+        //RFC 4226 appendix D, six digits
+        assert_eq!(*Totp::new(b"12345678901234567890".to_vec()).unwrap().code_at(0), "755224");
+    }
+
+    #[test]
+    fn uri_round_trip() {
+        let totp = Totp::generate(&mut ChaCha20Rng::seed_from_u64(3), DEFAULT_SECRET_BYTES).unwrap();
+        let secret = totp.secret_base32();
+        assert_eq!(secret.len(), 32);
+
+        let uri = totp.uri("ACME Co", "john.doe@example.com").unwrap();
+        assert_eq!(*uri, format!("otpauth://totp/ACME%20Co:john.doe%40example.com?secret={}&issuer=ACME%20Co\
+                                  &algorithm=SHA1&digits=6&period=30", *secret));
+        let parsed = Totp::from_uri(&uri).unwrap();
+        assert_eq!(*parsed.code_at(1_700_000_000), *totp.code_at(1_700_000_000));
+        assert_eq!(*Totp::from_base32(&secret.to_lowercase()).unwrap().secret_base32(), *secret);
+
+        let google = "otpauth://totp/ACME%20Co:john.doe@email.com?secret=HXDMVJECJJWSRB3HWIZR4IFUGFTMXBOZ\
+                      &issuer=ACME%20Co&algorithm=SHA256&digits=8&period=60";
+        assert_eq!(Totp::from_uri(google).unwrap().to_string(), "TOTP(SHA256, 8 digits, 60s)");
+        assert_eq!(*Totp::from_base32("MZXW 6YTB OI======").unwrap().secret, b"foobar");
+    }
+
+    #[test]
+    fn verify_window() {
+        let totp = Totp::from_base32("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ").unwrap();
+        let code = totp.code_at(1_000_000_010);
+        assert_eq!(*code, *totp.code_at(1_000_000_000));
+        assert!(totp.verify(&code, 1_000_000_000, 0));
+        assert!(totp.verify(&code, 1_000_000_040, 1));
+        assert!(!totp.verify(&code, 1_000_000_040, 0));
+        assert!(!totp.verify(&code, 1_000_000_070, 1));
+        assert!(!totp.verify(&code[1..], 1_000_000_000, 1));
+        assert!(totp.verify(&totp.code_at(15), 0, 1));
+        assert!(totp.verify(&code, 1_000_000_000 + 30 * MAX_WINDOW, MAX_WINDOW));
+        assert!(!totp.verify(&code, 1_000_000_000, MAX_WINDOW + 1));
+        assert!(!totp.verify(&code, 1_000_000_000, u64::MAX));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(Totp::generate(&mut ChaCha20Rng::seed_from_u64(3), 10).err(), Some(TotpError::ShortSecret(10)));
+        assert_eq!(Totp::from_base32("").err(), Some(TotpError::EmptySecret));
+        assert_eq!(Totp::from_base32("MZXW1").err(), Some(TotpError::InvalidBase32));
+        let totp = || Totp::from_base32("MZXW6YTB").unwrap();
+        assert_eq!(totp().with_params(TotpAlgorithm::Sha1, 9, 30).err(), Some(TotpError::InvalidDigits(9)));
+        assert_eq!(totp().with_params(TotpAlgorithm::Sha1, 6, 0).err(), Some(TotpError::ZeroPeriod));
+        assert_eq!("md5".parse::<TotpAlgorithm>().unwrap_err().to_string(),
+                   "unknown algorithm 'md5', expected one of SHA1, SHA256, SHA512");
+        assert_eq!("sha256".parse::<TotpAlgorithm>(), Ok(TotpAlgorithm::Sha256));
+        assert!(matches!(totp().uri("ACME:Co", "john"), Err(TotpError::InvalidLabel(_))));
+        assert!(matches!(totp().uri("ACME", " "), Err(TotpError::InvalidLabel(_))));
+        assert!(matches!(Totp::from_uri("otpauth://hotp/x?secret=MZXW6YTB"), Err(TotpError::InvalidUri(_))));
+        assert!(matches!(Totp::from_uri("otpauth://totp/x?issuer=x"), Err(TotpError::InvalidUri(_))));
+    }
+}
@@ -0,0 +1,198 @@
+//! Password files for a list of users.
+//!
+//! Every user gets a new password from the spec. Only hashes go into the
+//! htpasswd file or the `chpasswd -e` and shadow lines; the plaintext goes
+//! into a separate handoff CSV for getting the passwords to their users.
+
+use clap::ValueEnum;
+use mpass::{Passphrase, Specifier};
+use mpass::hash::{legacy, HashAlgorithm};
+use zeroize::Zeroizing;
+use crate::error::CliError;
+
+/// Longest user name `useradd` accepts.
+const UNIX_NAME_MAX: usize = 32;
+/// Longest user name Apache accepts in an htpasswd file.
+const HTPASSWD_NAME_MAX: usize = 255;
+
+/// Password hashes understood by Apache and nginx in htpasswd files.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum HtpasswdScheme {
+    /// bcrypt, `$2y$`
+    #[default]
+    Bcrypt,
+    /// Apache MD5 crypt, `$apr1$`. Weak; only for servers without bcrypt
+    Apr1,
+    /// Unsalted SHA-1, `{SHA}`. Weak; only for servers without bcrypt
+    Sha
+}
+
+impl HtpasswdScheme {
+    /// Hash a password, with `cost` as the bcrypt cost.
+    pub fn hash(self, password: &str, cost: Option<u32>) -> Result<String, CliError> {
+        match self {
+            HtpasswdScheme::Bcrypt => {
+                let algorithm = HashAlgorithm::Bcrypt;
+                let hash = algorithm.hash_with_cost(password, cost.unwrap_or(algorithm.default_cost()))?;
+                //same algorithm, but older Apache versions only know the $2y$ prefix
+                Ok(hash.replacen("$2b$", "$2y$", 1))
+            }
+            HtpasswdScheme::Apr1 => Ok(legacy::apr1(password)),
+            HtpasswdScheme::Sha => Ok(legacy::htpasswd_sha1(password))
+        }
+    }
+}
+
+/// Which user names are accepted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NameRules {
+    /// Anything without a colon or control characters.
+    Htpasswd,
+    /// Portable system user names, as `useradd` accepts by default.
+    Unix
+}
+
+impl NameRules {
+    fn accepts(self, name: &str) -> bool {
+        match self {
+            NameRules::Htpasswd => {
+                name.len() <= HTPASSWD_NAME_MAX && !name.contains(|c: char| c == ':' || c.is_control())
+            }
+            NameRules::Unix => {
+                let body = name.strip_suffix('$').unwrap_or(name);
+                name.len() <= UNIX_NAME_MAX
+                    && body.starts_with(|c: char| c.is_ascii_lowercase() || c == '_')
+                    && body.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+            }
+        }
+    }
+}
+
+/// A user with a new password and its hash.
+pub struct Account {
+    pub username: String,
+    pub password: Passphrase,
+    pub hash: String
+}
+
+/// Read user names one per line, skipping blank lines and `#` comments.
+pub fn read_usernames(input: &str, rules: NameRules) -> Result<Vec<String>, CliError> {
+    let mut names: Vec<String> = Vec::new();
+    for (n, line) in input.lines().enumerate() {
+        let name = line.trim();
+        if name.is_empty() || name.starts_with('#') {
+            continue;
+        }
+        if !rules.accepts(name) {
+            return Err(CliError::InvalidUsername { line: n + 1, name: name.to_string() });
+        }
+        if names.iter().any(|existing| existing == name) {
+            return Err(CliError::DuplicateUsername(name.to_string()));
+        }
+        names.push(name.to_string());
+    }
+
+    Ok(names)
+}
+
+/// Generate a password for each user and hash it.
+pub fn create(usernames: Vec<String>, specifier: &Specifier,
+              hash: impl Fn(&str) -> Result<String, CliError>) -> Result<Vec<Account>, CliError> {
+    usernames.into_iter()
+             .map(|username| {
+                 let password = specifier.get_passphrase()?;
+                 let hash = hash(password.expose())?;
+                 Ok(Account { username, password, hash })
+             })
+             .collect()
+}
+
+/// `user:hash` lines, the format of both htpasswd files and `chpasswd -e`.
+pub fn to_htpasswd(accounts: &[Account]) -> String {
+    accounts.iter().map(|a| format!("{}:{}\n", a.username, a.hash)).collect()
+}
+
+/// `/etc/shadow` lines, with the password last changed `days` after the
+/// epoch and the usual ageing defaults of no minimum, a 99999 day maximum
+/// and 7 days of warning.
+pub fn to_shadow(accounts: &[Account], days: u64) -> String {
+    accounts.iter().map(|a| format!("{}:{}:{}:0:99999:7:::\n", a.username, a.hash, days)).collect()
+}
+
+/// The plaintext passwords as a `username,password` CSV.
+pub fn to_handoff(accounts: &[Account]) -> Result<Zeroizing<Vec<u8>>, CliError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(["username", "password"]).map_err(CliError::Csv)?;
+    for account in accounts {
+        writer.write_record([account.username.as_str(), account.password.expose()]).map_err(CliError::Csv)?;
+    }
+    //only fails flushing, which writing to memory can't
+    let csv = writer.into_inner().map_err(|e| CliError::io("<handoff>", e.into_error()))?;
+
+    Ok(Zeroizing::new(csv))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_chacha::ChaCha20Rng;
+    use rand_chacha::rand_core::SeedableRng;
+
+    fn accounts(names: &str, scheme: HtpasswdScheme) -> Vec<Account> {
+        let mut specifier = Specifier::try_parse("i w ##").unwrap();
+        specifier.set_rng(ChaCha20Rng::seed_from_u64(3));
+        let names = read_usernames(names, NameRules::Htpasswd).unwrap();
+
+        create(names, &specifier, |p| scheme.hash(p, Some(4))).unwrap()
+    }
+
+    #[test]
+    fn reads_usernames() {
+        let names = read_usernames("alice\n\n  # staff\nbob-2\n_svc$\n", NameRules::Unix).unwrap();
+        assert_eq!(names, ["alice", "bob-2", "_svc$"]);
+
+        assert!(matches!(read_usernames("alice\nBob\n", NameRules::Unix),
+                         Err(CliError::InvalidUsername { line: 2, .. })));
+        assert!(matches!(read_usernames("9lives", NameRules::Unix), Err(CliError::InvalidUsername { .. })));
+        assert!(matches!(read_usernames(&"a".repeat(33), NameRules::Unix), Err(CliError::InvalidUsername { .. })));
+        assert!(matches!(read_usernames("alice\nalice", NameRules::Unix), Err(CliError::DuplicateUsername(_))));
+
+        assert_eq!(read_usernames("Alice Smith\nbob@example.com", NameRules::Htpasswd).unwrap().len(), 2);
+        assert!(matches!(read_usernames("a:b", NameRules::Htpasswd), Err(CliError::InvalidUsername { .. })));
+    }
+
+    #[test]
+    fn htpasswd_schemes() {
+        let bcrypt = accounts("alice\nbob", HtpasswdScheme::Bcrypt);
+        let file = to_htpasswd(&bcrypt);
+        let lines: Vec<&str> = file.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("alice:$2y$04$"));
+        let hash = lines[1].strip_prefix("bob:").unwrap();
+        assert!(HashAlgorithm::Bcrypt.verify(bcrypt[1].password.expose(), hash).unwrap());
+        assert!(matches!(HtpasswdScheme::Bcrypt.hash(&"a".repeat(73), Some(4)), Err(CliError::Hash(_))));
+        assert_ne!(bcrypt[0].password.expose(), bcrypt[1].password.expose());
+
+        let apr1 = accounts("alice", HtpasswdScheme::Apr1);
+        assert_eq!(apr1[0].hash, legacy::apr1_with_salt(apr1[0].password.expose(), &apr1[0].hash[6..14]));
+        let sha = accounts("alice", HtpasswdScheme::Sha);
+        assert_eq!(sha[0].hash, legacy::htpasswd_sha1(sha[0].password.expose()));
+    }
+
+    #[test]
+    fn shadow_and_handoff() {
+        let mut specifier = Specifier::try_parse("i w ##").unwrap();
+        specifier.set_rng(ChaCha20Rng::seed_from_u64(3));
+        let names = read_usernames("root\nsvc", NameRules::Unix).unwrap();
+        let accounts = create(names, &specifier, |_| Ok("$6$salt$hash".to_string())).unwrap();
+
+        assert_eq!(to_shadow(&accounts, 20_000), "root:$6$salt$hash:20000:0:99999:7:::\n\
+                                                  svc:$6$salt$hash:20000:0:99999:7:::\n");
+        assert_eq!(to_htpasswd(&accounts), "root:$6$salt$hash\nsvc:$6$salt$hash\n");
+
+        let handoff = to_handoff(&accounts).unwrap();
+        assert_eq!(String::from_utf8(handoff.to_vec()).unwrap(),
+                   format!("username,password\nroot,{}\nsvc,{}\n", accounts[0].password.expose(),
+                           accounts[1].password.expose()));
+    }
+}
@@ -0,0 +1,882 @@
+//! Command line arguments.
+
+use std::ffi::OsString;
+use std::path::PathBuf;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap::builder::RangedU64ValueParser;
+use clap::error::ErrorKind;
+use mpass::apikey::DEFAULT_KEY_LENGTH;
+use mpass::hash::HashAlgorithm;
+use mpass::spoken::Language;
+use mpass::totp::{TotpAlgorithm, DEFAULT_DIGITS, DEFAULT_PERIOD, DEFAULT_SECRET_BYTES, MAX_WINDOW, MIN_SECRET_BYTES};
+use crate::accounts::HtpasswdScheme;
+use crate::escape::Escape;
+use crate::export::ExportFormat;
+use crate::handout::{HandoutFormat, Layout};
+use crate::kdbx::{KdfParams, VaultCipher};
+use crate::output::OutputFormat;
+use crate::qr::{QrFormat, WifiSecurity};
+use crate::secrets::SecretFormat;
+
+/// The spec used when none is given on the command line.
+pub const DEFAULT_SPEC: &str = "i w w ###$";
+
+/// The spec for the master passphrase of a KeePass database.
+pub const DEFAULT_MASTER_SPEC: &str = "i w w w w ###$";
+
+/// Generate random passphrases from a spec string.
+///
+/// Spec tokens: w lowercase word, W uppercase word, i initial caps word,
+/// r random cap word, a lowercase letter, A uppercase letter,
+/// x alphanumeric, z alphanumeric or symbol, # digit, $ symbol,
+/// (space) space, ? shuffle the token order. Random keys: h hex, B base32,
+/// c Crockford base32, b base64url, each 16 bytes or a count like 32b, and
+/// u a UUIDv4.
+///
+/// Without a command, the arguments are those of `generate`.
+#[derive(Parser, Debug)]
+#[command(name = "mpassgen", version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+
+    #[command(flatten)]
+    pub lists: ListArgs,
+
+    #[command(flatten)]
+    pub encrypt: EncryptArgs,
+}
+
+impl Cli {
+    /// Parse the process arguments, exiting with usage on errors.
+    pub fn parse_args() -> Self {
+        Self::try_parse_args(std::env::args_os()).unwrap_or_else(|e| e.exit())
+    }
+
+    /// Parse arguments, running `generate` when no command is named, so
+    /// `mpassgen -n 5 ww##` still works and global options can go anywhere.
+    pub fn try_parse_args<I, T>(args: I) -> Result<Self, clap::Error>
+    where I: IntoIterator<Item = T>, T: Into<OsString> {
+        let mut args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+        let command = Self::command();
+        let named = |arg: &OsString| arg.to_str().is_some_and(|a| {
+            a == "help" || command.get_subcommands().any(|c| c.get_name() == a || c.get_all_aliases().any(|n| n == a))
+        });
+        let top_level = |arg: &OsString| ["-h", "--help", "-V", "--version"].iter().any(|f| arg == f);
+
+        //options that can come before a spec and take the next argument as their value
+        let generate = command.find_subcommand("generate").expect("generate is a command");
+        let takes_value = |arg: &str| {
+            command.get_arguments().chain(generate.get_arguments())
+                   .filter(|a| a.get_action().takes_values())
+                   .any(|a| {
+                       a.get_long().is_some_and(|l| arg.strip_prefix("--") == Some(l))
+                           || a.get_short().is_some_and(|c| arg.strip_prefix('-') == Some(c.encode_utf8(&mut [0; 4])))
+                   })
+        };
+
+        //only the first positional argument can name a command
+        let mut rest = args.iter().skip(1);
+        let mut first = None;
+        while let Some(arg) = rest.next() {
+            match arg.to_str() {
+                Some("--") => {
+                    first = rest.next();
+                    break;
+                }
+                Some(a) if a.len() > 1 && a.starts_with('-') => {
+                    if takes_value(a) {
+                        rest.next();
+                    }
+                }
+                _ => {
+                    first = Some(arg);
+                    break;
+                }
+            }
+        }
+
+        if !first.is_some_and(named) && !args.get(1).is_some_and(top_level) {
+            args.insert(args.len().min(1), OsString::from("generate"));
+        }
+        let cli = Self::try_parse_from(args)?;
+
+        //the encryption options are global, but decrypt has nothing to encrypt
+        if matches!(cli.command, Command::Decrypt(_)) && cli.encrypt.is_set() {
+            return Err(Self::command().error(ErrorKind::ArgumentConflict,
+                                             "decrypt does not take --encrypt-to, --encrypt-to-file, \
+                                              --encrypt-passphrase-file or --armor"));
+        }
+        Ok(cli)
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Generate passphrases from a spec. This is the command when none is named
+    Generate(GenerateArgs),
+    /// Replace <<name:spec>> placeholders in a template with generated passphrases
+    Render(RenderArgs),
+    /// Render a template once per CSV row, with the row's columns as <<column>>
+    Merge(MergeArgs),
+    /// Print a credential card with a new password for each user in a CSV file
+    Handout(HandoutArgs),
+    /// Generate a passphrase and print it as a QR code
+    Qr(QrArgs),
+    /// Write an htpasswd file with a new password for each user in a list
+    Htpasswd(HtpasswdArgs),
+    /// Write `chpasswd -e` or /etc/shadow lines with a new password for each user in a list
+    Chpasswd(ChpasswdArgs),
+    /// Write a password manager import file with a new password for each account in a CSV file
+    Export(ExportArgs),
+    /// Write a KeePass database with a new password for each account in a CSV file
+    Vault(VaultArgs),
+    /// Write a Kubernetes Secret, .env file or systemd EnvironmentFile from a file of KEY=SPEC lines
+    Secrets(SecretsArgs),
+    /// Generate API keys with a prefix and a checksum, like acme_live_<random>_<checksum>
+    Apikey(ApikeyArgs),
+    /// Check the format and checksum of API keys, without looking them up
+    CheckApikey(CheckApikeyArgs),
+    /// Generate a TOTP secret and the otpauth URI that enrols it in an authenticator app
+    Totp(TotpArgs),
+    /// Print the TOTP code of a secret or otpauth URI, or check a code
+    TotpCode(TotpCodeArgs),
+    /// Derive the same passphrase every time from a master secret, site, login and counter
+    Derive(DeriveArgs),
+    /// Decrypt a file written with --encrypt-to or --encrypt-passphrase-file
+    Decrypt(DecryptArgs),
+}
+
+/// Options for generating passphrases directly from a spec.
+#[derive(Args, Debug)]
+pub struct GenerateArgs {
+    /// Spec string describing the passphrase
+    #[arg(default_value = DEFAULT_SPEC)]
+    pub spec: String,
+
+    /// Number of passphrases to generate
+    #[arg(short = 'n', long = "count", default_value_t = 1)]
+    pub count: usize,
+
+    /// Print the estimated entropy of the spec in bits. The other formats
+    /// always include it
+    #[arg(long)]
+    pub entropy: bool,
+
+    /// Output format. The structured formats include the spec, entropy,
+    /// the text of each token and a timestamp
+    #[arg(long, value_enum, default_value_t = OutputFormat::Plain)]
+    pub format: OutputFormat,
+
+    /// Spell each passphrase out on the following line, for reading aloud
+    #[arg(long)]
+    pub spell: bool,
+
+    /// Also output a hash of each passphrase: argon2id, bcrypt, sha512crypt or pbkdf2
+    #[arg(long, value_name = "ALGORITHM")]
+    pub hash: Option<HashAlgorithm>,
+
+    /// Work factor for --hash: Argon2id passes, bcrypt cost, SHA-512 crypt
+    /// rounds or PBKDF2 iterations. Defaults to the algorithm's recommendation
+    #[arg(long, value_name = "COST", requires = "hash")]
+    pub hash_cost: Option<u32>,
+}
+
+/// Word and symbol list options shared by every command.
+#[derive(Args, Debug)]
+pub struct ListArgs {
+    /// Word list file, one word per line
+    #[arg(long, value_name = "FILE", global = true)]
+    pub words: Option<PathBuf>,
+
+    /// Symbol list file, one symbol per line
+    #[arg(long, value_name = "FILE", global = true)]
+    pub symbols: Option<PathBuf>,
+
+    /// Seed the random number generator for reproducible output.
+    /// Only for testing; seeded passphrases are not secret.
+    #[arg(long, global = true)]
+    pub seed: Option<u64>,
+
+    /// Language for spelled out passphrases: en (NATO), de (DIN 5009) or fr
+    #[arg(long, default_value = "en", global = true)]
+    pub language: Language,
+}
+
+/// Options for encrypting output with age, shared by every command.
+#[derive(Args, Debug)]
+pub struct EncryptArgs {
+    /// Encrypt output with age to this recipient, an age1... public key.
+    /// Can be repeated
+    #[arg(long, value_name = "RECIPIENT", global = true)]
+    pub encrypt_to: Vec<String>,
+
+    /// Encrypt output with age to every recipient in this file, one per line.
+    /// Can be repeated
+    #[arg(long, value_name = "FILE", global = true)]
+    pub encrypt_to_file: Vec<PathBuf>,
+
+    /// Encrypt output with age to the passphrase on the first line of this file
+    #[arg(long, value_name = "FILE", global = true, conflicts_with_all = ["encrypt_to", "encrypt_to_file"])]
+    pub encrypt_passphrase_file: Option<PathBuf>,
+
+    /// Write encrypted output as ASCII armored text instead of binary
+    #[arg(long, global = true)]
+    pub armor: bool,
+}
+
+impl EncryptArgs {
+    /// Returns true if any encryption option was given.
+    pub fn is_set(&self) -> bool {
+        !self.encrypt_to.is_empty() || !self.encrypt_to_file.is_empty() || self.encrypt_passphrase_file.is_some() || self.armor
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct RenderArgs {
+    /// Template file to read, or - for stdin
+    #[arg(default_value = "-")]
+    pub template: PathBuf,
+
+    /// File to write the rendered output to instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// How to escape substituted values. Values are escaped to sit inside a
+    /// quoted string of the format; add |raw to a placeholder to skip escaping
+    #[arg(long, value_enum, default_value_t = Escape::Auto)]
+    pub escape: Escape,
+}
+
+#[derive(Args, Debug)]
+pub struct MergeArgs {
+    /// Template file to render for each row
+    pub template: PathBuf,
+
+    /// CSV file with a header row, or - for stdin
+    pub csv: PathBuf,
+
+    /// Output file name pattern, e.g. "letters/<<username>>.html".
+    /// Columns and <<_row>> (the row number) can be used
+    #[arg(short, long, value_name = "PATTERN")]
+    pub output: String,
+
+    /// File to write the summary CSV to instead of stdout
+    #[arg(long, value_name = "FILE")]
+    pub summary: Option<PathBuf>,
+
+    /// How to escape substituted values, as for render
+    #[arg(long, value_enum, default_value_t = Escape::Auto)]
+    pub escape: Escape,
+}
+
+#[derive(Args, Debug)]
+pub struct HandoutArgs {
+    /// CSV file of users with a header row, or - for stdin
+    pub csv: PathBuf,
+
+    /// File to write the handout to instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Output format. Defaults to PDF for a .pdf output file, otherwise HTML
+    #[arg(long, value_enum)]
+    pub format: Option<HandoutFormat>,
+
+    /// Spec string for the passwords
+    #[arg(long, default_value = DEFAULT_SPEC)]
+    pub spec: String,
+
+    /// File to write the CSV columns and each password to
+    #[arg(long, value_name = "FILE")]
+    pub summary: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub layout: Layout,
+}
+
+#[derive(Args, Debug)]
+pub struct QrArgs {
+    /// Spec string describing the passphrase
+    #[arg(default_value = DEFAULT_SPEC)]
+    pub spec: String,
+
+    /// File to write the image to instead of stdout. The passphrase is
+    /// printed to stdout when this is given
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Image format. Defaults to the output file extension, otherwise the terminal
+    #[arg(long, value_enum)]
+    pub format: Option<QrFormat>,
+
+    /// Pixels per module for PNG and SVG images
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..=64))]
+    pub scale: u32,
+
+    /// Encode a Wi-Fi network with this name and the passphrase as its password
+    #[arg(long, value_name = "SSID")]
+    pub wifi: Option<String>,
+
+    /// Wi-Fi authentication type
+    #[arg(long, value_enum, default_value_t = WifiSecurity::Wpa, requires = "wifi")]
+    pub security: WifiSecurity,
+
+    /// Mark the Wi-Fi network as hidden
+    #[arg(long, requires = "wifi")]
+    pub hidden: bool,
+}
+
+/// Options shared by the commands that create passwords for a list of users.
+#[derive(Args, Debug)]
+pub struct AccountArgs {
+    /// File of user names, one per line, or - for stdin
+    pub users: PathBuf,
+
+    /// Spec string for the passwords
+    #[arg(long, default_value = DEFAULT_SPEC)]
+    pub spec: String,
+
+    /// File to write the hashed passwords to instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// CSV file to write each user name and plaintext password to, for
+    /// handing out. Only the owner can read it
+    #[arg(long, value_name = "FILE")]
+    pub handoff: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct HtpasswdArgs {
+    #[command(flatten)]
+    pub accounts: AccountArgs,
+
+    /// Password hash
+    #[arg(long, value_enum, default_value_t = HtpasswdScheme::Bcrypt)]
+    pub scheme: HtpasswdScheme,
+
+    /// bcrypt cost
+    #[arg(long)]
+    pub cost: Option<u32>,
+}
+
+#[derive(Args, Debug)]
+pub struct ChpasswdArgs {
+    #[command(flatten)]
+    pub accounts: AccountArgs,
+
+    /// Write complete /etc/shadow lines instead of `user:hash` for `chpasswd -e`
+    #[arg(long)]
+    pub shadow: bool,
+
+    /// SHA-512 crypt rounds
+    #[arg(long)]
+    pub rounds: Option<u32>,
+}
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    /// CSV file of accounts with title and optional username, url and notes
+    /// columns, or - for stdin
+    pub csv: PathBuf,
+
+    /// Import format of the password manager
+    #[arg(long, value_enum)]
+    pub format: ExportFormat,
+
+    /// File to write the import file to instead of stdout. Only the owner can read it
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Spec string for the passwords
+    #[arg(long, default_value = DEFAULT_SPEC)]
+    pub spec: String,
+}
+
+#[derive(Args, Debug)]
+pub struct VaultArgs {
+    /// CSV file of accounts with title and optional username, url and notes
+    /// columns, or - for stdin
+    pub csv: PathBuf,
+
+    /// KeePass database file to write
+    #[arg(short, long, value_name = "FILE")]
+    pub output: PathBuf,
+
+    /// Spec string for the passwords
+    #[arg(long, default_value = DEFAULT_SPEC)]
+    pub spec: String,
+
+    /// Spec string for the master passphrase, which is printed to stdout
+    #[arg(long, default_value = DEFAULT_MASTER_SPEC)]
+    pub master_spec: String,
+
+    /// Cipher for the database
+    #[arg(long, value_enum, default_value_t = VaultCipher::Chacha20)]
+    pub cipher: VaultCipher,
+
+    #[command(flatten)]
+    pub kdf: KdfParams,
+}
+
+#[derive(Args, Debug)]
+pub struct SecretsArgs {
+    /// File of KEY=SPEC lines, or - for stdin. A key alone uses the default spec
+    pub mapping: PathBuf,
+
+    /// File to write the secrets to instead of stdout. Only the owner can read it
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Output format. Defaults to kubernetes for a .yaml output file, systemd
+    /// for .conf, otherwise env
+    #[arg(long, value_enum)]
+    pub format: Option<SecretFormat>,
+
+    /// Name of the Kubernetes Secret
+    #[arg(long, default_value = "secrets")]
+    pub name: String,
+
+    /// Namespace of the Kubernetes Secret
+    #[arg(long)]
+    pub namespace: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ApikeyArgs {
+    /// Prefix of the keys, of letters, digits and inner underscores
+    pub prefix: String,
+
+    /// Number of keys to generate
+    #[arg(short = 'n', long = "count", default_value_t = 1)]
+    pub count: usize,
+
+    /// Number of random characters in each key
+    #[arg(long, default_value_t = DEFAULT_KEY_LENGTH, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub length: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct CheckApikeyArgs {
+    /// Keys to check. Without any, keys are read from stdin one per line
+    pub keys: Vec<String>,
+
+    /// Also require this prefix
+    #[arg(long)]
+    pub prefix: Option<String>,
+
+    /// Also require this many random characters. Needs --prefix
+    #[arg(long, requires = "prefix")]
+    pub length: Option<usize>,
+}
+
+#[derive(Args, Debug)]
+pub struct TotpArgs {
+    /// Account the secret is for, usually a user name or email address
+    pub account: String,
+
+    /// Service or organisation shown with the account in authenticator apps
+    #[arg(long)]
+    pub issuer: String,
+
+    /// Secret length in bytes
+    #[arg(long, default_value_t = DEFAULT_SECRET_BYTES,
+          value_parser = RangedU64ValueParser::<usize>::new().range(MIN_SECRET_BYTES as u64..=64))]
+    pub bytes: usize,
+
+    /// HMAC hash: SHA1, SHA256 or SHA512. Many apps only support SHA1
+    #[arg(long, default_value = "SHA1")]
+    pub algorithm: TotpAlgorithm,
+
+    /// Digits in each code
+    #[arg(long, default_value_t = DEFAULT_DIGITS, value_parser = clap::value_parser!(u32).range(6..=8))]
+    pub digits: u32,
+
+    /// Seconds each code is valid for
+    #[arg(long, default_value_t = DEFAULT_PERIOD, value_parser = clap::value_parser!(u64).range(1..))]
+    pub period: u64,
+
+    /// Show the URI as a QR code in the terminal
+    #[arg(long)]
+    pub qr: bool,
+
+    /// File to write a QR code image of the URI to. The secret and URI are
+    /// printed to stdout when this is given
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// QR image format. Defaults to the output file extension, otherwise the terminal
+    #[arg(long, value_enum)]
+    pub format: Option<QrFormat>,
+
+    /// Pixels per module for PNG and SVG images
+    #[arg(long, default_value_t = 8, value_parser = clap::value_parser!(u32).range(1..=64))]
+    pub scale: u32,
+}
+
+#[derive(Args, Debug)]
+pub struct TotpCodeArgs {
+    /// Base32 secret or otpauth:// URI, or - to read it from the first line of stdin
+    #[arg(default_value = "-")]
+    pub secret: String,
+
+    /// Unix time in seconds to compute the code for, instead of now
+    #[arg(long)]
+    pub time: Option<u64>,
+
+    /// Check this code instead of printing one, exiting with 12 if it is wrong
+    #[arg(long, value_name = "CODE")]
+    pub check: Option<String>,
+
+    /// Periods before and after the time in which --check accepts a code, at most 10
+    #[arg(long, default_value_t = 1, requires = "check", value_parser = clap::value_parser!(u64).range(0..=MAX_WINDOW))]
+    pub window: u64,
+
+    /// HMAC hash of a base32 secret. URIs name their own
+    #[arg(long, default_value = "SHA1")]
+    pub algorithm: TotpAlgorithm,
+
+    /// Digits in each code of a base32 secret
+    #[arg(long, default_value_t = DEFAULT_DIGITS, value_parser = clap::value_parser!(u32).range(6..=8))]
+    pub digits: u32,
+
+    /// Seconds each code of a base32 secret is valid for
+    #[arg(long, default_value_t = DEFAULT_PERIOD, value_parser = clap::value_parser!(u64).range(1..))]
+    pub period: u64,
+}
+
+#[derive(Args, Debug)]
+pub struct DeriveArgs {
+    /// Site the passphrase is for, such as example.com. Case is ignored
+    pub site: String,
+
+    /// Spec string describing the passphrase
+    #[arg(default_value = DEFAULT_SPEC)]
+    pub spec: String,
+
+    /// Login name at the site
+    #[arg(short, long, default_value = "")]
+    pub login: String,
+
+    /// Counter to raise for a new passphrase with the same site and login
+    #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub counter: u32,
+
+    /// File with the master secret on its first line, or - for stdin
+    #[arg(long, value_name = "FILE")]
+    pub master_file: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct DecryptArgs {
+    /// age encrypted file, binary or armored, or - for stdin
+    #[arg(default_value = "-")]
+    pub input: PathBuf,
+
+    /// Identity file with the private key, as written by age-keygen. Can be repeated
+    #[arg(short, long, value_name = "FILE")]
+    pub identity: Vec<PathBuf>,
+
+    /// File with the passphrase on its first line, for passphrase encrypted files
+    #[arg(long, value_name = "FILE")]
+    pub passphrase_file: Option<PathBuf>,
+
+    /// File to write the plaintext to instead of stdout. Only the owner can read it
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate_args(cli: Cli) -> GenerateArgs {
+        let Command::Generate(args) = cli.command else {
+            panic!("expected generate command");
+        };
+        args
+    }
+
+    #[test]
+    fn defaults() {
+        let cli = Cli::try_parse_args(["mpassgen"]).unwrap();
+        assert_eq!(cli.lists.seed, None);
+        assert_eq!(cli.lists.language, Language::English);
+
+        let args = generate_args(cli);
+        assert_eq!(args.spec, DEFAULT_SPEC);
+        assert_eq!(args.count, 1);
+        assert!(!args.entropy);
+        assert_eq!(args.format, OutputFormat::Plain);
+    }
+
+    #[test]
+    fn all_options() {
+        let cli = Cli::try_parse_args(["mpassgen", "ww##", "-n", "5", "--words", "w.txt", "--symbols", "s.txt",
+                                       "--entropy", "--seed", "7", "--spell", "--language", "de"]).unwrap();
+
+        assert_eq!(cli.lists.words, Some(PathBuf::from("w.txt")));
+        assert_eq!(cli.lists.symbols, Some(PathBuf::from("s.txt")));
+        assert_eq!(cli.lists.seed, Some(7));
+        assert_eq!(cli.lists.language, Language::German);
+        let args = generate_args(cli);
+        assert_eq!(args.spec, "ww##");
+        assert_eq!(args.count, 5);
+        assert!(args.entropy);
+        assert!(args.spell);
+        assert!(Cli::try_parse_args(["mpassgen", "--language", "xx"]).is_err());
+
+        let cli = Cli::try_parse_args(["mpassgen", "--format", "ndjson"]).unwrap();
+        assert_eq!(generate_args(cli).format, OutputFormat::Ndjson);
+
+        let args = generate_args(Cli::try_parse_args(["mpassgen", "--hash", "sha512crypt", "--hash-cost", "10000"]).unwrap());
+        assert_eq!(args.hash, Some(HashAlgorithm::Sha512Crypt));
+        assert_eq!(args.hash_cost, Some(10000));
+        assert!(Cli::try_parse_args(["mpassgen", "--hash", "md5"]).is_err());
+        assert!(Cli::try_parse_args(["mpassgen", "--hash-cost", "4"]).is_err());
+    }
+
+    #[test]
+    fn global_options_before_command() {
+        let cli = Cli::try_parse_args(["mpassgen", "--seed", "1", "handout", "users.csv"]).unwrap();
+        assert_eq!(cli.lists.seed, Some(1));
+        assert!(matches!(cli.command, Command::Handout(_)));
+
+        let cli = Cli::try_parse_args(["mpassgen", "--encrypt-passphrase-file", "pp", "render", "t.txt"]).unwrap();
+        assert_eq!(cli.encrypt.encrypt_passphrase_file, Some(PathBuf::from("pp")));
+        assert!(matches!(cli.command, Command::Render(_)));
+
+        let cli = Cli::try_parse_args(["mpassgen", "--seed", "2", "generate", "ww", "--words", "w.txt"]).unwrap();
+        assert_eq!((cli.lists.seed, cli.lists.words.clone()), (Some(2), Some(PathBuf::from("w.txt"))));
+        assert_eq!(generate_args(cli).spec, "ww");
+        let cli = Cli::try_parse_args(["mpassgen", "--seed", "2", "-n", "3", "ww"]).unwrap();
+        assert_eq!(generate_args(cli).count, 3);
+
+        //option values that happen to be command names don't pick the command
+        let cli = Cli::try_parse_args(["mpassgen", "--words", "render", "w"]).unwrap();
+        assert_eq!(cli.lists.words, Some(PathBuf::from("render")));
+        assert_eq!(generate_args(cli).spec, "w");
+        let cli = Cli::try_parse_args(["mpassgen", "--hash", "bcrypt", "--words", "qr"]).unwrap();
+        assert_eq!(generate_args(cli).spec, DEFAULT_SPEC);
+        let cli = Cli::try_parse_args(["mpassgen", "--words=qr", "qr", "-o", "x.png"]).unwrap();
+        assert_eq!(cli.lists.words, Some(PathBuf::from("qr")));
+        assert!(matches!(cli.command, Command::Qr(_)));
+    }
+
+    #[test]
+    fn render() {
+        let cli = Cli::try_parse_args(["mpassgen", "render", "in.html", "-o", "out.html", "--seed", "3"]).unwrap();
+
+        let Command::Render(args) = cli.command else {
+            panic!("expected render command");
+        };
+        assert_eq!(args.template, PathBuf::from("in.html"));
+        assert_eq!(args.output, Some(PathBuf::from("out.html")));
+        assert_eq!(cli.lists.seed, Some(3));
+
+        let cli = Cli::try_parse_args(["mpassgen", "render"]).unwrap();
+        assert!(matches!(cli.command, Command::Render(RenderArgs { output: None, escape: Escape::Auto, .. })));
+
+        let cli = Cli::try_parse_args(["mpassgen", "render", "--escape", "shell"]).unwrap();
+        assert!(matches!(cli.command, Command::Render(RenderArgs { escape: Escape::Shell, .. })));
+    }
+
+    #[test]
+    fn merge() {
+        let cli = Cli::try_parse_args(["mpassgen", "merge", "letter.html", "users.csv",
+                                       "-o", "out/<<username>>.html", "--summary", "sent.csv"]).unwrap();
+
+        let Command::Merge(args) = cli.command else {
+            panic!("expected merge command");
+        };
+        assert_eq!(args.template, PathBuf::from("letter.html"));
+        assert_eq!(args.csv, PathBuf::from("users.csv"));
+        assert_eq!(args.output, "out/<<username>>.html");
+        assert_eq!(args.summary, Some(PathBuf::from("sent.csv")));
+
+        assert!(Cli::try_parse_args(["mpassgen", "merge", "letter.html", "users.csv"]).is_err());
+    }
+
+    #[test]
+    fn handout() {
+        let cli = Cli::try_parse_args(["mpassgen", "handout", "users.csv", "-o", "cards.pdf", "--qr",
+                                       "--columns", "3", "--fields", "name,username"]).unwrap();
+
+        let Command::Handout(args) = cli.command else {
+            panic!("expected handout command");
+        };
+        assert_eq!(args.output, Some(PathBuf::from("cards.pdf")));
+        assert_eq!(args.format, None);
+        assert_eq!(args.spec, DEFAULT_SPEC);
+        assert!(args.layout.qr);
+        assert_eq!((args.layout.columns, args.layout.rows), (3, 4));
+        assert_eq!(args.layout.fields, Some(vec!["name".to_string(), "username".to_string()]));
+
+        assert!(Cli::try_parse_args(["mpassgen", "handout", "users.csv", "--columns", "0"]).is_err());
+    }
+
+    #[test]
+    fn qr() {
+        let cli = Cli::try_parse_args(["mpassgen", "qr", "w w ##", "--wifi", "Guest", "--hidden"]).unwrap();
+
+        let Command::Qr(args) = cli.command else {
+            panic!("expected qr command");
+        };
+        assert_eq!(args.spec, "w w ##");
+        assert_eq!(args.wifi.as_deref(), Some("Guest"));
+        assert_eq!(args.security, WifiSecurity::Wpa);
+        assert!(args.hidden);
+        assert_eq!((args.format, args.scale), (None, 8));
+
+        assert!(Cli::try_parse_args(["mpassgen", "qr", "--hidden"]).is_err());
+    }
+
+    #[test]
+    fn accounts() {
+        let cli = Cli::try_parse_args(["mpassgen", "htpasswd", "users.txt", "--handoff", "pw.csv",
+                                       "--scheme", "apr1", "-o", ".htpasswd"]).unwrap();
+
+        let Command::Htpasswd(args) = cli.command else {
+            panic!("expected htpasswd command");
+        };
+        assert_eq!(args.accounts.users, PathBuf::from("users.txt"));
+        assert_eq!(args.accounts.handoff, PathBuf::from("pw.csv"));
+        assert_eq!(args.accounts.output, Some(PathBuf::from(".htpasswd")));
+        assert_eq!(args.accounts.spec, DEFAULT_SPEC);
+        assert_eq!((args.scheme, args.cost), (HtpasswdScheme::Apr1, None));
+
+        let cli = Cli::try_parse_args(["mpassgen", "chpasswd", "-", "--handoff", "pw.csv", "--shadow"]).unwrap();
+        assert!(matches!(cli.command, Command::Chpasswd(ChpasswdArgs { shadow: true, rounds: None, .. })));
+
+        assert!(Cli::try_parse_args(["mpassgen", "chpasswd", "users.txt"]).is_err());
+    }
+
+    #[test]
+    fn export() {
+        let cli = Cli::try_parse_args(["mpassgen", "export", "accounts.csv", "--format", "1password-csv"]).unwrap();
+
+        let Command::Export(args) = cli.command else {
+            panic!("expected export command");
+        };
+        assert_eq!(args.csv, PathBuf::from("accounts.csv"));
+        assert_eq!((args.format, args.output, args.spec.as_str()), (ExportFormat::OnepasswordCsv, None, DEFAULT_SPEC));
+
+        assert!(Cli::try_parse_args(["mpassgen", "export", "accounts.csv"]).is_err());
+    }
+
+    #[test]
+    fn vault() {
+        let cli = Cli::try_parse_args(["mpassgen", "vault", "accounts.csv", "-o", "team.kdbx", "--cipher", "aes256",
+                                       "--kdf-memory", "256"]).unwrap();
+
+        let Command::Vault(args) = cli.command else {
+            panic!("expected vault command");
+        };
+        assert_eq!(args.output, PathBuf::from("team.kdbx"));
+        assert_eq!((args.spec.as_str(), args.master_spec.as_str()), (DEFAULT_SPEC, DEFAULT_MASTER_SPEC));
+        assert_eq!(args.cipher, VaultCipher::Aes256);
+        assert_eq!(args.kdf, KdfParams { memory_mib: 256, iterations: 10, parallelism: 2 });
+
+        assert!(Cli::try_parse_args(["mpassgen", "vault", "accounts.csv"]).is_err());
+        assert!(Cli::try_parse_args(["mpassgen", "vault", "a.csv", "-o", "a.kdbx", "--kdf-iterations", "0"]).is_err());
+    }
+
+    #[test]
+    fn secrets() {
+        let cli = Cli::try_parse_args(["mpassgen", "secrets", "app.secrets", "-o", "secret.yaml",
+                                       "--name", "app", "--namespace", "prod"]).unwrap();
+
+        let Command::Secrets(args) = cli.command else {
+            panic!("expected secrets command");
+        };
+        assert_eq!(args.mapping, PathBuf::from("app.secrets"));
+        assert_eq!(args.format, None);
+        assert_eq!((args.name.as_str(), args.namespace.as_deref()), ("app", Some("prod")));
+
+        let cli = Cli::try_parse_args(["mpassgen", "secrets", "-", "--format", "systemd"]).unwrap();
+        assert!(matches!(cli.command, Command::Secrets(SecretsArgs { format: Some(SecretFormat::Systemd), .. })));
+    }
+
+    #[test]
+    fn encryption() {
+        let cli = Cli::try_parse_args(["mpassgen", "-n", "3", "--encrypt-to", "age1a", "--encrypt-to", "age1b",
+                                       "--encrypt-to-file", "team.txt", "--armor"]).unwrap();
+        assert_eq!(cli.encrypt.encrypt_to, ["age1a", "age1b"]);
+        assert_eq!(cli.encrypt.encrypt_to_file, [PathBuf::from("team.txt")]);
+        assert!(cli.encrypt.armor);
+
+        let cli = Cli::try_parse_args(["mpassgen", "secrets", "app.secrets", "--encrypt-passphrase-file", "pass"]).unwrap();
+        assert_eq!(cli.encrypt.encrypt_passphrase_file, Some(PathBuf::from("pass")));
+        assert!(Cli::try_parse_args(["mpassgen", "--encrypt-to", "age1a", "--encrypt-passphrase-file", "pass"]).is_err());
+
+        let cli = Cli::try_parse_args(["mpassgen", "decrypt", "-i", "key.txt", "-i", "old.txt", "-o", "out.csv"]).unwrap();
+        let Command::Decrypt(args) = cli.command else {
+            panic!("expected decrypt command");
+        };
+        assert_eq!(args.input, PathBuf::from("-"));
+        assert_eq!(args.identity, [PathBuf::from("key.txt"), PathBuf::from("old.txt")]);
+        assert_eq!(args.output, Some(PathBuf::from("out.csv")));
+        for option in [&["--encrypt-to", "age1a"][..], &["--encrypt-passphrase-file", "pass"], &["--armor"]] {
+            let e = Cli::try_parse_args(["mpassgen", "decrypt", "-i", "key.txt"].iter().chain(option)).unwrap_err();
+            assert_eq!(e.kind(), ErrorKind::ArgumentConflict);
+        }
+    }
+
+    #[test]
+    fn derive() {
+        let cli = Cli::try_parse_args(["mpassgen", "derive", "example.com", "xxxxxxxxxxxx", "--login", "alice",
+                                       "-c", "3", "--master-file", "-"]).unwrap();
+
+        let Command::Derive(args) = cli.command else {
+            panic!("expected derive command");
+        };
+        assert_eq!((args.site.as_str(), args.spec.as_str(), args.login.as_str()), ("example.com", "xxxxxxxxxxxx", "alice"));
+        assert_eq!((args.counter, args.master_file), (3, PathBuf::from("-")));
+
+        let cli = Cli::try_parse_args(["mpassgen", "derive", "example.com", "--master-file", "master"]).unwrap();
+        assert!(matches!(cli.command, Command::Derive(DeriveArgs { counter: 1, .. })));
+        assert!(Cli::try_parse_args(["mpassgen", "derive", "example.com", "--master-file", "m", "-c", "0"]).is_err());
+        assert!(Cli::try_parse_args(["mpassgen", "derive", "example.com"]).is_err());
+    }
+
+    #[test]
+    fn apikey() {
+        let cli = Cli::try_parse_args(["mpassgen", "apikey", "acme_live", "-n", "3", "--length", "40"]).unwrap();
+
+        let Command::Apikey(args) = cli.command else {
+            panic!("expected apikey command");
+        };
+        assert_eq!((args.prefix.as_str(), args.count, args.length), ("acme_live", 3, 40));
+        assert!(Cli::try_parse_args(["mpassgen", "apikey", "acme", "--length", "0"]).is_err());
+
+        let cli = Cli::try_parse_args(["mpassgen", "check-apikey", "acme_a_1", "acme_b_2", "--prefix", "acme"]).unwrap();
+        let Command::CheckApikey(args) = cli.command else {
+            panic!("expected check-apikey command");
+        };
+        assert_eq!(args.keys, ["acme_a_1", "acme_b_2"]);
+        assert_eq!((args.prefix.as_deref(), args.length), (Some("acme"), None));
+        assert!(Cli::try_parse_args(["mpassgen", "check-apikey", "--length", "32"]).is_err());
+    }
+
+    #[test]
+    fn totp() {
+        let cli = Cli::try_parse_args(["mpassgen", "totp", "alice", "--issuer", "Acme", "--algorithm", "sha256"]).unwrap();
+        let Command::Totp(args) = cli.command else {
+            panic!("expected totp command");
+        };
+        assert_eq!((args.bytes, args.algorithm, args.digits, args.period), (20, TotpAlgorithm::Sha256, 6, 30));
+        assert!(Cli::try_parse_args(["mpassgen", "totp", "alice"]).is_err());
+        assert!(Cli::try_parse_args(["mpassgen", "totp", "alice", "--issuer", "Acme", "--bytes", "10"]).is_err());
+        assert!(Cli::try_parse_args(["mpassgen", "totp", "alice", "--issuer", "Acme", "--digits", "4"]).is_err());
+
+        let cli = Cli::try_parse_args(["mpassgen", "totp-code", "--check", "123456", "--time", "59"]).unwrap();
+        let Command::TotpCode(args) = cli.command else {
+            panic!("expected totp-code command");
+        };
+        assert_eq!((args.secret.as_str(), args.check.as_deref(), args.time, args.window), ("-", Some("123456"), Some(59), 1));
+        assert!(Cli::try_parse_args(["mpassgen", "totp-code", "--window", "2"]).is_err());
+        assert!(Cli::try_parse_args(["mpassgen", "totp-code", "--check", "1", "--window", "10"]).is_ok());
+        assert!(Cli::try_parse_args(["mpassgen", "totp-code", "--check", "1", "--window", "11"]).is_err());
+    }
+}
@@ -0,0 +1,175 @@
+//! Encrypting output with [age](https://age-encryption.org).
+//!
+//! Output is encrypted to X25519 recipients, the `age1...` keys made by
+//! `age-keygen`, or to a passphrase, and can be ASCII armored. Files are
+//! readable by `age -d` and `rage -d`, and by [`decrypt`].
+
+use std::io::{Read, Write};
+use age::armor::{ArmoredReader, ArmoredWriter, Format};
+use age::secrecy::SecretString;
+use age::{scrypt, x25519};
+use zeroize::Zeroizing;
+use crate::error::CliError;
+
+/// Who can decrypt the output.
+pub struct Encryption {
+    recipients: Vec<Box<dyn age::Recipient>>,
+    armor: bool
+}
+
+impl Encryption {
+    /// Encrypt to the holders of the identities for these recipients.
+    pub fn to_recipients(recipients: Vec<x25519::Recipient>, armor: bool) -> Self {
+        let recipients = recipients.into_iter().map(|r| Box::new(r) as Box<dyn age::Recipient>).collect();
+        Encryption { recipients, armor }
+    }
+
+    /// Encrypt to a passphrase. The scrypt work factor is age's default,
+    /// which is tuned to take about a second to derive the key.
+    pub fn to_passphrase(passphrase: SecretString, armor: bool) -> Self {
+        Encryption { recipients: vec![Box::new(scrypt::Recipient::new(passphrase))], armor }
+    }
+
+    /// Whether the output is armored text rather than binary.
+    pub fn is_armored(&self) -> bool {
+        self.armor
+    }
+
+    /// Encrypt a whole file.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, CliError> {
+        let encryptor = age::Encryptor::with_recipients(self.recipients.iter().map(|r| r.as_ref()))
+                                       .map_err(|e| CliError::Encryption(e.to_string()))?;
+        let format = if self.armor { Format::AsciiArmor } else { Format::Binary };
+        //only the writer can fail, and writing to memory can't
+        let error = |e: std::io::Error| CliError::Encryption(e.to_string());
+
+        let armored = ArmoredWriter::wrap_output(Vec::new(), format).map_err(error)?;
+        let mut writer = encryptor.wrap_output(armored).map_err(error)?;
+        writer.write_all(plaintext).map_err(error)?;
+        writer.finish()
+              .and_then(|armored| armored.finish())
+              .map_err(error)
+    }
+}
+
+/// Parse an `age1...` recipient.
+pub fn parse_recipient(key: &str) -> Result<x25519::Recipient, CliError> {
+    key.parse().map_err(|e| CliError::Encryption(format!("invalid recipient '{}': {}", key, e)))
+}
+
+/// Read recipients one per line, skipping blank lines and `#` comments,
+/// as in the recipients files `age -R` takes.
+pub fn parse_recipients(input: &str) -> Result<Vec<x25519::Recipient>, CliError> {
+    parse_keys(input, "recipient")
+}
+
+/// Read the identities in an identity file, as written by `age-keygen`.
+pub fn parse_identities(input: &str) -> Result<Vec<x25519::Identity>, CliError> {
+    parse_keys(input, "identity")
+}
+
+fn parse_keys<K: std::str::FromStr<Err = &'static str>>(input: &str, kind: &str) -> Result<Vec<K>, CliError> {
+    input.lines()
+         .enumerate()
+         .map(|(n, line)| (n, line.trim()))
+         .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+         .map(|(n, line)| {
+             line.parse().map_err(|e| CliError::Line {
+                 line: n + 1,
+                 error: Box::new(CliError::Encryption(format!("invalid {}: {}", kind, e)))
+             })
+         })
+         .collect()
+}
+
+/// The passphrase in a passphrase file: its first line, which can't be empty.
+pub fn read_passphrase(input: &str) -> Result<SecretString, CliError> {
+    match input.lines().next() {
+        Some(line) if !line.is_empty() => Ok(SecretString::from(line.to_string())),
+        _ => Err(CliError::Encryption("the passphrase file is empty".to_string()))
+    }
+}
+
+/// Decrypt a binary or armored file with identities or a passphrase.
+pub fn decrypt(ciphertext: &[u8], identities: &[x25519::Identity],
+               passphrase: Option<SecretString>) -> Result<Zeroizing<Vec<u8>>, CliError> {
+    let error = |e: age::DecryptError| CliError::Encryption(e.to_string());
+    let decryptor = age::Decryptor::new_buffered(ArmoredReader::new(ciphertext)).map_err(error)?;
+
+    let mut keys: Vec<Box<dyn age::Identity>> = Vec::new();
+    match passphrase {
+        Some(passphrase) if decryptor.is_scrypt() => keys.push(Box::new(scrypt::Identity::new(passphrase))),
+        None if decryptor.is_scrypt() => {
+            return Err(CliError::Encryption("the file is encrypted to a passphrase".to_string()));
+        }
+        _ => keys.extend(identities.iter().map(|i| Box::new(i.clone()) as Box<dyn age::Identity>))
+    }
+    if keys.is_empty() {
+        return Err(CliError::Encryption("the file is encrypted to recipients, and no identity was given".to_string()));
+    }
+
+    let mut reader = decryptor.decrypt(keys.iter().map(|k| k.as_ref())).map_err(error)?;
+    let mut plaintext = Zeroizing::new(Vec::new());
+    reader.read_to_end(&mut plaintext).map_err(|e| CliError::Encryption(e.to_string()))?;
+
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAINTEXT: &[u8] = b"alice,Sturdy pasta 381!\nbob,Fuzzy otter 552#\n";
+
+    #[test]
+    fn recipients_round_trip() {
+        let alice = x25519::Identity::generate();
+        let bob = x25519::Identity::generate();
+        let encryption = Encryption::to_recipients(vec![alice.to_public(), bob.to_public()], false);
+        let ciphertext = encryption.encrypt(PLAINTEXT).unwrap();
+        assert!(ciphertext.starts_with(b"age-encryption.org/v1\n-> X25519 "));
+
+        assert_eq!(*decrypt(&ciphertext, std::slice::from_ref(&bob), None).unwrap(), PLAINTEXT);
+        assert_eq!(*decrypt(&ciphertext, &[x25519::Identity::generate(), alice], None).unwrap(), PLAINTEXT);
+        assert!(decrypt(&ciphertext, &[x25519::Identity::generate()], None).is_err());
+        assert!(decrypt(&ciphertext, &[], None).is_err());
+
+        let mut tampered = ciphertext.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(decrypt(&tampered, &[bob], None).is_err());
+    }
+
+    #[test]
+    fn armored_passphrase_round_trip() {
+        let passphrase = || SecretString::from("Correct horse 12!".to_string());
+        let ciphertext = Encryption::to_passphrase(passphrase(), true).encrypt(PLAINTEXT).unwrap();
+        let text = String::from_utf8(ciphertext).unwrap();
+        assert!(text.starts_with("-----BEGIN AGE ENCRYPTED FILE-----\n"));
+        assert!(text.ends_with("-----END AGE ENCRYPTED FILE-----\n"));
+
+        assert_eq!(*decrypt(text.as_bytes(), &[], Some(passphrase())).unwrap(), PLAINTEXT);
+        assert!(decrypt(text.as_bytes(), &[], Some(SecretString::from("wrong".to_string()))).is_err());
+        assert!(decrypt(text.as_bytes(), &[x25519::Identity::generate()], None).is_err());
+    }
+
+    #[test]
+    fn parses_keys() {
+        let identity = x25519::Identity::generate();
+        let file = format!("# created: 2024-01-01T00:00:00Z\n# public key: {}\n{}\n",
+                           identity.to_public(), age::secrecy::ExposeSecret::expose_secret(&identity.to_string()));
+        assert_eq!(parse_identities(&file).unwrap().len(), 1);
+
+        let recipients = parse_recipients(&format!("\n# ops\n  {}\n", identity.to_public())).unwrap();
+        assert_eq!(parse_recipient(&identity.to_public().to_string()).unwrap().to_string(), recipients[0].to_string());
+        assert_eq!(parse_recipient("ssh-ed25519").unwrap_err().to_string(),
+                   "age: invalid recipient 'ssh-ed25519': invalid Bech32 encoding");
+        assert_eq!(recipients[0].to_string(), identity.to_public().to_string());
+        let error = parse_recipients("# ops\nage1nope").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: age: invalid recipient: invalid Bech32 encoding");
+        assert!(parse_identities(&identity.to_public().to_string()).is_err());
+
+        assert!(read_passphrase("\nsecond line").is_err());
+        assert!(read_passphrase("").is_err());
+        assert_eq!(age::secrecy::ExposeSecret::expose_secret(&read_passphrase("pass word\n").unwrap()), "pass word");
+    }
+}
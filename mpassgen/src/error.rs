@@ -0,0 +1,236 @@
+//! Errors reported by mpassgen and the exit codes they map to.
+
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::PathBuf;
+use mpass::apikey::ApiKeyError;
+use mpass::derive::DeriveError;
+use mpass::hash::HashError;
+use mpass::specifier::specifier_error::SpecifierError;
+use mpass::totp::TotpError;
+use qrcode::types::QrError;
+use crate::template::{TemplateError, TemplateErrorKind};
+
+/// Exit code for a spec string that is invalid or contains no tokens.
+pub const EXIT_INVALID_SPEC: i32 = 3;
+/// Exit code for an empty word list.
+pub const EXIT_EMPTY_WORD_LIST: i32 = 4;
+/// Exit code for an empty symbol list.
+pub const EXIT_EMPTY_SYMBOL_LIST: i32 = 5;
+/// Exit code when every generated passphrase was rejected by a check.
+pub const EXIT_ATTEMPTS_EXHAUSTED: i32 = 6;
+/// Exit code for a malformed template.
+pub const EXIT_TEMPLATE: i32 = 7;
+/// Exit code for malformed input data, such as a bad CSV file or a value too
+/// long for a QR code, or output that would overwrite itself.
+pub const EXIT_DATA: i32 = 8;
+/// Exit code for a passphrase that could not be hashed, such as with a cost
+/// the algorithm does not accept.
+pub const EXIT_HASH: i32 = 9;
+/// Exit code for a KeePass database that could not be written or did not
+/// read back as written.
+pub const EXIT_VAULT: i32 = 10;
+/// Exit code for output that could not be encrypted, or a file that could
+/// not be decrypted with the keys given.
+pub const EXIT_ENCRYPTION: i32 = 11;
+/// Exit code for input that was checked and found invalid, such as an API
+/// key with a bad checksum or a wrong TOTP code.
+pub const EXIT_REJECTED: i32 = 12;
+/// Exit code for a file that could not be read or written.
+pub const EXIT_IO: i32 = 74;
+
+#[derive(Debug)]
+pub enum CliError {
+    /// The spec string has an invalid character at the given byte offset,
+    /// or no tokens at all if the offset is the spec length.
+    InvalidSpec { spec: String, offset: usize },
+    Specifier(SpecifierError),
+    Template(TemplateError),
+    Csv(csv::Error),
+    Hash(HashError),
+    Derive(DeriveError),
+    ApiKey(ApiKeyError),
+    /// Some of the API keys checked are invalid: how many, of how many.
+    InvalidApiKeys { invalid: usize, total: usize },
+    Totp(TotpError),
+    /// A TOTP code checked against a secret is wrong.
+    InvalidTotpCode,
+    /// A KeePass database could not be written or read.
+    Vault(String),
+    /// Output could not be age encrypted, or input decrypted.
+    Encryption(String),
+    /// The data does not fit in a QR code.
+    Qr(QrError),
+    /// A handout card that can't be printed as generated.
+    Handout(String),
+    /// A column named on the command line is not in the CSV header.
+    UnknownColumn(String),
+    /// A user name that the output format can't hold, on a line starting at 1.
+    InvalidUsername { line: usize, name: String },
+    /// A user name listed more than once.
+    DuplicateUsername(String),
+    /// A line of a secrets mapping file that can't be used.
+    InvalidMapping(String),
+    /// Two rows of a merge would write the same file.
+    DuplicateOutput(PathBuf),
+    /// An error while processing a row of a merge, starting at 1.
+    Row { row: usize, error: Box<CliError> },
+    /// An error on a line of an input file, starting at 1.
+    Line { line: usize, error: Box<CliError> },
+    Io { path: PathBuf, error: io::Error }
+}
+
+impl CliError {
+    /// Get the process exit code for this error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::InvalidSpec { .. } => EXIT_INVALID_SPEC,
+            CliError::Specifier(e) => specifier_exit_code(e),
+            CliError::Template(e) => match &e.kind {
+                TemplateErrorKind::InvalidSpec(_) => EXIT_INVALID_SPEC,
+                TemplateErrorKind::ConflictingDefinition { .. }
+                | TemplateErrorKind::Undefined(_)
+                | TemplateErrorKind::FieldConflict(_)
+                | TemplateErrorKind::Filter(_) => EXIT_TEMPLATE,
+                TemplateErrorKind::Specifier(e) => specifier_exit_code(e)
+            },
+            CliError::Csv(_)
+            | CliError::Derive(_)
+            | CliError::ApiKey(_)
+            | CliError::Totp(_)
+            | CliError::Qr(_)
+            | CliError::Handout(_)
+            | CliError::UnknownColumn(_)
+            | CliError::InvalidUsername { .. }
+            | CliError::DuplicateUsername(_)
+            | CliError::InvalidMapping(_)
+            | CliError::DuplicateOutput(_) => EXIT_DATA,
+            CliError::Hash(_) => EXIT_HASH,
+            CliError::Vault(_) => EXIT_VAULT,
+            CliError::Encryption(_) => EXIT_ENCRYPTION,
+            CliError::InvalidApiKeys { .. } | CliError::InvalidTotpCode => EXIT_REJECTED,
+            CliError::Row { error, .. } | CliError::Line { error, .. } => error.exit_code(),
+            CliError::Io { .. } => EXIT_IO
+        }
+    }
+
+    pub fn io(path: impl Into<PathBuf>, error: io::Error) -> Self {
+        CliError::Io { path: path.into(), error }
+    }
+}
+
+fn specifier_exit_code(e: &SpecifierError) -> i32 {
+    match e {
+        SpecifierError::SpecTokenError(_) | SpecifierError::UnrecognizedChar(_) => EXIT_INVALID_SPEC,
+        SpecifierError::EmptyWordList => EXIT_EMPTY_WORD_LIST,
+        SpecifierError::EmptySymbolList => EXIT_EMPTY_SYMBOL_LIST,
+        SpecifierError::AttemptsExhausted(_) => EXIT_ATTEMPTS_EXHAUSTED,
+        SpecifierError::CheckFailed(_) => EXIT_IO
+    }
+}
+
+impl From<SpecifierError> for CliError {
+    fn from(value: SpecifierError) -> Self {
+        CliError::Specifier(value)
+    }
+}
+
+impl From<HashError> for CliError {
+    fn from(value: HashError) -> Self {
+        CliError::Hash(value)
+    }
+}
+
+impl From<ApiKeyError> for CliError {
+    fn from(value: ApiKeyError) -> Self {
+        CliError::ApiKey(value)
+    }
+}
+
+impl From<TotpError> for CliError {
+    fn from(value: TotpError) -> Self {
+        CliError::Totp(value)
+    }
+}
+
+impl From<DeriveError> for CliError {
+    fn from(value: DeriveError) -> Self {
+        CliError::Derive(value)
+    }
+}
+
+impl From<TemplateError> for CliError {
+    fn from(value: TemplateError) -> Self {
+        CliError::Template(value)
+    }
+}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::InvalidSpec { spec, offset } => {
+                match spec[*offset..].chars().next() {
+                    Some(ch) => writeln!(f, "invalid character '{}' at offset {} in spec", ch, offset)?,
+                    None => writeln!(f, "spec contains no tokens")?
+                }
+                // point at the offending character below the spec
+                let column = spec[..*offset].chars().count();
+                writeln!(f, "  \"{}\"", spec)?;
+                write!(f, "   {}^", " ".repeat(column))
+            }
+            CliError::Specifier(e) => write!(f, "{}", e),
+            CliError::Template(e) => write!(f, "{}", e),
+            CliError::Csv(e) => write!(f, "CSV: {}", e),
+            CliError::Qr(e) => write!(f, "QR code: {}", e),
+            CliError::Handout(message) => write!(f, "handout: {}", message),
+            CliError::Hash(e) => write!(f, "hash {}", e),
+            CliError::Derive(e) => write!(f, "{}", e),
+            CliError::ApiKey(e) => write!(f, "API key: {}", e),
+            CliError::InvalidApiKeys { invalid, total } => write!(f, "{} of {} API keys are invalid", invalid, total),
+            CliError::Totp(e) => write!(f, "TOTP: {}", e),
+            CliError::InvalidTotpCode => write!(f, "the TOTP code is not valid"),
+            CliError::Vault(message) => write!(f, "KeePass database: {}", message),
+            CliError::Encryption(message) => write!(f, "age: {}", message),
+            CliError::UnknownColumn(name) => write!(f, "no column named '{}' in the CSV header", name),
+            CliError::InvalidUsername { line, name } => write!(f, "line {}: invalid user name '{}'", line, name),
+            CliError::DuplicateUsername(name) => write!(f, "user name '{}' is listed more than once", name),
+            CliError::DuplicateOutput(path) => write!(f, "output file {} is written more than once", path.display()),
+            CliError::InvalidMapping(message) => write!(f, "{}", message),
+            CliError::Row { row, error } => write!(f, "row {}: {}", row, error),
+            CliError::Line { line, error } => write!(f, "line {}: {}", line, error),
+            CliError::Io { path, error } => write!(f, "{}: {}", path.display(), error)
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_at_offset() {
+        let e = CliError::InvalidSpec { spec: "i wqw".to_string(), offset: 3 };
+
+        assert_eq!(e.to_string(), "invalid character 'q' at offset 3 in spec\n  \"i wqw\"\n      ^");
+        assert_eq!(e.exit_code(), EXIT_INVALID_SPEC);
+    }
+
+    #[test]
+    fn empty_spec() {
+        let e = CliError::InvalidSpec { spec: "??".to_string(), offset: 2 };
+
+        assert!(e.to_string().starts_with("spec contains no tokens"));
+    }
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(CliError::from(SpecifierError::EmptyWordList).exit_code(), EXIT_EMPTY_WORD_LIST);
+        assert_eq!(CliError::from(SpecifierError::EmptySymbolList).exit_code(), EXIT_EMPTY_SYMBOL_LIST);
+        assert_eq!(CliError::from(SpecifierError::AttemptsExhausted(3)).exit_code(), EXIT_ATTEMPTS_EXHAUSTED);
+        assert_eq!(CliError::InvalidApiKeys { invalid: 1, total: 2 }.exit_code(), EXIT_REJECTED);
+        assert_eq!(CliError::InvalidTotpCode.exit_code(), EXIT_REJECTED);
+        assert_eq!(CliError::from(ApiKeyError::ZeroLength).exit_code(), EXIT_DATA);
+    }
+}
@@ -0,0 +1,182 @@
+//! Escaping of values substituted into templates.
+//!
+//! Each mode makes a value safe to place inside a string of the given
+//! format. For formats with quoted strings the template supplies the quotes:
+//!     html  - <p><<pw>></p> or <input value="<<pw>>">
+//!     json  - "password": "<<pw>>"
+//!     yaml  - password: "<<pw>>"
+//!     toml  - password = "<<pw>>"
+//!     shell - PASSWORD='<<pw>>'
+//!     sql   - VALUES ('<<pw>>')
+//!     mysql - VALUES ('<<pw>>')
+//!
+//! `sql` is standard SQL quoting, as used by PostgreSQL, SQLite and SQL
+//! Server, where a backslash is an ordinary character. MySQL and MariaDB
+//! treat backslashes as escapes unless `NO_BACKSLASH_ESCAPES` is set, so a
+//! value ending in one would close the literal there; use `mysql` for them.
+
+use std::path::Path;
+use clap::ValueEnum;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum Escape {
+    /// Pick a mode from the output or template file extension
+    Auto,
+    /// Insert values unchanged
+    None,
+    /// HTML and XML text and quoted attributes
+    Html,
+    /// Inside a JSON string
+    Json,
+    /// Inside a YAML double-quoted string
+    Yaml,
+    /// Inside a TOML basic string
+    Toml,
+    /// Inside a POSIX shell single-quoted string
+    Shell,
+    /// Inside a standard SQL single-quoted string literal, where backslashes
+    /// are not special
+    Sql,
+    /// Inside a MySQL or MariaDB single-quoted string literal, where
+    /// backslashes escape
+    Mysql,
+    /// A single file name component, used for output file patterns
+    #[value(skip)]
+    Filename,
+}
+
+impl Escape {
+    /// Choose the mode for a file from its extension. Returns None for
+    /// unknown extensions.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "html" | "htm" | "xhtml" | "xml" | "svg" => Some(Escape::Html),
+            "json" => Some(Escape::Json),
+            "yaml" | "yml" => Some(Escape::Yaml),
+            "toml" => Some(Escape::Toml),
+            "sh" | "bash" | "zsh" | "ksh" => Some(Escape::Shell),
+            "sql" => Some(Escape::Sql),
+            _ => None
+        }
+    }
+
+    /// Resolve Auto by trying each path in turn, falling back to None.
+    /// Other modes are returned unchanged.
+    pub fn resolve(self, paths: &[Option<&Path>]) -> Self {
+        if self != Escape::Auto {
+            return self;
+        }
+        paths.iter()
+             .flatten()
+             .find_map(|p| Escape::from_path(p))
+             .unwrap_or(Escape::None)
+    }
+
+    /// Escape a value. Auto is treated as None; resolve it first.
+    pub fn apply(self, value: &str) -> String {
+        match self {
+            Escape::Auto | Escape::None => value.to_string(),
+            Escape::Html => escape_chars(value, |c| match c {
+                '&' => Some("&amp;".to_string()),
+                '<' => Some("&lt;".to_string()),
+                '>' => Some("&gt;".to_string()),
+                '"' => Some("&quot;".to_string()),
+                '\'' => Some("&#39;".to_string()),
+                _ => None
+            }),
+            Escape::Json => escape_chars(value, |c| match c {
+                '"' => Some("\\\"".to_string()),
+                '\\' => Some("\\\\".to_string()),
+                '\n' => Some("\\n".to_string()),
+                '\r' => Some("\\r".to_string()),
+                '\t' => Some("\\t".to_string()),
+                c if (c as u32) < 0x20 => Some(format!("\\u{:04x}", c as u32)),
+                _ => None
+            }),
+            Escape::Yaml => escape_chars(value, |c| match c {
+                '"' => Some("\\\"".to_string()),
+                '\\' => Some("\\\\".to_string()),
+                '\n' => Some("\\n".to_string()),
+                '\r' => Some("\\r".to_string()),
+                '\t' => Some("\\t".to_string()),
+                c if (c as u32) < 0x20 || c as u32 == 0x7f => Some(format!("\\x{:02x}", c as u32)),
+                _ => None
+            }),
+            Escape::Toml => escape_chars(value, |c| match c {
+                '"' => Some("\\\"".to_string()),
+                '\\' => Some("\\\\".to_string()),
+                '\n' => Some("\\n".to_string()),
+                '\r' => Some("\\r".to_string()),
+                '\t' => Some("\\t".to_string()),
+                c if (c as u32) < 0x20 || c as u32 == 0x7f => Some(format!("\\u{:04X}", c as u32)),
+                _ => None
+            }),
+            // a single quote can't appear inside single quotes, so close the
+            // string, add an escaped quote and reopen it
+            Escape::Shell => value.replace('\'', "'\\''"),
+            Escape::Sql => value.replace('\'', "''"),
+            Escape::Mysql => value.replace('\\', "\\\\").replace('\'', "''"),
+            // keep values from adding directories or escaping the output directory
+            Escape::Filename => {
+                let escaped = escape_chars(value, |c| match c {
+                    '/' | '\\' | ':' => Some("_".to_string()),
+                    c if c.is_control() => Some("_".to_string()),
+                    _ => None
+                });
+                if escaped == "." || escaped == ".." {
+                    "_".to_string()
+                }
+                else {
+                    escaped
+                }
+            }
+        }
+    }
+}
+
+fn escape_chars(value: &str, escape: impl Fn(char) -> Option<String>) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match escape(c) {
+            Some(e) => escaped.push_str(&e),
+            None => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes() {
+        let value = "a&b<c>\"d'e\\f$g\n";
+
+        assert_eq!(Escape::None.apply(value), value);
+        assert_eq!(Escape::Html.apply(value), "a&amp;b&lt;c&gt;&quot;d&#39;e\\f$g\n");
+        assert_eq!(Escape::Json.apply(value), "a&b<c>\\\"d'e\\\\f$g\\n");
+        assert_eq!(Escape::Yaml.apply("a\"\u{1}"), "a\\\"\\x01");
+        assert_eq!(Escape::Toml.apply("a\"\u{1}"), "a\\\"\\u0001");
+        assert_eq!(Escape::Shell.apply(value), "a&b<c>\"d'\\''e\\f$g\n");
+        assert_eq!(Escape::Sql.apply("it's"), "it''s");
+        assert_eq!(Escape::Sql.apply("a\\"), "a\\");
+        assert_eq!(Escape::Mysql.apply("it's a\\"), "it''s a\\\\");
+        assert_eq!(Escape::Filename.apply("../a/b\\c:d"), ".._a_b_c_d");
+        assert_eq!(Escape::Filename.apply(".."), "_");
+    }
+
+    #[test]
+    fn resolves_from_paths() {
+        let html = Path::new("letter.HTML");
+        let sql = Path::new("seed.sql");
+        let txt = Path::new("notes.txt");
+
+        assert_eq!(Escape::Auto.resolve(&[Some(txt), Some(html)]), Escape::Html);
+        assert_eq!(Escape::Auto.resolve(&[None, Some(sql)]), Escape::Sql);
+        assert_eq!(Escape::Auto.resolve(&[Some(txt), None]), Escape::None);
+        assert_eq!(Escape::Json.resolve(&[Some(html)]), Escape::Json);
+        assert_eq!(Escape::from_path(Path::new("deploy.yml")), Some(Escape::Yaml));
+    }
+}
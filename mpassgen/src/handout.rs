@@ -0,0 +1,271 @@
+//! Printable credential handouts.
+//!
+//! Each user in a CSV file gets a card with a generated password, its
+//! phonetic spelling and optionally a QR code of the password. The cards are
+//! laid out in a grid on printable pages, either as a self-contained HTML
+//! file or as a PDF.
+
+mod pdf;
+
+use std::io::Write;
+use std::path::Path;
+use clap::{Args, ValueEnum};
+use mpass::{Passphrase, Specifier, Speller};
+use mpass::spoken::Language;
+use zeroize::Zeroizing;
+use crate::error::CliError;
+use crate::escape::Escape;
+use crate::qr::Qr;
+
+pub use pdf::to_pdf;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HandoutFormat {
+    Html,
+    Pdf
+}
+
+impl HandoutFormat {
+    /// Pick the format from a file extension, defaulting to HTML.
+    pub fn from_path(path: Option<&Path>) -> Self {
+        match path.and_then(|p| p.extension()).and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("pdf") => HandoutFormat::Pdf,
+            _ => HandoutFormat::Html
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PageSize {
+    A4,
+    Letter
+}
+
+impl PageSize {
+    /// Width and height in millimetres.
+    pub fn size_mm(self) -> (f64, f64) {
+        match self {
+            PageSize::A4 => (210.0, 297.0),
+            PageSize::Letter => (215.9, 279.4)
+        }
+    }
+}
+
+/// How cards are placed on the page and what they show.
+#[derive(Args, Clone, Debug)]
+pub struct Layout {
+    /// Page size
+    #[arg(long, value_enum, default_value_t = PageSize::A4)]
+    pub page: PageSize,
+
+    /// Cards across each page
+    #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u8).range(1..=6))]
+    pub columns: u8,
+
+    /// Cards down each page
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u8).range(1..=10))]
+    pub rows: u8,
+
+    /// Heading printed on every card
+    #[arg(long, default_value = "Your account")]
+    pub title: String,
+
+    /// CSV columns to print on the cards, in order. Defaults to every column
+    #[arg(long, value_delimiter = ',', value_name = "COLUMNS")]
+    pub fields: Option<Vec<String>>,
+
+    /// Add a QR code of the password to each card
+    #[arg(long)]
+    pub qr: bool,
+}
+
+impl Layout {
+    pub fn cards_per_page(&self) -> usize {
+        self.columns as usize * self.rows as usize
+    }
+}
+
+/// One user's credentials.
+pub struct Card {
+    /// Labels and values of the columns shown on the card.
+    pub fields: Vec<(String, String)>,
+    pub password: Passphrase,
+    pub spelling: Vec<String>,
+    pub qr: Option<Qr>
+}
+
+/// Generate a card for every record in the CSV input, spelling each password
+/// with the speller. If a summary is given, the CSV columns and each password
+/// are written to it.
+pub fn read_cards(csv_input: impl std::io::Read, specifier: &Specifier, speller: &Speller, layout: &Layout,
+                  summary: Option<&mut dyn Write>) -> Result<Vec<Card>, CliError> {
+    let mut reader = csv::Reader::from_reader(csv_input);
+    let headers: Vec<String> = reader.headers()
+                                     .map_err(CliError::Csv)?
+                                     .iter()
+                                     .map(|h| h.trim().to_string())
+                                     .collect();
+    let shown = match &layout.fields {
+        Some(fields) => fields.iter()
+                              .map(|f| headers.iter()
+                                              .position(|h| h == f.trim())
+                                              .ok_or_else(|| CliError::UnknownColumn(f.trim().to_string())))
+                              .collect::<Result<Vec<_>, _>>()?,
+        None => (0..headers.len()).collect()
+    };
+    let mut summary = summary.map(csv::Writer::from_writer);
+    if let Some(summary) = &mut summary {
+        summary.write_record(headers.iter().chain([&"password".to_string()])).map_err(CliError::Csv)?;
+    }
+    let mut cards = Vec::new();
+
+    for (index, record) in reader.records().enumerate() {
+        let in_row = |e: CliError| CliError::Row { row: index + 1, error: Box::new(e) };
+        let record = record.map_err(|e| in_row(CliError::Csv(e)))?;
+        let password = specifier.get_passphrase().map_err(|e| in_row(e.into()))?;
+        let qr = match layout.qr {
+            true => Some(Qr::encode(password.expose().as_bytes()).map_err(in_row)?),
+            false => None
+        };
+
+        if let Some(summary) = &mut summary {
+            summary.write_record(record.iter().chain([password.expose()])).map_err(CliError::Csv)?;
+        }
+        cards.push(Card {
+            fields: shown.iter()
+                         .map(|&i| (headers[i].clone(), record.get(i).unwrap_or_default().to_string()))
+                         .collect(),
+            spelling: speller.spell(password.expose()),
+            password,
+            qr
+        });
+    }
+
+    if let Some(summary) = &mut summary {
+        summary.flush().map_err(|e| CliError::io("<summary>", e))?;
+    }
+    Ok(cards)
+}
+
+/// Render the cards as a standalone HTML document, paginated for printing,
+/// in the language the passwords are spelled in.
+pub fn to_html(cards: &[Card], layout: &Layout, language: Language) -> Zeroizing<String> {
+    let (width, height) = layout.page.size_mm();
+    let page_name = match layout.page {
+        PageSize::A4 => "A4",
+        PageSize::Letter => "letter"
+    };
+    let title = Escape::Html.apply(&layout.title);
+    let mut html = Zeroizing::new(format!("<!DOCTYPE html>\n<html lang=\"{lang}\">\n<head>\n<meta charset=\"utf-8\">\n\
+        <title>{title}</title>\n<style>\n\
+        @page {{ size: {page_name}; margin: {margin}mm; }}\n\
+        body {{ margin: 0; font-family: Helvetica, Arial, sans-serif; }}\n\
+        .page {{ display: grid; grid-template-columns: repeat({columns}, 1fr); grid-template-rows: repeat({rows}, 1fr); \
+        gap: {gap}mm; width: {width}mm; height: {height}mm; break-after: page; }}\n\
+        .page:last-child {{ break-after: auto; }}\n\
+        .card {{ display: flex; gap: 4mm; padding: 4mm; border: 1px dashed #888; overflow: hidden; }}\n\
+        .text {{ flex: 1; min-width: 0; }}\n\
+        h2 {{ margin: 0 0 2mm; font-size: 12pt; }}\n\
+        dl {{ display: grid; grid-template-columns: auto 1fr; gap: 0 2mm; margin: 0; font-size: 9pt; }}\n\
+        dt {{ font-weight: bold; }} dd {{ margin: 0; }}\n\
+        .password {{ margin: 2mm 0; font-size: 14pt; overflow-wrap: anywhere; }}\n\
+        .spelling {{ margin: 0; font-size: 8pt; }}\n\
+        svg {{ flex: none; width: 30mm; height: 30mm; }}\n\
+        </style>\n</head>\n<body>\n",
+        lang = language.code(),
+        margin = pdf::MARGIN_MM,
+        gap = pdf::GAP_MM,
+        width = width - 2.0 * pdf::MARGIN_MM,
+        height = height - 2.0 * pdf::MARGIN_MM,
+        columns = layout.columns,
+        rows = layout.rows));
+
+    for page in cards.chunks(layout.cards_per_page()) {
+        html.push_str("<section class=\"page\">\n");
+        for card in page {
+            html.push_str("<div class=\"card\"><div class=\"text\">");
+            html.push_str(&format!("<h2>{}</h2><dl>", title));
+            for (label, value) in &card.fields {
+                html.push_str(&format!("<dt>{}</dt><dd>{}</dd>", Escape::Html.apply(label), Escape::Html.apply(value)));
+            }
+            html.push_str(&format!("</dl><p class=\"password\"><code>{}</code></p><p class=\"spelling\">{}</p></div>",
+                                   Escape::Html.apply(card.password.expose()),
+                                   Escape::Html.apply(&card.spelling.join(", "))));
+            if let Some(qr) = &card.qr {
+                html.push_str(&qr.to_svg(160));
+            }
+            html.push_str("</div>\n");
+        }
+        html.push_str("</section>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use rand_chacha::ChaCha20Rng;
+    use rand_chacha::rand_core::SeedableRng;
+
+    #[derive(Parser)]
+    struct TestLayout {
+        #[command(flatten)]
+        layout: Layout
+    }
+
+    pub(super) fn parse_layout(args: &[&str]) -> Layout {
+        TestLayout::try_parse_from([&["test"], args].concat()).unwrap().layout
+    }
+
+    pub(super) fn generate(csv: &str, layout: &Layout) -> Result<Vec<Card>, CliError> {
+        let mut specifier = Specifier::try_parse("i##$").unwrap();
+        specifier.set_rng(ChaCha20Rng::seed_from_u64(4));
+        read_cards(csv.as_bytes(), &specifier, &Speller::default(), layout, None)
+    }
+
+    #[test]
+    fn reads_cards() {
+        let layout = parse_layout(&["--fields", "name, username"]);
+        let mut summary = Vec::new();
+        let mut specifier = Specifier::try_parse("i##$").unwrap();
+        specifier.set_rng(ChaCha20Rng::seed_from_u64(4));
+        let cards = read_cards("username,name,email\njdoe,Jane Doe,j@x\n".as_bytes(), &specifier,
+                               &Speller::default(), &layout, Some(&mut summary)).unwrap();
+
+        assert_eq!(cards.len(), 1);
+        assert_eq!(cards[0].fields, vec![("name".to_string(), "Jane Doe".to_string()),
+                                         ("username".to_string(), "jdoe".to_string())]);
+        assert_eq!(cards[0].spelling.len(), cards[0].password.expose().chars().count());
+        assert!(cards[0].qr.is_none());
+        assert_eq!(String::from_utf8(summary).unwrap(),
+                   format!("username,name,email,password\njdoe,Jane Doe,j@x,{}\n", cards[0].password.expose()));
+
+        let layout = parse_layout(&["--fields", "phone"]);
+        assert!(matches!(generate("username\njdoe\n", &layout), Err(CliError::UnknownColumn(c)) if c == "phone"));
+    }
+
+    #[test]
+    fn html() {
+        let layout = parse_layout(&["--columns", "1", "--rows", "1", "--qr", "--title", "Wi-Fi & VPN"]);
+        let cards = generate("name\n<Jane>\nBob\n", &layout).unwrap();
+        let html = to_html(&cards, &layout, Language::English);
+
+        assert!(html.starts_with("<!DOCTYPE html>\n<html lang=\"en\">"));
+        assert_eq!(html.matches("<section class=\"page\">").count(), 2);
+        assert_eq!(html.matches("<svg").count(), 2);
+        assert!(html.contains("<h2>Wi-Fi &amp; VPN</h2><dl><dt>name</dt><dd>&lt;Jane&gt;</dd></dl>"));
+        assert!(html.contains(&format!("<code>{}</code>", Escape::Html.apply(cards[1].password.expose()))));
+        assert!(html.contains(&cards[0].spelling[..2].join(", ")));
+        assert!(to_html(&cards, &layout, Language::German).starts_with("<!DOCTYPE html>\n<html lang=\"de\">"));
+    }
+
+    #[test]
+    fn format_from_path() {
+        assert_eq!(HandoutFormat::from_path(Some(Path::new("cards.PDF"))), HandoutFormat::Pdf);
+        assert_eq!(HandoutFormat::from_path(Some(Path::new("cards.html"))), HandoutFormat::Html);
+        assert_eq!(HandoutFormat::from_path(None), HandoutFormat::Html);
+    }
+}
@@ -0,0 +1,411 @@
+//! A minimal PDF writer for handouts.
+//!
+//! Pages use the standard Helvetica and Courier fonts, which every PDF reader
+//! provides, so nothing is embedded. Text is encoded as WinAnsi, and cards
+//! with characters it lacks are an error rather than printed wrong.
+
+use zeroize::Zeroizing;
+use crate::error::CliError;
+use crate::qr::QUIET_ZONE;
+use super::{Card, Layout};
+
+/// Page margin in millimetres.
+pub const MARGIN_MM: f64 = 12.0;
+/// Space between cards in millimetres.
+pub const GAP_MM: f64 = 6.0;
+
+const POINTS_PER_MM: f64 = 72.0 / 25.4;
+const PADDING: f64 = 11.0;
+const TITLE_SIZE: f64 = 12.0;
+const FIELD_SIZE: f64 = 9.0;
+const PASSWORD_SIZE: f64 = 14.0;
+const MIN_PASSWORD_SIZE: f64 = 7.0;
+const SPELLING_SIZE: f64 = 8.0;
+const LEADING: f64 = 1.3;
+
+/// Helvetica advance widths for ' ' to '~', in thousandths of the font size.
+const HELVETICA_WIDTHS: [u16; 95] = [278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+                                     556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+                                     1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+                                     667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+                                     333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+                                     556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584];
+
+#[derive(Clone, Copy)]
+enum Font {
+    Regular,
+    Bold,
+    Mono
+}
+
+impl Font {
+    const ALL: [Font; 3] = [Font::Regular, Font::Bold, Font::Mono];
+
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+            Font::Mono => "F3"
+        }
+    }
+
+    fn base_font(self) -> &'static str {
+        match self {
+            Font::Regular => "Helvetica",
+            Font::Bold => "Helvetica-Bold",
+            Font::Mono => "Courier-Bold"
+        }
+    }
+
+    /// Approximate width of the text in points. Bold Helvetica is measured
+    /// with the regular widths, which is close enough for headings.
+    fn width(self, text: &str, size: f64) -> f64 {
+        let units: u32 = match self {
+            Font::Mono => 600 * text.chars().count() as u32,
+            _ => text.chars()
+                     .map(|c| match c {
+                         ' '..='~' => HELVETICA_WIDTHS[c as usize - 0x20] as u32,
+                         _ => 556
+                     })
+                     .sum()
+        };
+        units as f64 * size / 1000.0
+    }
+}
+
+/// The content stream of one page.
+struct Page {
+    content: Zeroizing<Vec<u8>>
+}
+
+impl Page {
+    fn text(&mut self, x: f64, y: f64, font: Font, size: f64, text: &str) -> Result<(), CliError> {
+        let string = pdf_string(text).map_err(|c| {
+            CliError::Handout(format!("the PDF fonts can't print '{}' (U+{:04X}); use --format html", c, c as u32))
+        })?;
+        self.content.extend_from_slice(format!("BT /{} {:.1} Tf {:.2} {:.2} Td ", font.resource(), size, x, y).as_bytes());
+        self.content.extend_from_slice(&string);
+        self.content.extend_from_slice(b" Tj ET\n");
+        Ok(())
+    }
+}
+
+/// Render the cards as a PDF document. A card whose password doesn't fit,
+/// or has characters the fonts lack, is an error.
+pub fn to_pdf(cards: &[Card], layout: &Layout) -> Result<Zeroizing<Vec<u8>>, CliError> {
+    let (width, height) = layout.page.size_mm();
+    let (width, height) = (width * POINTS_PER_MM, height * POINTS_PER_MM);
+    let margin = MARGIN_MM * POINTS_PER_MM;
+    let gap = GAP_MM * POINTS_PER_MM;
+    let columns = layout.columns as usize;
+    let rows = layout.rows as usize;
+    let card_width = (width - 2.0 * margin - (columns - 1) as f64 * gap) / columns as f64;
+    let card_height = (height - 2.0 * margin - (rows - 1) as f64 * gap) / rows as f64;
+
+    let per_page = layout.cards_per_page();
+    let mut pages = Vec::new();
+    for (n, chunk) in cards.chunks(per_page).enumerate() {
+        let mut page = Page { content: Zeroizing::new(Vec::new()) };
+        for (i, card) in chunk.iter().enumerate() {
+            let x = margin + (i % columns) as f64 * (card_width + gap);
+            let top = height - margin - (i / columns) as f64 * (card_height + gap);
+            draw_card(&mut page, card, &layout.title, x, top, card_width, card_height)
+                .map_err(|e| CliError::Row { row: n * per_page + i + 1, error: Box::new(e) })?;
+        }
+        pages.push(page);
+    }
+
+    Ok(write_document(&pages, width, height))
+}
+
+fn draw_card(page: &mut Page, card: &Card, title: &str, x: f64, top: f64, width: f64,
+             height: f64) -> Result<(), CliError> {
+    page.content.extend_from_slice(format!("q 0.55 G 0.5 w [3 2] 0 d {:.2} {:.2} {:.2} {:.2} re S Q\n",
+                                           x, top - height, width, height).as_bytes());
+
+    let qr_size = match card.qr {
+        Some(_) => (height - 2.0 * PADDING).min(width * 0.4),
+        None => 0.0
+    };
+    if let Some(qr) = &card.qr {
+        let module = qr_size / (qr.width() + 2 * QUIET_ZONE) as f64;
+        let left = x + width - PADDING - qr_size;
+        let bottom = top - PADDING - qr_size;
+        for (mx, my) in qr.dark_modules() {
+            page.content.extend_from_slice(format!("{:.2} {:.2} {:.2} {:.2} re\n",
+                                                   left + (mx + QUIET_ZONE) as f64 * module,
+                                                   bottom + qr_size - (my + QUIET_ZONE + 1) as f64 * module,
+                                                   module, module).as_bytes());
+        }
+        page.content.extend_from_slice(b"f\n");
+    }
+
+    let text_width = width - 2.0 * PADDING - if qr_size > 0.0 { qr_size + PADDING } else { 0.0 };
+    let mut cursor = Cursor { left: x + PADDING, y: top - PADDING, bottom: top - height + PADDING };
+
+    //shrink long passwords to fit, and break them only if they still don't
+    let password = card.password.expose();
+    let size = (text_width / Font::Mono.width(password, 1.0)).clamp(MIN_PASSWORD_SIZE, PASSWORD_SIZE);
+    let per_line = ((text_width / Font::Mono.width("0", size)) as usize).max(1);
+    let chars: Zeroizing<Vec<char>> = Zeroizing::new(password.chars().collect());
+    let password_lines = chars.len().div_ceil(per_line).max(1);
+    let password_height = FIELD_SIZE * (LEADING - 1.0) + size + (password_lines - 1) as f64 * size * LEADING;
+
+    //the password always gets its space, the fields are cut short to make room
+    cursor.line(page, Font::Bold, TITLE_SIZE, &fit(title, Font::Bold, TITLE_SIZE, text_width))?;
+    if cursor.y - password_height < cursor.bottom {
+        return Err(CliError::Handout("the password doesn't fit on the card; use fewer --rows or --columns".to_string()));
+    }
+    let room = ((cursor.y - password_height - cursor.bottom) / (FIELD_SIZE * LEADING)) as usize;
+    let shown = if card.fields.len() <= room { card.fields.len() } else { room.saturating_sub(1) };
+    for (label, value) in &card.fields[..shown] {
+        let text = fit(&format!("{}: {}", label, value), Font::Regular, FIELD_SIZE, text_width);
+        cursor.line(page, Font::Regular, FIELD_SIZE, &text)?;
+    }
+    if shown < card.fields.len() && room > 0 {
+        cursor.line(page, Font::Regular, FIELD_SIZE, "...")?;
+    }
+
+    cursor.y -= FIELD_SIZE * (LEADING - 1.0);
+    for chunk in chars.chunks(per_line) {
+        cursor.line(page, Font::Mono, size, &Zeroizing::new(chunk.iter().collect::<String>()))?;
+    }
+
+    let lines = wrap(&card.spelling, SPELLING_SIZE, text_width);
+    for (i, text) in lines.iter().enumerate() {
+        //mark the spelling as cut short if the card is full
+        if i + 1 < lines.len() && !cursor.fits(2, SPELLING_SIZE) {
+            cursor.line(page, Font::Regular, SPELLING_SIZE, &format!("{} ...", text))?;
+            break;
+        }
+        cursor.line(page, Font::Regular, SPELLING_SIZE, text)?;
+    }
+
+    Ok(())
+}
+
+/// The position of the next line of text on a card.
+struct Cursor {
+    left: f64,
+    y: f64,
+    bottom: f64
+}
+
+impl Cursor {
+    /// Check whether this many lines of text still fit on the card.
+    fn fits(&self, lines: usize, size: f64) -> bool {
+        self.y - size - (lines - 1) as f64 * size * LEADING >= self.bottom
+    }
+
+    /// Write a line of text, or nothing if the card is full.
+    fn line(&mut self, page: &mut Page, font: Font, size: f64, text: &str) -> Result<(), CliError> {
+        if self.fits(1, size) {
+            page.text(self.left, self.y - size, font, size, text)?;
+        }
+        self.y -= size * LEADING;
+        Ok(())
+    }
+}
+
+/// Truncate text with an ellipsis so it fits the width.
+fn fit(text: &str, font: Font, size: f64, width: f64) -> String {
+    if font.width(text, size) <= width {
+        return text.to_string();
+    }
+    let mut fitted = String::new();
+    for ch in text.chars() {
+        if font.width(&format!("{}{}...", fitted, ch), size) > width {
+            break;
+        }
+        fitted.push(ch);
+    }
+    fitted + "..."
+}
+
+/// Join spoken words into comma separated lines no wider than the width.
+fn wrap(words: &[String], size: f64, width: f64) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+
+    for (i, word) in words.iter().enumerate() {
+        let word = if i + 1 < words.len() { format!("{},", word) } else { word.clone() };
+        let candidate = if current.is_empty() { word.clone() } else { format!("{} {}", current, word) };
+        if !current.is_empty() && Font::Regular.width(&candidate, size) > width {
+            lines.push(std::mem::replace(&mut current, word));
+        }
+        else {
+            current = candidate;
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+
+    lines
+}
+
+/// The characters WinAnsi puts in 0x80 to 0x9f, where Latin-1 has controls.
+const WIN_ANSI_EXTRA: [(char, u8); 27] = [('€', 0x80), ('‚', 0x82), ('ƒ', 0x83), ('„', 0x84), ('…', 0x85), ('†', 0x86),
+                                          ('‡', 0x87), ('ˆ', 0x88), ('‰', 0x89), ('Š', 0x8a), ('‹', 0x8b), ('Œ', 0x8c),
+                                          ('Ž', 0x8e), ('‘', 0x91), ('’', 0x92), ('“', 0x93), ('”', 0x94), ('•', 0x95),
+                                          ('–', 0x96), ('—', 0x97), ('˜', 0x98), ('™', 0x99), ('š', 0x9a), ('›', 0x9b),
+                                          ('œ', 0x9c), ('ž', 0x9e), ('Ÿ', 0x9f)];
+
+/// Encode text as a PDF literal string in WinAnsi encoding, or return the
+/// first character it has no code for.
+fn pdf_string(text: &str) -> Result<Zeroizing<Vec<u8>>, char> {
+    let mut bytes = Zeroizing::new(vec![b'(']);
+    for ch in text.chars() {
+        match ch {
+            '(' | ')' | '\\' => bytes.extend_from_slice(&[b'\\', ch as u8]),
+            ' '..='~' | '\u{a0}'..='\u{ff}' => bytes.push(ch as u32 as u8),
+            _ => match WIN_ANSI_EXTRA.iter().find(|(c, _)| *c == ch) {
+                Some((_, code)) => bytes.push(*code),
+                None => return Err(ch)
+            }
+        }
+    }
+    bytes.push(b')');
+
+    Ok(bytes)
+}
+
+/// Assemble the objects of the document and its cross-reference table.
+fn write_document(pages: &[Page], width: f64, height: f64) -> Zeroizing<Vec<u8>> {
+    //objects 1 and 2 are the catalog and page tree, then the fonts, then a
+    //page object and content stream for each page
+    let first_page = 3 + Font::ALL.len();
+    let kids: Vec<String> = (0..pages.len()).map(|i| format!("{} 0 R", first_page + 2 * i)).collect();
+    let fonts: Vec<String> = Font::ALL.iter()
+                                      .enumerate()
+                                      .map(|(i, f)| format!("/{} {} 0 R", f.resource(), 3 + i))
+                                      .collect();
+
+    let mut objects: Vec<Zeroizing<Vec<u8>>> = Vec::new();
+    let mut add = |bytes: Vec<u8>| objects.push(Zeroizing::new(bytes));
+    add(b"<< /Type /Catalog /Pages 2 0 R >>".to_vec());
+    add(format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages.len()).into_bytes());
+    for font in Font::ALL {
+        add(format!("<< /Type /Font /Subtype /Type1 /BaseFont /{} /Encoding /WinAnsiEncoding >>",
+                    font.base_font()).into_bytes());
+    }
+    for (i, page) in pages.iter().enumerate() {
+        add(format!("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] /Resources << /Font << {} >> >> \
+                     /Contents {} 0 R >>", width, height, fonts.join(" "), first_page + 2 * i + 1).into_bytes());
+        let mut stream = format!("<< /Length {} >>\nstream\n", page.content.len()).into_bytes();
+        stream.extend_from_slice(&page.content);
+        stream.extend_from_slice(b"\nendstream");
+        add(stream);
+    }
+
+    let mut pdf = Zeroizing::new(b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec());
+    let mut offsets = Vec::new();
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+        pdf.extend_from_slice(object);
+        pdf.extend_from_slice(b"\nendobj\n");
+    }
+    let xref = pdf.len();
+    pdf.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+    for offset in offsets {
+        pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    pdf.extend_from_slice(format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+                                  objects.len() + 1, xref).as_bytes());
+
+    pdf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mpass::Passphrase;
+    use super::super::tests::{generate, parse_layout};
+
+    /// Check that every cross-reference entry points at its object.
+    fn check_xref(pdf: &[u8]) -> usize {
+        let startxref = pdf.windows(9).rposition(|w| w == b"startxref").unwrap();
+        let tail = std::str::from_utf8(&pdf[startxref..]).unwrap();
+        let start: usize = tail.lines().nth(1).unwrap().parse().unwrap();
+        let xref = std::str::from_utf8(&pdf[start..]).unwrap();
+        assert!(xref.starts_with("xref\n0 "));
+
+        let entries: Vec<usize> = xref.lines()
+                                      .skip(3)
+                                      .take_while(|l| l.ends_with(" n "))
+                                      .map(|l| l[..10].parse().unwrap())
+                                      .collect();
+        for (i, offset) in entries.iter().enumerate() {
+            assert!(pdf[*offset..].starts_with(format!("{} 0 obj\n", i + 1).as_bytes()));
+        }
+        entries.len()
+    }
+
+    #[test]
+    fn writes_pages() {
+        let layout = parse_layout(&["--columns", "1", "--rows", "2", "--qr", "--page", "letter"]);
+        let cards = generate("name,username\nJane Doe,jdoe\nBob (IT),bob\nÉmile,emile\n", &layout).unwrap();
+        let pdf = to_pdf(&cards, &layout).unwrap();
+        let text = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.starts_with(b"%PDF-1.4\n") && pdf.ends_with(b"%%EOF\n"));
+        // catalog, pages, 3 fonts and two pages of two objects
+        assert_eq!(check_xref(&pdf), 9);
+        assert!(text.contains("/Count 2") && text.contains("/MediaBox [0 0 612.00 792.00]"));
+        assert!(text.contains("(name: Bob \\(IT\\))"));
+        assert!(pdf.windows(11).any(|w| w == b"(name: \xc9mil"));
+        assert!(text.contains(&format!("({})", cards[0].password.expose())));
+        assert!(text.contains(&cards[2].spelling[0]));
+    }
+
+    #[test]
+    fn encodes_strings() {
+        assert_eq!(&pdf_string("a(b)\\c").unwrap()[..], b"(a\\(b\\)\\\\c)");
+        assert_eq!(&pdf_string("é€—").unwrap()[..], b"(\xe9\x80\x97)");
+        assert_eq!(pdf_string("ok Жук"), Err('Ж'));
+
+        let layout = parse_layout(&[]);
+        let mut cards = generate("name\nЖанна\n", &layout).unwrap();
+        assert_eq!(to_pdf(&cards, &layout).unwrap_err().to_string(),
+                   "row 1: handout: the PDF fonts can't print 'Ж' (U+0416); use --format html");
+        cards[0].fields.clear();
+        cards[0].password = Passphrase::new("Ωmega 12!".to_string());
+        assert!(to_pdf(&cards, &layout).is_err());
+    }
+
+    #[test]
+    fn password_always_printed() {
+        //eight rows leave room for the title, two lines of fields and the password
+        let layout = parse_layout(&["--rows", "8"]);
+        let csv = "name,username,email,phone\nJane Doe,jdoe,jane@example.com,555-0100\n";
+        let cards = generate(csv, &layout).unwrap();
+        let text = String::from_utf8_lossy(&to_pdf(&cards, &layout).unwrap()).into_owned();
+        assert!(text.contains(&format!("({})", cards[0].password.expose())));
+        assert!(text.contains("(name: Jane Doe)") && text.contains("(...)"));
+        assert!(!text.contains("username") && !text.contains("phone"));
+
+        //ten only for the title and the password
+        let layout = parse_layout(&["--rows", "10"]);
+        let cards = generate(csv, &layout).unwrap();
+        let text = String::from_utf8_lossy(&to_pdf(&cards, &layout).unwrap()).into_owned();
+        assert!(text.contains(&format!("({})", cards[0].password.expose())));
+        assert!(!text.contains("Jane"));
+
+        let layout = parse_layout(&["--rows", "10", "--columns", "6"]);
+        let mut cards = generate("name\nJane\n", &layout).unwrap();
+        cards[0].password = Passphrase::new("x".repeat(80));
+        assert_eq!(to_pdf(&cards, &layout).unwrap_err().to_string(),
+                   "row 1: handout: the password doesn't fit on the card; use fewer --rows or --columns");
+    }
+
+    #[test]
+    fn wraps_and_fits() {
+        let words: Vec<String> = ["capital Alpha", "seven", "exclamation mark"].iter().map(|s| s.to_string()).collect();
+
+        assert_eq!(wrap(&words, 8.0, 1000.0), vec!["capital Alpha, seven, exclamation mark"]);
+        assert_eq!(wrap(&words, 8.0, 80.0), vec!["capital Alpha, seven,", "exclamation mark"]);
+        assert_eq!(fit("short", Font::Regular, 9.0, 100.0), "short");
+        assert!(Font::Regular.width(&fit(&"W".repeat(40), Font::Regular, 9.0, 100.0), 9.0) <= 100.0);
+    }
+}
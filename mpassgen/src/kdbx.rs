@@ -0,0 +1,400 @@
+//! KeePass KDBX 4 databases.
+//!
+//! Generated records are written straight into an encrypted database, so
+//! their passwords never reach the disk in plaintext. The key is derived
+//! from the master passphrase with Argon2id and the database encrypted with
+//! ChaCha20 or AES-256-CBC; passwords inside it are further protected with
+//! the ChaCha20 inner stream, as KeePass does. [`read`] opens a database
+//! again to check what was written.
+
+mod read;
+
+pub use read::read;
+
+use aes::Aes256;
+use argon2::Argon2;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use cbc::cipher::block_padding::Pkcs7;
+use cbc::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit, StreamCipher};
+use chacha20::ChaCha20;
+use clap::{Args, ValueEnum};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::{Digest, Sha256, Sha512};
+use zeroize::Zeroizing;
+use crate::error::CliError;
+use crate::export::{self, Record};
+
+/// The two KeePass signatures.
+const SIGNATURE: [u8; 8] = [0x03, 0xd9, 0xa2, 0x9a, 0x67, 0xfb, 0x4b, 0xb5];
+/// Format version 4.0, minor version first.
+const VERSION: [u8; 4] = [0x00, 0x00, 0x04, 0x00];
+const MAJOR_VERSION: u16 = 4;
+
+const CHACHA20_UUID: [u8; 16] = [0xd6, 0x03, 0x8a, 0x2b, 0x8b, 0x6f, 0x4c, 0xb5,
+                                 0xa5, 0x24, 0x33, 0x9a, 0x31, 0xdb, 0xb5, 0x9a];
+const AES256_UUID: [u8; 16] = [0x31, 0xc1, 0xf2, 0xe6, 0xbf, 0x71, 0x43, 0x50,
+                               0xbe, 0x58, 0x05, 0x21, 0x6a, 0xfc, 0x5a, 0xff];
+const ARGON2D_UUID: [u8; 16] = [0xef, 0x63, 0x6d, 0xdf, 0x8c, 0x29, 0x44, 0x4b,
+                                0x91, 0xf7, 0xa9, 0xa4, 0x03, 0xe3, 0x0a, 0x0c];
+const ARGON2ID_UUID: [u8; 16] = [0x9e, 0x29, 0x8b, 0x19, 0x56, 0xdb, 0x47, 0x73,
+                                 0xb2, 0x3d, 0xfc, 0x3e, 0xc6, 0xf0, 0xa1, 0xe6];
+
+//outer header field ids
+const END_OF_HEADER: u8 = 0;
+const CIPHER_ID: u8 = 2;
+const COMPRESSION: u8 = 3;
+const MASTER_SEED: u8 = 4;
+const ENCRYPTION_IV: u8 = 7;
+const KDF_PARAMETERS: u8 = 11;
+
+//inner header field ids
+const INNER_END: u8 = 0;
+const INNER_STREAM_ID: u8 = 1;
+const INNER_STREAM_KEY: u8 = 2;
+/// Inner random stream id of ChaCha20.
+const INNER_STREAM_CHACHA20: u32 = 3;
+
+//variant dictionary value types
+const DICTIONARY_VERSION: [u8; 2] = [0x00, 0x01];
+const UINT32: u8 = 0x04;
+const UINT64: u8 = 0x05;
+const BYTES: u8 = 0x42;
+
+/// Largest HMAC block of the encrypted payload.
+const BLOCK_SIZE: usize = 1 << 20;
+
+/// Cipher for the database contents.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum VaultCipher {
+    #[default]
+    Chacha20,
+    /// AES-256 in CBC mode
+    Aes256
+}
+
+impl VaultCipher {
+    fn uuid(self) -> [u8; 16] {
+        match self {
+            VaultCipher::Chacha20 => CHACHA20_UUID,
+            VaultCipher::Aes256 => AES256_UUID
+        }
+    }
+
+    fn from_uuid(uuid: &[u8]) -> Option<Self> {
+        [VaultCipher::Chacha20, VaultCipher::Aes256].into_iter().find(|c| c.uuid() == uuid)
+    }
+
+    fn iv_len(self) -> usize {
+        match self {
+            VaultCipher::Chacha20 => 12,
+            VaultCipher::Aes256 => 16
+        }
+    }
+
+    fn encrypt(self, key: &[u8; 32], iv: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut buffer = data.to_vec();
+        match self {
+            VaultCipher::Chacha20 => ChaCha20::new(key.into(), iv.into()).apply_keystream(&mut buffer),
+            VaultCipher::Aes256 => {
+                buffer.resize(data.len() + 16, 0);
+                let len = cbc::Encryptor::<Aes256>::new(key.into(), iv.into())
+                    .encrypt_padded_mut::<Pkcs7>(&mut buffer, data.len())
+                    .expect("buffer has room for a block of padding")
+                    .len();
+                buffer.truncate(len);
+            }
+        }
+        buffer
+    }
+
+    /// Decrypt, returning None if the padding is wrong.
+    fn decrypt(self, key: &[u8; 32], iv: &[u8], data: &[u8]) -> Option<Zeroizing<Vec<u8>>> {
+        let mut buffer = Zeroizing::new(data.to_vec());
+        match self {
+            VaultCipher::Chacha20 => ChaCha20::new(key.into(), iv.into()).apply_keystream(&mut buffer),
+            VaultCipher::Aes256 => {
+                let len = cbc::Decryptor::<Aes256>::new(key.into(), iv.into())
+                    .decrypt_padded_mut::<Pkcs7>(&mut buffer)
+                    .ok()?
+                    .len();
+                buffer.truncate(len);
+            }
+        }
+        Some(buffer)
+    }
+}
+
+/// Argon2id cost of deriving the key from the master passphrase.
+#[derive(Args, Clone, Copy, Debug, Eq, PartialEq)]
+pub struct KdfParams {
+    /// Argon2 memory in MiB
+    #[arg(long = "kdf-memory", value_name = "MIB", default_value_t = 64,
+          value_parser = clap::value_parser!(u32).range(1..=4096))]
+    pub memory_mib: u32,
+
+    /// Argon2 iterations
+    #[arg(long = "kdf-iterations", value_name = "N", default_value_t = 10,
+          value_parser = clap::value_parser!(u32).range(1..))]
+    pub iterations: u32,
+
+    /// Argon2 lanes
+    #[arg(long = "kdf-parallelism", value_name = "N", default_value_t = 2,
+          value_parser = clap::value_parser!(u32).range(1..=16))]
+    pub parallelism: u32,
+}
+
+/// Argon2 settings as stored in the KDF parameters of the header.
+struct Argon2Kdf {
+    algorithm: argon2::Algorithm,
+    salt: Vec<u8>,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32
+}
+
+impl Argon2Kdf {
+    fn to_dictionary(&self) -> Vec<u8> {
+        let uuid = match self.algorithm {
+            argon2::Algorithm::Argon2d => ARGON2D_UUID,
+            _ => ARGON2ID_UUID
+        };
+        let memory = self.memory_kib as u64 * 1024;
+        let items: [(&str, u8, &[u8]); 6] = [("$UUID", BYTES, &uuid), ("S", BYTES, &self.salt),
+                                             ("P", UINT32, &self.parallelism.to_le_bytes()),
+                                             ("M", UINT64, &memory.to_le_bytes()),
+                                             ("I", UINT64, &(self.iterations as u64).to_le_bytes()),
+                                             ("V", UINT32, &0x13u32.to_le_bytes())];
+
+        let mut dictionary = DICTIONARY_VERSION.to_vec();
+        for (name, kind, value) in items {
+            dictionary.push(kind);
+            dictionary.extend((name.len() as u32).to_le_bytes());
+            dictionary.extend(name.as_bytes());
+            dictionary.extend((value.len() as u32).to_le_bytes());
+            dictionary.extend(value);
+        }
+        dictionary.push(0);
+        dictionary
+    }
+
+    /// Derive the 32 byte transformed key from the composite key.
+    fn transform(&self, composite: &[u8]) -> Result<Zeroizing<[u8; 32]>, String> {
+        let params = argon2::Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| format!("Argon2: {}", e))?;
+        let mut key = Zeroizing::new([0u8; 32]);
+        Argon2::new(self.algorithm, argon2::Version::V0x13, params)
+            .hash_password_into(composite, &self.salt, &mut *key)
+            .map_err(|e| format!("Argon2: {}", e))?;
+        Ok(key)
+    }
+}
+
+/// The keys derived from the master passphrase and the master seed.
+struct Keys {
+    cipher: Zeroizing<[u8; 32]>,
+    hmac: Zeroizing<[u8; 64]>
+}
+
+impl Keys {
+    fn derive(master: &str, seed: &[u8], kdf: &Argon2Kdf) -> Result<Keys, String> {
+        //the composite key of a passphrase alone hashes its hash
+        let composite = Zeroizing::new(<[u8; 32]>::from(Sha256::digest(Sha256::digest(master.as_bytes()))));
+        let transformed = kdf.transform(&*composite)?;
+
+        let cipher = Sha256::new().chain_update(seed).chain_update(*transformed).finalize();
+        let hmac = Sha512::new().chain_update(seed).chain_update(*transformed).chain_update([1]).finalize();
+        Ok(Keys { cipher: Zeroizing::new(cipher.into()), hmac: Zeroizing::new(hmac.into()) })
+    }
+
+    /// An HMAC keyed for a block, or for the header with index `u64::MAX`.
+    fn mac(&self, index: u64) -> Hmac<Sha256> {
+        let key = Sha512::new().chain_update(index.to_le_bytes()).chain_update(*self.hmac).finalize();
+        Hmac::new_from_slice(&*Zeroizing::new(<[u8; 64]>::from(key))).expect("HMAC takes keys of any length")
+    }
+
+    fn header_mac(&self, header: &[u8]) -> Hmac<Sha256> {
+        self.mac(u64::MAX).chain_update(header)
+    }
+
+    fn block_mac(&self, index: u64, block: &[u8]) -> Hmac<Sha256> {
+        self.mac(index)
+            .chain_update(index.to_le_bytes())
+            .chain_update((block.len() as u32).to_le_bytes())
+            .chain_update(block)
+    }
+}
+
+/// The ChaCha20 stream protected values inside the XML are XORed with, in
+/// document order.
+struct InnerStream(ChaCha20);
+
+impl InnerStream {
+    fn new(key: &[u8]) -> Self {
+        let hash = Zeroizing::new(<[u8; 64]>::from(Sha512::digest(key)));
+        InnerStream(ChaCha20::new(hash[..32].into(), hash[32..44].into()))
+    }
+
+    fn apply(&mut self, data: &mut [u8]) {
+        self.0.apply_keystream(data);
+    }
+}
+
+fn random_bytes(rng: &mut impl RngCore, len: usize) -> Vec<u8> {
+    let mut bytes = vec![0u8; len];
+    rng.fill_bytes(&mut bytes);
+    bytes
+}
+
+/// Append a header field with a 32 bit length, as both headers use.
+fn push_field(out: &mut Vec<u8>, id: u8, data: &[u8]) {
+    out.push(id);
+    out.extend((data.len() as u32).to_le_bytes());
+    out.extend(data);
+}
+
+/// Write records into a database locked with the master passphrase.
+pub fn write(records: &[Record], master: &str, cipher: VaultCipher, kdf: &KdfParams,
+             rng: &mut impl RngCore) -> Result<Vec<u8>, CliError> {
+    let seed = random_bytes(rng, 32);
+    let iv = random_bytes(rng, cipher.iv_len());
+    let inner_key = Zeroizing::new(random_bytes(rng, 64));
+    let argon2 = Argon2Kdf { algorithm: argon2::Algorithm::Argon2id, salt: random_bytes(rng, 32),
+                             memory_kib: kdf.memory_mib * 1024, iterations: kdf.iterations,
+                             parallelism: kdf.parallelism };
+
+    let mut header = [SIGNATURE.as_slice(), &VERSION].concat();
+    push_field(&mut header, CIPHER_ID, &cipher.uuid());
+    push_field(&mut header, COMPRESSION, &0u32.to_le_bytes());
+    push_field(&mut header, MASTER_SEED, &seed);
+    push_field(&mut header, ENCRYPTION_IV, &iv);
+    push_field(&mut header, KDF_PARAMETERS, &argon2.to_dictionary());
+    push_field(&mut header, END_OF_HEADER, b"\r\n\r\n");
+
+    let mut inner = Zeroizing::new(Vec::new());
+    push_field(&mut inner, INNER_STREAM_ID, &INNER_STREAM_CHACHA20.to_le_bytes());
+    push_field(&mut inner, INNER_STREAM_KEY, &inner_key);
+    push_field(&mut inner, INNER_END, &[]);
+    let mut stream = InnerStream::new(&inner_key);
+    let xml = export::keepass_xml(records, rng, |password| {
+        let mut value = Zeroizing::new(password.as_bytes().to_vec());
+        stream.apply(&mut value);
+        (" Protected=\"True\"", Zeroizing::new(STANDARD.encode(&*value)))
+    });
+    inner.extend(xml.as_bytes());
+
+    let keys = Keys::derive(master, &seed, &argon2).map_err(CliError::Vault)?;
+    let payload = cipher.encrypt(&keys.cipher, &iv, &inner);
+
+    let mut database = header.clone();
+    database.extend(Sha256::digest(&header));
+    database.extend(keys.header_mac(&header).finalize().into_bytes());
+    for (index, block) in payload.chunks(BLOCK_SIZE).chain([&[][..]]).enumerate() {
+        database.extend(keys.block_mac(index as u64, block).finalize().into_bytes());
+        database.extend((block.len() as u32).to_le_bytes());
+        database.extend(block);
+    }
+
+    Ok(database)
+}
+
+/// Open a database written by [`write`] and check it holds the records.
+pub fn verify(database: &[u8], master: &str, records: &[Record]) -> Result<(), CliError> {
+    let read = read(database, master)?;
+    let fields = |r: &Record| [r.title.clone(), r.username.clone(), r.url.clone(), r.notes.clone(),
+                               r.password.expose().to_string()].map(Zeroizing::new);
+    if read.len() != records.len() || read.iter().zip(records).any(|(a, b)| fields(a) != fields(b)) {
+        return Err(CliError::Vault("the database read back does not match the generated entries".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mpass::Passphrase;
+    use rand_chacha::ChaCha20Rng;
+    use rand_chacha::rand_core::SeedableRng;
+
+    /// The cheapest key derivation, to keep the tests fast.
+    pub(super) const KDF: KdfParams = KdfParams { memory_mib: 1, iterations: 1, parallelism: 1 };
+
+    pub(super) fn records() -> Vec<Record> {
+        [("Mail & Calendar", "alice", "https://mail.example.com", "", "Medium \"test\" 12!"),
+         ("Router", "admin", "", "first line\nsecond <line>", "a<b>'c,d\\&e")]
+            .into_iter()
+            .map(|(title, username, url, notes, password)| Record {
+                title: title.to_string(),
+                username: username.to_string(),
+                url: url.to_string(),
+                notes: notes.to_string(),
+                password: Passphrase::new(password.to_string())
+            })
+            .collect()
+    }
+
+    pub(super) fn database(cipher: VaultCipher) -> Vec<u8> {
+        write(&records(), "Master phrase 99#", cipher, &KDF, &mut ChaCha20Rng::seed_from_u64(4)).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        for cipher in [VaultCipher::Chacha20, VaultCipher::Aes256] {
+            let database = database(cipher);
+            assert!(database.starts_with(&[0x03, 0xd9, 0xa2, 0x9a, 0x67, 0xfb, 0x4b, 0xb5, 0, 0, 4, 0]));
+            // passwords are protected inside the encrypted XML too
+            assert!(!database.windows(6).any(|w| w == b"Medium"));
+
+            verify(&database, "Master phrase 99#", &records()).unwrap();
+            let read = read(&database, "Master phrase 99#").unwrap();
+            assert_eq!(read[1].notes, "first line\nsecond <line>");
+            assert_eq!(read[1].password.expose(), "a<b>'c,d\\&e");
+        }
+    }
+
+    #[test]
+    fn rejects_bad_databases() {
+        let database = database(VaultCipher::Aes256);
+        let error = |data: &[u8], master: &str| match read(data, master) {
+            Err(CliError::Vault(message)) => message,
+            _ => panic!("expected an error")
+        };
+
+        assert_eq!(error(&database, "master phrase 99#"), "wrong master passphrase, or the header is corrupted");
+        let mut corrupted = database.clone();
+        // the last byte of block 0, before the empty final block
+        let last = corrupted.len() - 37;
+        corrupted[last] ^= 1;
+        assert_eq!(error(&corrupted, "Master phrase 99#"), "block 0 is corrupted");
+        assert_eq!(error(&database[..database.len() - 40], "Master phrase 99#"), "the file is truncated");
+        assert_eq!(error(b"PK\x03\x04 not a database", "x"), "not a KeePass database");
+
+        let mut other = records();
+        other[0].password = Passphrase::new("changed".to_string());
+        assert!(matches!(verify(&database, "Master phrase 99#", &other), Err(CliError::Vault(_))));
+    }
+
+    #[test]
+    fn opens_with_keepass_crate() {
+        //an independent reader, so a mistake shared by write and read can't pass
+        for cipher in [VaultCipher::Chacha20, VaultCipher::Aes256] {
+            let database = database(cipher);
+            let key = keepass::DatabaseKey::new().with_password("Master phrase 99#");
+            let opened = keepass::Database::open(&mut database.as_slice(), key).unwrap();
+
+            let mut entries: Vec<[String; 5]> = opened.iter_all_entries().map(|e| {
+                let field = |key: &str| e.get(key).unwrap_or_default().to_string();
+                [field("Title"), field("UserName"), field("URL"), field("Notes"), field("Password")]
+            }).collect();
+            entries.sort();
+            let expected: Vec<[String; 5]> = records().iter().map(|r| {
+                [&r.title, &r.username, &r.url, &r.notes, r.password.expose()].map(|s| s.to_string())
+            }).collect();
+            assert_eq!(entries, expected, "{:?}", cipher);
+
+            let wrong = keepass::DatabaseKey::new().with_password("master phrase 99#");
+            assert!(keepass::Database::open(&mut database.as_slice(), wrong).is_err());
+        }
+    }
+}
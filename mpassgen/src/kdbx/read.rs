@@ -0,0 +1,202 @@
+//! Reading KDBX 4 databases back.
+//!
+//! Only what [`write`](super::write) produces is supported: uncompressed
+//! databases with an Argon2 key and a passphrase alone as the master key.
+
+use std::collections::HashMap;
+use mpass::Passphrase;
+use super::*;
+
+/// A cursor over the bytes of a database.
+struct Input<'a>(&'a [u8]);
+
+impl<'a> Input<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.0.len() < len {
+            return Err("the file is truncated".to_string());
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().expect("took 4 bytes")))
+    }
+
+    /// Read header fields up to and including the end field.
+    fn fields(&mut self, end: u8) -> Result<HashMap<u8, &'a [u8]>, String> {
+        let mut fields = HashMap::new();
+        loop {
+            let id = self.u8()?;
+            let len = self.u32()? as usize;
+            fields.insert(id, self.take(len)?);
+            if id == end {
+                return Ok(fields);
+            }
+        }
+    }
+}
+
+/// Open a database with its master passphrase and return its entries.
+pub fn read(database: &[u8], master: &str) -> Result<Vec<Record>, CliError> {
+    read_records(database, master).map_err(CliError::Vault)
+}
+
+fn read_records(database: &[u8], master: &str) -> Result<Vec<Record>, String> {
+    let mut input = Input(database);
+    if input.take(8)? != SIGNATURE {
+        return Err("not a KeePass database".to_string());
+    }
+    let version = input.take(4)?;
+    if u16::from_le_bytes([version[2], version[3]]) != MAJOR_VERSION {
+        return Err("not a KDBX 4 database".to_string());
+    }
+
+    let fields = input.fields(END_OF_HEADER)?;
+    let header = &database[..database.len() - input.0.len()];
+    let field = |id: u8, name: &str| fields.get(&id).copied().ok_or_else(|| format!("the header has no {}", name));
+    let cipher = VaultCipher::from_uuid(field(CIPHER_ID, "cipher")?).ok_or("unsupported cipher")?;
+    if field(COMPRESSION, "compression flag")? != 0u32.to_le_bytes() {
+        return Err("compressed databases are not supported".to_string());
+    }
+    let seed = field(MASTER_SEED, "master seed")?;
+    let iv = field(ENCRYPTION_IV, "encryption IV")?;
+    if iv.len() != cipher.iv_len() {
+        return Err("the encryption IV has the wrong length".to_string());
+    }
+    let kdf = parse_kdf(field(KDF_PARAMETERS, "KDF parameters")?)?;
+
+    if input.take(32)? != Sha256::digest(header).as_slice() {
+        return Err("the header is corrupted".to_string());
+    }
+    let keys = Keys::derive(master, seed, &kdf)?;
+    if keys.header_mac(header).verify_slice(input.take(32)?).is_err() {
+        return Err("wrong master passphrase, or the header is corrupted".to_string());
+    }
+
+    let mut payload = Vec::new();
+    for index in 0.. {
+        let mac = input.take(32)?;
+        let len = input.u32()? as usize;
+        let block = input.take(len)?;
+        if keys.block_mac(index, block).verify_slice(mac).is_err() {
+            return Err(format!("block {} is corrupted", index));
+        }
+        if block.is_empty() {
+            break;
+        }
+        payload.extend(block);
+    }
+
+    let inner = cipher.decrypt(&keys.cipher, iv, &payload).ok_or("the contents could not be decrypted")?;
+    let mut input = Input(&inner);
+    let fields = input.fields(INNER_END)?;
+    if fields.get(&INNER_STREAM_ID).copied() != Some(&INNER_STREAM_CHACHA20.to_le_bytes()[..]) {
+        return Err("unsupported inner stream".to_string());
+    }
+    let stream = InnerStream::new(fields.get(&INNER_STREAM_KEY).ok_or("the inner header has no stream key")?);
+    let xml = std::str::from_utf8(input.0).map_err(|_| "the XML is not UTF-8".to_string())?;
+
+    parse_xml(xml, stream)
+}
+
+fn parse_kdf(dictionary: &[u8]) -> Result<Argon2Kdf, String> {
+    let mut input = Input(dictionary);
+    if input.take(2)?[1] != DICTIONARY_VERSION[1] {
+        return Err("unsupported KDF parameters version".to_string());
+    }
+    let mut items = HashMap::new();
+    loop {
+        let kind = input.u8()?;
+        if kind == 0 {
+            break;
+        }
+        let name_len = input.u32()? as usize;
+        let name = input.take(name_len)?;
+        let value_len = input.u32()? as usize;
+        items.insert(name, input.take(value_len)?);
+    }
+
+    let item = |name: &str| items.get(name.as_bytes()).copied().ok_or_else(|| format!("the KDF has no {} parameter", name));
+    let number = |name: &str| -> Result<u64, String> {
+        match item(name)? {
+            &[a, b, c, d] => Ok(u32::from_le_bytes([a, b, c, d]) as u64),
+            bytes => Ok(u64::from_le_bytes(bytes.try_into().map_err(|_| format!("bad KDF parameter {}", name))?))
+        }
+    };
+    let algorithm = match item("$UUID")? {
+        uuid if uuid == ARGON2D_UUID => argon2::Algorithm::Argon2d,
+        uuid if uuid == ARGON2ID_UUID => argon2::Algorithm::Argon2id,
+        _ => return Err("only Argon2 keys are supported".to_string())
+    };
+    let small = |name: &str, value: u64| u32::try_from(value).map_err(|_| format!("KDF parameter {} is too large", name));
+
+    Ok(Argon2Kdf {
+        algorithm,
+        salt: item("S")?.to_vec(),
+        memory_kib: small("M", number("M")? / 1024)?,
+        iterations: small("I", number("I")?)?,
+        parallelism: small("P", number("P")?)?
+    })
+}
+
+/// Read the entries of the root group, unprotecting values in document order.
+fn parse_xml(xml: &str, mut stream: InnerStream) -> Result<Vec<Record>, String> {
+    let document = roxmltree::Document::parse(xml).map_err(|e| format!("XML: {}", e))?;
+    let mut protected = HashMap::new();
+    for node in document.descendants().filter(|n| n.has_tag_name("Value") && n.attribute("Protected") == Some("True")) {
+        let mut value = Zeroizing::new(STANDARD.decode(node.text().unwrap_or_default())
+                                               .map_err(|_| "a protected value is not base64".to_string())?);
+        stream.apply(&mut value);
+        let text = String::from_utf8(value.to_vec()).map_err(|_| "a protected value is not UTF-8".to_string())?;
+        protected.insert(node.id(), Zeroizing::new(text));
+    }
+
+    let entries = document.descendants()
+                          .filter(|n| n.has_tag_name("Entry") && n.parent().is_some_and(|p| p.has_tag_name("Group")));
+    Ok(entries.map(|entry| {
+        let value = |key: &str| -> String {
+            entry.children()
+                 .filter(|n| n.has_tag_name("String"))
+                 .find(|n| n.children().any(|k| k.has_tag_name("Key") && k.text() == Some(key)))
+                 .and_then(|n| n.children().find(|v| v.has_tag_name("Value")))
+                 .map(|v| match protected.get(&v.id()) {
+                     Some(text) => text.to_string(),
+                     None => v.text().unwrap_or_default().to_string()
+                 })
+                 .unwrap_or_default()
+        };
+        Record {
+            title: value("Title"),
+            username: value("UserName"),
+            url: value("URL"),
+            notes: value("Notes"),
+            password: Passphrase::new(value("Password"))
+        }
+    }).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kdbx::tests::database;
+
+    #[test]
+    fn header() {
+        let database = database(VaultCipher::Chacha20);
+        let mut input = Input(&database[12..]);
+        let fields = input.fields(END_OF_HEADER).unwrap();
+
+        assert_eq!(fields[&CIPHER_ID], CHACHA20_UUID);
+        assert_eq!(fields[&ENCRYPTION_IV].len(), 12);
+        assert_eq!(fields[&END_OF_HEADER], b"\r\n\r\n");
+        let kdf = parse_kdf(fields[&KDF_PARAMETERS]).unwrap();
+        assert_eq!((kdf.memory_kib, kdf.iterations, kdf.parallelism, kdf.salt.len()), (1024, 1, 1, 32));
+        assert_eq!(kdf.algorithm, argon2::Algorithm::Argon2id);
+    }
+}
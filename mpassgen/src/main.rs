@@ -1,30 +1,615 @@
-use regex::Regex;
+mod accounts;
+mod cli;
+mod encrypt;
+mod error;
+mod escape;
+mod export;
+mod handout;
+mod kdbx;
+mod merge;
+mod output;
+mod qr;
+mod secrets;
+mod template;
 
-use mpass::Specifier;
+use std::borrow::Cow;
+use std::fs;
+use std::io::{IsTerminal, Read, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use rand_chacha::ChaCha20Rng;
+use rand_chacha::rand_core::{RngCore, SeedableRng};
+use zeroize::Zeroizing;
 
-fn main() {
-    println!("Hello, world!");
+use mpass::{RandomTokenGenerator, Specifier, Speller};
+use mpass::apikey::{self, ApiKeyGenerator};
+use mpass::derive::Derivation;
+use mpass::filter::FilterRegistry;
+use mpass::hash::HashAlgorithm;
+use mpass::rtg::default_lists::{get_ez_ascii_symbols, get_simpleton_words};
+use mpass::totp::Totp;
+use crate::accounts::{Account, NameRules};
+use crate::cli::{AccountArgs, ApikeyArgs, CheckApikeyArgs, ChpasswdArgs, Cli, Command, DecryptArgs, DeriveArgs, EncryptArgs, ExportArgs, GenerateArgs, HandoutArgs,
+                 HtpasswdArgs, ListArgs, MergeArgs, QrArgs, RenderArgs, SecretsArgs, TotpArgs, TotpCodeArgs, VaultArgs,
+                 DEFAULT_SPEC};
+use crate::encrypt::Encryption;
+use crate::error::CliError;
+use crate::handout::HandoutFormat;
+use crate::merge::Merge;
+use crate::output::{Item, ItemWriter, OutputFormat};
+use crate::qr::{Qr, QrFormat};
+use crate::secrets::SecretFormat;
+use crate::template::Template;
 
-    let hay = "<html>\
-    <head><title>Test</title>\
-    </head>\
-    <body>\
-    <<flarg:wW#$>>
-    <<frobz:#w W #$>>
-    <<ppwd:i w w w ####>>
-    <<shuffle:?i w w w ####>>
-    </body></html>";
+fn main() -> ExitCode {
+    let cli = Cli::parse_args();
 
-    let re = Regex::new(r"<<(?<name>[a-zA-Z]+):(?<spec>[a-zA-Z#$? ]+)>>").unwrap();
-    let caps = re.captures_iter(hay);
+    let result = build_output(&cli.encrypt).and_then(|out| match &cli.command {
+        Command::Generate(args) => generate(args, &cli.lists, &out),
+        Command::Render(args) => render(args, &cli.lists, &out),
+        Command::Merge(args) => merge(args, &cli.lists, &out),
+        Command::Handout(args) => handout(args, &cli.lists, &out),
+        Command::Qr(args) => qr(args, &cli.lists, &out),
+        Command::Htpasswd(args) => htpasswd(args, &cli.lists, &out),
+        Command::Chpasswd(args) => chpasswd(args, &cli.lists, &out),
+        Command::Export(args) => export(args, &cli.lists, &out),
+        Command::Vault(args) => vault(args, &cli.lists, &out),
+        Command::Secrets(args) => secrets(args, &cli.lists, &out),
+        Command::Apikey(args) => apikey(args, &cli.lists, &out),
+        Command::CheckApikey(args) => check_apikey(args, &out),
+        Command::Totp(args) => totp(args, &cli.lists, &out),
+        Command::TotpCode(args) => totp_code(args, &out),
+        Command::Derive(args) => derive(args, &cli.lists, &out),
+        Command::Decrypt(args) => decrypt(args)
+    });
 
-    for cap in caps {
-        let name = cap.name("name").unwrap().as_str();
-        let spec = cap.name("spec").unwrap().as_str();
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("mpassgen: {}", e);
+            ExitCode::from(e.exit_code() as u8)
+        }
+    }
+}
+
+fn generate(args: &GenerateArgs, lists: &ListArgs, out: &Output) -> Result<(), CliError> {
+    let specifier = build_specifier(&args.spec, lists)?;
+    let stdout = |e| CliError::io("<stdout>", e);
+    //encrypted output has to be whole before it can be written
+    let mut buffer = Zeroizing::new(Vec::new());
+    let mut lock = std::io::stdout().lock();
+    let output: &mut dyn Write = match out.encryption {
+        Some(_) => &mut *buffer,
+        None => &mut lock
+    };
+
+    if args.entropy && args.format == OutputFormat::Plain {
+        writeln!(output, "Entropy: {:.1} bits", specifier.entropy()).map_err(stdout)?;
+    }
+
+    let speller = Speller::new(lists.language);
+    let mut writer = ItemWriter::new(args.format, &args.spec, specifier.entropy(), output);
+    for _ in 0..args.count {
+        let breakdown = specifier.get_breakdown()?;
+        let spelling = args.spell.then(|| speller.speak(breakdown.passphrase.expose()));
+        let hash = match args.hash {
+            Some(algorithm) => {
+                let cost = args.hash_cost.unwrap_or(algorithm.default_cost());
+                Some(algorithm.hash_with_cost(breakdown.passphrase.expose(), cost)?)
+            }
+            None => None
+        };
+        writer.write(&Item { breakdown, spelling, hash, generated_at: SystemTime::now() }).map_err(stdout)?;
+    }
+    writer.finish().map_err(stdout)?;
+
+    match out.encryption {
+        Some(_) => out.write(None, &buffer),
+        None => Ok(())
+    }
+}
+
+fn render(args: &RenderArgs, lists: &ListArgs, out: &Output) -> Result<(), CliError> {
+    let source = read_input(&args.template)?;
+    let template = Template::parse(&source)?;
+    let mut specifier = build_specifier(DEFAULT_SPEC, lists)?;
+    let escape = args.escape.resolve(&[args.output.as_deref(), Some(args.template.as_path())]);
+    let output = template.render(&mut specifier, &build_filters(lists), escape)?;
+
+    out.write_secret(args.output.as_deref(), output.as_bytes())
+}
+
+fn merge(args: &MergeArgs, lists: &ListArgs, out: &Output) -> Result<(), CliError> {
+    let template = Template::parse(&read_input(&args.template)?)?;
+    let pattern = Template::parse(&args.output)?;
+    let csv_input = read_input(&args.csv)?;
+    let mut specifier = build_specifier(DEFAULT_SPEC, lists)?;
+    let escape = args.escape.resolve(&[Some(Path::new(&args.output)), Some(args.template.as_path())]);
+    let filters = build_filters(lists);
+    let merge = Merge { template: &template, pattern: &pattern, filters: &filters, escape };
+    let mut summary = Zeroizing::new(Vec::new());
+
+    //every letter and the summary hold passwords
+    merge.run(csv_input.as_bytes(), &mut specifier, &mut *summary, |path, contents| {
+        merge::create_parent_dirs(path)?;
+        out.write_private(path, contents)
+    })?;
+
+    out.write_secret(args.summary.as_deref(), &summary)
+}
+
+fn handout(args: &HandoutArgs, lists: &ListArgs, out: &Output) -> Result<(), CliError> {
+    let csv_input = read_input(&args.csv)?;
+    let specifier = build_specifier(&args.spec, lists)?;
+    let mut summary = Zeroizing::new(Vec::new());
+    let speller = Speller::new(lists.language);
+    let cards = handout::read_cards(csv_input.as_bytes(), &specifier, &speller, &args.layout,
+                                    args.summary.as_ref().map(|_| &mut *summary as &mut dyn Write))?;
+
+    let document = match args.format.unwrap_or_else(|| HandoutFormat::from_path(args.output.as_deref())) {
+        HandoutFormat::Html => Zeroizing::new(handout::to_html(&cards, &args.layout, lists.language).as_bytes().to_vec()),
+        HandoutFormat::Pdf => handout::to_pdf(&cards, &args.layout)?
+    };
+    out.write_secret(args.output.as_deref(), &document)?;
+
+    match &args.summary {
+        Some(path) => out.write_private(path, &summary),
+        None => Ok(())
+    }
+}
+
+fn qr(args: &QrArgs, lists: &ListArgs, out: &Output) -> Result<(), CliError> {
+    let specifier = build_specifier(&args.spec, lists)?;
+    let passphrase = specifier.get_passphrase()?;
+    let payload = match &args.wifi {
+        Some(ssid) => qr::wifi_payload(ssid, args.security, passphrase.expose(), args.hidden),
+        None => Zeroizing::new(passphrase.expose().to_string())
+    };
+    let code = Qr::encode(payload.as_bytes())?;
+    let line = Zeroizing::new(format!("{}\n", passphrase.expose()));
+
+    let format = args.format.unwrap_or_else(|| QrFormat::from_path(args.output.as_deref()));
+    write_qr(&code, format, args.scale, args.output.as_deref(), &line, out)
+}
+
+/// Write a QR code image, with the text it stands for after a terminal
+/// code or on stdout when the image goes to a file.
+fn write_qr(code: &Qr, format: QrFormat, scale: u32, output: Option<&Path>, text: &str, out: &Output) -> Result<(), CliError> {
+    let image = match format {
+        QrFormat::Terminal => Zeroizing::new(code.to_terminal().as_bytes().to_vec()),
+        QrFormat::Svg => {
+            let size = (code.width() + 2 * qr::QUIET_ZONE) * scale as usize;
+            Zeroizing::new(code.to_svg(size).as_bytes().to_vec())
+        }
+        QrFormat::Png => code.to_png(scale)
+    };
+    match output {
+        //the image holds the secret as much as the text does
+        Some(path) => {
+            out.write_private(path, &image)?;
+            out.write(None, text.as_bytes())
+        }
+        //show the text after a terminal image, but not inside an SVG or PNG
+        None if format == QrFormat::Terminal => {
+            let mut image = image;
+            image.extend_from_slice(text.as_bytes());
+            out.write(None, &image)
+        }
+        None => out.write(None, &image)
+    }
+}
+
+fn htpasswd(args: &HtpasswdArgs, lists: &ListArgs, out: &Output) -> Result<(), CliError> {
+    let names = accounts::read_usernames(&read_input(&args.accounts.users)?, NameRules::Htpasswd)?;
+    let specifier = build_specifier(&args.accounts.spec, lists)?;
+    let accounts = accounts::create(names, &specifier, |p| args.scheme.hash(p, args.cost))?;
+
+    write_accounts(&args.accounts, &accounts, &accounts::to_htpasswd(&accounts), out)
+}
+
+fn chpasswd(args: &ChpasswdArgs, lists: &ListArgs, out: &Output) -> Result<(), CliError> {
+    let names = accounts::read_usernames(&read_input(&args.accounts.users)?, NameRules::Unix)?;
+    let specifier = build_specifier(&args.accounts.spec, lists)?;
+    let algorithm = HashAlgorithm::Sha512Crypt;
+    let rounds = args.rounds.unwrap_or(algorithm.default_cost());
+    let accounts = accounts::create(names, &specifier, |p| Ok(algorithm.hash_with_cost(p, rounds)?))?;
+
+    let lines = if args.shadow {
+        let days = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() / 86_400).unwrap_or(0);
+        accounts::to_shadow(&accounts, days)
+    }
+    else {
+        accounts::to_htpasswd(&accounts)
+    };
+    write_accounts(&args.accounts, &accounts, &lines, out)
+}
+
+/// Write the handoff file of plaintext passwords, then the hashes.
+fn write_accounts(args: &AccountArgs, accounts: &[Account], hashes: &str, out: &Output) -> Result<(), CliError> {
+    if args.output.as_deref() == Some(args.handoff.as_path()) {
+        return Err(CliError::DuplicateOutput(args.handoff.clone()));
+    }
+    out.write_private(&args.handoff, &accounts::to_handoff(accounts)?)?;
+
+    //hashes can be cracked offline, so they are no more public than /etc/shadow
+    out.write_secret(args.output.as_deref(), hashes.as_bytes())
+}
+
+fn export(args: &ExportArgs, lists: &ListArgs, out: &Output) -> Result<(), CliError> {
+    let csv_input = read_input(&args.csv)?;
+    let specifier = build_specifier(&args.spec, lists)?;
+    let records = export::read_records(csv_input.as_bytes(), &specifier)?;
+    let document = export::export(&records, args.format, &mut rand::thread_rng())?;
+
+    out.write_secret(args.output.as_deref(), &document)
+}
+
+fn vault(args: &VaultArgs, lists: &ListArgs, out: &Output) -> Result<(), CliError> {
+    let csv_input = read_input(&args.csv)?;
+    //a seeded run must not repeat an entry's password as the master passphrase
+    let mut seeds = lists.seed.map(ChaCha20Rng::seed_from_u64);
+    let specifier = seeded_specifier(&args.spec, lists, &mut seeds)?;
+    let records = export::read_records(csv_input.as_bytes(), &specifier)?;
+    let master = seeded_specifier(&args.master_spec, lists, &mut seeds)?.get_passphrase()?;
+    let database = kdbx::write(&records, master.expose(), args.cipher, &args.kdf, &mut rand::thread_rng())?;
+    write_private(&args.output, &database)?;
+
+    //open what reached the disk, not what was meant to
+    let written = fs::read(&args.output).map_err(|e| CliError::io(&args.output, e))?;
+    kdbx::verify(&written, master.expose(), &records)?;
+
+    //the database is encrypted already, but its master passphrase isn't
+    out.write(None, Zeroizing::new(format!("{}\n", master.expose())).as_bytes())
+}
+
+fn secrets(args: &SecretsArgs, lists: &ListArgs, out: &Output) -> Result<(), CliError> {
+    let format = args.format.unwrap_or_else(|| SecretFormat::from_path(args.output.as_deref()));
+    let mapping = secrets::parse_mapping(&read_input(&args.mapping)?, format)?;
+    let mut values = Vec::with_capacity(mapping.len());
+    //keys sharing a spec still get their own values when seeded
+    let mut seeds = lists.seed.map(ChaCha20Rng::seed_from_u64);
+    for (key, spec) in mapping {
+        let passphrase = seeded_specifier(&spec, lists, &mut seeds)?.get_passphrase()?;
+        values.push((key, passphrase));
+    }
+
+    let document = match format {
+        SecretFormat::Kubernetes => secrets::to_kubernetes(&values, &args.name, args.namespace.as_deref()),
+        SecretFormat::Env | SecretFormat::Systemd => secrets::to_env(&values)
+    };
+    out.write_secret(args.output.as_deref(), document.as_bytes())
+}
+
+fn apikey(args: &ApikeyArgs, lists: &ListArgs, out: &Output) -> Result<(), CliError> {
+    let generator = ApiKeyGenerator::new(&args.prefix, args.length)?;
+    let mut rng: Box<dyn RngCore> = match lists.seed {
+        Some(seed) => Box::new(ChaCha20Rng::seed_from_u64(seed)),
+        None => Box::new(rand::thread_rng())
+    };
+    let mut keys = Zeroizing::new(String::new());
+    for _ in 0..args.count {
+        keys.push_str(generator.get_token(rng.as_mut()).expose());
+        keys.push('\n');
+    }
+
+    out.write(None, keys.as_bytes())
+}
+
+fn check_apikey(args: &CheckApikeyArgs, out: &Output) -> Result<(), CliError> {
+    let input = if args.keys.is_empty() {
+        Zeroizing::new(read_input(Path::new("-"))?)
+    }
+    else {
+        Zeroizing::new(args.keys.join("\n"))
+    };
+    let generator = match (&args.prefix, args.length) {
+        (Some(prefix), Some(length)) => Some(ApiKeyGenerator::new(prefix, length)?),
+        _ => None
+    };
+
+    let mut report = String::new();
+    let (mut invalid, mut total) = (0, 0);
+    for (n, key) in input.lines().map(|l| l.trim()).filter(|l| !l.is_empty()).enumerate() {
+        let result = match (&generator, &args.prefix) {
+            (Some(generator), _) => generator.check(key),
+            (None, Some(prefix)) => apikey::check_prefixed(key, prefix),
+            (None, None) => apikey::check(key).map(|_| ())
+        };
+        match result {
+            Ok(()) => report.push_str(&format!("{}: ok\n", n + 1)),
+            Err(e) => {
+                report.push_str(&format!("{}: {}\n", n + 1, e));
+                invalid += 1;
+            }
+        }
+        total += 1;
+    }
+    out.write(None, report.as_bytes())?;
+
+    match invalid {
+        0 => Ok(()),
+        _ => Err(CliError::InvalidApiKeys { invalid, total })
+    }
+}
+
+fn totp(args: &TotpArgs, lists: &ListArgs, out: &Output) -> Result<(), CliError> {
+    let mut rng: Box<dyn RngCore> = match lists.seed {
+        Some(seed) => Box::new(ChaCha20Rng::seed_from_u64(seed)),
+        None => Box::new(rand::thread_rng())
+    };
+    let totp = Totp::generate(rng.as_mut(), args.bytes)?.with_params(args.algorithm, args.digits, args.period)?;
+    let uri = totp.uri(&args.issuer, &args.account)?;
+    let text = Zeroizing::new(format!("{}\n{}\n", *totp.secret_base32(), *uri));
+
+    if !args.qr && args.output.is_none() && args.format.is_none() {
+        return out.write(None, text.as_bytes());
+    }
+    let format = args.format.unwrap_or_else(|| QrFormat::from_path(args.output.as_deref()));
+    write_qr(&Qr::encode(uri.as_bytes())?, format, args.scale, args.output.as_deref(), &text, out)
+}
+
+fn totp_code(args: &TotpCodeArgs, out: &Output) -> Result<(), CliError> {
+    let input = if args.secret == "-" { Zeroizing::new(read_input(Path::new("-"))?) } else { Zeroizing::new(args.secret.clone()) };
+    let secret = input.lines().map(|l| l.trim()).find(|l| !l.is_empty()).unwrap_or("");
+    let totp = if secret.starts_with("otpauth:") {
+        Totp::from_uri(secret)?
+    }
+    else {
+        Totp::from_base32(secret)?.with_params(args.algorithm, args.digits, args.period)?
+    };
+    let time = args.time.unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0));
+
+    match &args.check {
+        Some(code) if totp.verify(code.trim(), time, args.window) => out.write(None, b"ok\n"),
+        Some(_) => Err(CliError::InvalidTotpCode),
+        None => out.write(None, format!("{}\n", *totp.code_at(time)).as_bytes())
+    }
+}
+
+fn derive(args: &DeriveArgs, lists: &ListArgs, out: &Output) -> Result<(), CliError> {
+    let input = Zeroizing::new(read_input(&args.master_file)?);
+    let master = input.lines().next().unwrap_or_default();
+    let mut specifier = build_specifier(&args.spec, lists)?;
+    specifier.set_rng(Derivation::default().rng(master, &args.site, &args.login, args.counter)?);
+    let passphrase = specifier.get_passphrase()?;
+
+    out.write(None, Zeroizing::new(format!("{}\n", passphrase.expose())).as_bytes())
+}
+
+fn decrypt(args: &DecryptArgs) -> Result<(), CliError> {
+    let ciphertext = read_binary_input(&args.input)?;
+    let mut identities = Vec::new();
+    for path in &args.identity {
+        identities.extend(encrypt::parse_identities(&Zeroizing::new(read_input(path)?))?);
+    }
+    let passphrase = match &args.passphrase_file {
+        Some(path) => Some(encrypt::read_passphrase(&Zeroizing::new(read_input(path)?))?),
+        None => None
+    };
+    let plaintext = encrypt::decrypt(&ciphertext, &identities, passphrase)?;
+
+    write_secret(args.output.as_deref(), &plaintext)
+}
+
+/// Build a Specifier from a spec and the list and seed options.
+fn build_specifier(spec: &str, lists: &ListArgs) -> Result<Specifier, CliError> {
+    if let Err(offset) = Specifier::check_spec_string(spec) {
+        return Err(CliError::InvalidSpec { spec: spec.to_string(), offset });
+    }
+
+    let words = match &lists.words {
+        Some(path) => read_list(path)?,
+        None => get_simpleton_words().iter().map(|s| s.to_string()).collect()
+    };
+    let symbols = match &lists.symbols {
+        Some(path) => read_list(path)?,
+        None => get_ez_ascii_symbols().iter().map(|s| s.to_string()).collect()
+    };
+
+    let mut specifier = Specifier::try_parse_custom(spec, words, symbols)?;
+    if let Some(seed) = lists.seed {
+        specifier.set_rng(ChaCha20Rng::seed_from_u64(seed));
+    }
+
+    Ok(specifier)
+}
+
+/// Build one of several Specifiers for a run. When seeded, each draws its
+/// seed from `seeds` in turn, so none repeats another's output.
+fn seeded_specifier(spec: &str, lists: &ListArgs, seeds: &mut Option<ChaCha20Rng>) -> Result<Specifier, CliError> {
+    let mut specifier = build_specifier(spec, lists)?;
+    if let Some(seeds) = seeds {
+        specifier.set_rng(ChaCha20Rng::seed_from_u64(seeds.next_u64()));
+    }
+
+    Ok(specifier)
+}
+
+/// Build the template filters, spelling in the chosen language.
+fn build_filters(lists: &ListArgs) -> FilterRegistry {
+    let mut filters = FilterRegistry::with_defaults();
+    filters.register("spell", Rc::new(Speller::new(lists.language)));
+    filters
+}
+
+/// Read a list file with one entry per line, skipping blank lines.
+fn read_list(path: &Path) -> Result<Vec<String>, CliError> {
+    let contents = fs::read_to_string(path).map_err(|e| CliError::io(path, e))?;
+
+    Ok(contents.lines()
+               .map(|l| l.trim())
+               .filter(|l| !l.is_empty())
+               .map(|l| l.to_string())
+               .collect())
+}
+
+/// Read a whole file, or stdin if the path is `-`.
+fn read_input(path: &Path) -> Result<String, CliError> {
+    if path == Path::new("-") {
+        let mut input = String::new();
+        std::io::stdin().read_to_string(&mut input).map_err(|e| CliError::io("<stdin>", e))?;
+        Ok(input)
+    }
+    else {
+        fs::read_to_string(path).map_err(|e| CliError::io(path, e))
+    }
+}
+
+/// Read a whole file as bytes, or stdin if the path is `-`.
+fn read_binary_input(path: &Path) -> Result<Vec<u8>, CliError> {
+    if path == Path::new("-") {
+        let mut input = Vec::new();
+        std::io::stdin().read_to_end(&mut input).map_err(|e| CliError::io("<stdin>", e))?;
+        Ok(input)
+    }
+    else {
+        fs::read(path).map_err(|e| CliError::io(path, e))
+    }
+}
+
+/// Write a file that only its owner can read, replacing any existing one.
+fn write_private(path: &Path, contents: &[u8]) -> Result<(), CliError> {
+    let error = |e| CliError::io(path, e);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    //a new file is never readable by others, not even before the chmod
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(error)?;
+    //the mode only applies to new files, so tighten an existing one
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600)).map_err(error)?;
+    }
+
+    file.write_all(contents).map_err(error)
+}
+
+/// Write secrets to a file only its owner can read, or stdout if no path is given.
+fn write_secret(path: Option<&Path>, contents: &[u8]) -> Result<(), CliError> {
+    match path {
+        Some(path) => write_private(path, contents),
+        None => write_output(None, contents)
+    }
+}
+
+/// Write to a file, or stdout if no path is given.
+fn write_output(path: Option<&Path>, contents: &[u8]) -> Result<(), CliError> {
+    match path {
+        Some(path) => fs::write(path, contents).map_err(|e| CliError::io(path, e)),
+        None => std::io::stdout().write_all(contents).map_err(|e| CliError::io("<stdout>", e))
+    }
+}
+
+/// Where the commands write, encrypting everything if asked to.
+struct Output {
+    encryption: Option<Encryption>
+}
+
+impl Output {
+    /// Encrypt the contents of a file, or pass them through.
+    fn encrypt<'a>(&self, contents: &'a [u8]) -> Result<Cow<'a, [u8]>, CliError> {
+        match &self.encryption {
+            Some(encryption) => Ok(Cow::Owned(encryption.encrypt(contents)?)),
+            None => Ok(Cow::Borrowed(contents))
+        }
+    }
+
+    /// Write to a file, or stdout if no path is given.
+    fn write(&self, path: Option<&Path>, contents: &[u8]) -> Result<(), CliError> {
+        let binary = self.encryption.as_ref().is_some_and(|e| !e.is_armored());
+        if path.is_none() && binary && std::io::stdout().is_terminal() {
+            return Err(CliError::Encryption("not writing binary output to a terminal; use --armor or -o".to_string()));
+        }
+        write_output(path, &self.encrypt(contents)?)
+    }
+
+    /// Write a file that only its owner can read.
+    fn write_private(&self, path: &Path, contents: &[u8]) -> Result<(), CliError> {
+        write_private(path, &self.encrypt(contents)?)
+    }
 
-        let specifier = Specifier::try_parse(spec).unwrap();
-        println!("{} ({}): {}", name, spec, specifier.get_passphrase());
+    /// Write secrets to a file only its owner can read, or stdout if no path is given.
+    fn write_secret(&self, path: Option<&Path>, contents: &[u8]) -> Result<(), CliError> {
+        match path {
+            Some(path) => self.write_private(path, contents),
+            None => self.write(None, contents)
+        }
+    }
+}
+
+/// Set up encryption to the recipients or passphrase given, if any.
+fn build_output(args: &EncryptArgs) -> Result<Output, CliError> {
+    if let Some(path) = &args.encrypt_passphrase_file {
+        let passphrase = encrypt::read_passphrase(&read_input(path)?)?;
+        return Ok(Output { encryption: Some(Encryption::to_passphrase(passphrase, args.armor)) });
+    }
 
+    let mut recipients = args.encrypt_to
+                              .iter()
+                              .map(|key| encrypt::parse_recipient(key))
+                              .collect::<Result<Vec<_>, _>>()?;
+    for path in &args.encrypt_to_file {
+        recipients.extend(encrypt::parse_recipients(&read_input(path)?)?);
     }
+    let encryption = (!recipients.is_empty()).then(|| Encryption::to_recipients(recipients, args.armor));
 
+    Ok(Output { encryption })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("mpassgen-{}-{}", std::process::id(), name))
+    }
+
+    fn mode(path: &Path) -> u32 {
+        fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn render_output_is_private() {
+        let (template, output) = (temp_path("render.txt"), temp_path("render.out"));
+        fs::write(&template, "PASSWORD='<<pw:w w>>'\n").unwrap();
+        let cli = Cli::try_parse_args(["mpassgen".as_ref(), "render".as_ref(), template.as_os_str(),
+                                       "-o".as_ref(), output.as_os_str()]).unwrap();
+        let Command::Render(args) = &cli.command else {
+            panic!("expected render command");
+        };
+        render(args, &cli.lists, &Output { encryption: None }).unwrap();
+
+        assert_eq!(mode(&output), 0o600);
+        assert!(fs::read_to_string(&output).unwrap().starts_with("PASSWORD='"));
+        fs::remove_file(template).unwrap();
+        fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn account_files_are_private() {
+        let (users, handoff, output) = (temp_path("users.txt"), temp_path("handoff.csv"), temp_path("shadow.out"));
+        fs::write(&users, "alice\nbob\n").unwrap();
+        let cli = Cli::try_parse_args(["mpassgen".as_ref(), "chpasswd".as_ref(), users.as_os_str(), "--shadow".as_ref(),
+                                       "--rounds".as_ref(), "1000".as_ref(), "--handoff".as_ref(), handoff.as_os_str(),
+                                       "-o".as_ref(), output.as_os_str()]).unwrap();
+        let Command::Chpasswd(args) = &cli.command else {
+            panic!("expected chpasswd command");
+        };
+        chpasswd(args, &cli.lists, &Output { encryption: None }).unwrap();
+
+        assert_eq!((mode(&handoff), mode(&output)), (0o600, 0o600));
+        assert!(fs::read_to_string(&output).unwrap().starts_with("alice:$6$"));
+        for path in [users, handoff, output] {
+            fs::remove_file(path).unwrap();
+        }
+    }
 }
@@ -0,0 +1,148 @@
+//! Render one document per CSV row.
+//!
+//! Every column of the CSV is available in the template as `<<column>>`,
+//! alongside generated `<<name:spec>>` values. The output file name is itself
+//! a template, so `letters/<<username>>.html` writes one letter per user.
+//! A summary CSV lists each output file with the row and its generated values.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use mpass::Specifier;
+use mpass::filter::FilterRegistry;
+use crate::escape::Escape;
+use crate::error::CliError;
+use crate::template::Template;
+
+/// Name bound to the 1-based row number in file name patterns.
+pub const ROW_FIELD: &str = "_row";
+
+/// The templates and options for a merge.
+pub struct Merge<'a> {
+    pub template: &'a Template,
+    /// Output file name pattern.
+    pub pattern: &'a Template,
+    pub filters: &'a FilterRegistry,
+    pub escape: Escape
+}
+
+impl Merge<'_> {
+    /// Render the template for every record in the CSV input. Each output is
+    /// passed to `write` with its file name; the summary CSV is written to `summary`.
+    /// Returns the number of rows rendered.
+    pub fn run(&self, csv_input: impl std::io::Read, specifier: &mut Specifier, summary: impl Write,
+               mut write: impl FnMut(&Path, &[u8]) -> Result<(), CliError>) -> Result<usize, CliError> {
+        let mut reader = csv::Reader::from_reader(csv_input);
+        let headers: Vec<String> = reader.headers()
+                                         .map_err(CliError::Csv)?
+                                         .iter()
+                                         .map(|h| h.trim().to_string())
+                                         .collect();
+        let mut summary = csv::Writer::from_writer(summary);
+        let mut written: HashSet<PathBuf> = HashSet::new();
+        let mut rows = 0;
+
+        for (index, record) in reader.records().enumerate() {
+            let row = index + 1;
+            let in_row = |e: CliError| CliError::Row { row, error: Box::new(e) };
+            let record = record.map_err(|e| in_row(CliError::Csv(e)))?;
+
+            let mut fields: HashMap<String, String> = headers.iter()
+                                                             .cloned()
+                                                             .zip(record.iter().map(|v| v.to_string()))
+                                                             .collect();
+            let rendered = self.template.render_with(specifier, self.filters, self.escape, &fields)
+                                        .map_err(|e| in_row(e.into()))?;
+
+            fields.insert(ROW_FIELD.to_string(), row.to_string());
+            let name = self.pattern.render_with(specifier, self.filters, Escape::Filename, &fields)
+                                   .map_err(|e| in_row(e.into()))?;
+            let path = PathBuf::from(name.output.as_str());
+            if !written.insert(path.clone()) {
+                return Err(in_row(CliError::DuplicateOutput(path)));
+            }
+            write(&path, rendered.output.as_bytes()).map_err(in_row)?;
+
+            if rows == 0 {
+                let mut header = vec!["file".to_string()];
+                header.extend(headers.iter().cloned());
+                header.extend(rendered.generated.iter().map(|(n, _)| n.clone()));
+                summary.write_record(&header).map_err(CliError::Csv)?;
+            }
+            let mut line = vec![path.display().to_string()];
+            line.extend(record.iter().map(|v| v.to_string()));
+            line.extend(rendered.generated.iter().map(|(_, v)| v.expose().to_string()));
+            summary.write_record(&line).map_err(CliError::Csv)?;
+            rows += 1;
+        }
+
+        summary.flush().map_err(|e| CliError::io("<summary>", e))?;
+        Ok(rows)
+    }
+}
+
+/// Create the parent directories of an output file if needed.
+pub fn create_parent_dirs(path: &Path) -> Result<(), CliError> {
+    match path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        Some(parent) => fs::create_dir_all(parent).map_err(|e| CliError::io(parent, e)),
+        None => Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_chacha::ChaCha20Rng;
+    use rand_chacha::rand_core::SeedableRng;
+
+    fn run(template: &str, pattern: &str, csv: &str) -> Result<(Vec<(PathBuf, String)>, String), CliError> {
+        let mut specifier = Specifier::try_parse("w").unwrap();
+        specifier.set_rng(ChaCha20Rng::seed_from_u64(9));
+        let template = Template::parse(template).unwrap();
+        let pattern = Template::parse(pattern).unwrap();
+        let mut files = Vec::new();
+        let mut summary = Vec::new();
+
+        let filters = FilterRegistry::with_defaults();
+        let merge = Merge { template: &template, pattern: &pattern, filters: &filters, escape: Escape::Html };
+        merge.run(csv.as_bytes(), &mut specifier, &mut summary, |p, c| {
+            files.push((p.to_path_buf(), String::from_utf8(c.to_vec()).unwrap()));
+            Ok(())
+        })?;
+        Ok((files, String::from_utf8(summary).unwrap()))
+    }
+
+    #[test]
+    fn one_file_per_row() {
+        let csv = "username, name\njdoe,Jane Doe\nbsmith,\"Bob & Sons\"\n";
+        let (files, summary) = run("Dear <<name>>, your password is <<pw:####>> (<<pw>>)",
+                                   "out/<<username>>-<<_row>>.html", csv).unwrap();
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].0, PathBuf::from("out/jdoe-1.html"));
+        assert_eq!(files[1].0, PathBuf::from("out/bsmith-2.html"));
+        assert!(files[1].1.starts_with("Dear Bob &amp; Sons, your password is "));
+
+        let lines: Vec<&str> = summary.lines().collect();
+        assert_eq!(lines[0], "file,username,name,pw");
+        let pw = &files[0].1[files[0].1.len() - 5..files[0].1.len() - 1];
+        assert_eq!(lines[1], format!("out/jdoe-1.html,jdoe,Jane Doe,{}", pw));
+        assert!(lines[2].starts_with("out/bsmith-2.html,bsmith,Bob & Sons,"));
+    }
+
+    #[test]
+    fn rejects_duplicates_and_unsafe_names() {
+        let e = run("<<pw:##>>", "<<team>>.txt", "team\nred\nred\n").err().unwrap();
+        assert_eq!(e.to_string(), "row 2: output file red.txt is written more than once");
+
+        let (files, _) = run("<<pw:##>>", "<<team>>.txt", "team\n../../etc/passwd\n").unwrap();
+        assert_eq!(files[0].0, PathBuf::from(".._.._etc_passwd.txt"));
+    }
+
+    #[test]
+    fn reports_row_errors() {
+        let e = run("<<missing>>", "<<_row>>.txt", "team\nred\n").err().unwrap();
+        assert_eq!(e.to_string(), "row 1: line 1, column 1: 'missing' is not defined");
+    }
+}
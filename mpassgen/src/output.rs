@@ -0,0 +1,249 @@
+//! Machine-readable output of generated passphrases.
+//!
+//! Every item carries the spec, the passphrase, the spec's entropy, the text
+//! each token produced and when it was generated, plus a spelling and a hash
+//! of the passphrase if asked for. `json` writes one array,
+//! `ndjson` one object per line and `csv` one row per item with a
+//! `token_N`/`value_N` column pair per token.
+
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+use clap::ValueEnum;
+use mpass::Passphrase;
+use mpass::specifier::breakdown::Breakdown;
+use zeroize::Zeroizing;
+use crate::escape::Escape;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OutputFormat {
+    /// One passphrase per line
+    #[default]
+    Plain,
+    Json,
+    Ndjson,
+    Csv
+}
+
+/// One generated passphrase.
+pub struct Item {
+    pub breakdown: Breakdown,
+    /// The passphrase spelled out, if asked for.
+    pub spelling: Option<Passphrase>,
+    /// A password hash of the passphrase, if asked for.
+    pub hash: Option<String>,
+    pub generated_at: SystemTime
+}
+
+/// Writes items in one format, adding any header and footer it needs.
+pub struct ItemWriter<W: Write> {
+    format: OutputFormat,
+    spec: String,
+    entropy: f64,
+    out: W,
+    written: usize
+}
+
+impl<W: Write> ItemWriter<W> {
+    pub fn new(format: OutputFormat, spec: &str, entropy: f64, out: W) -> Self {
+        ItemWriter { format, spec: spec.to_string(), entropy, out, written: 0 }
+    }
+
+    pub fn write(&mut self, item: &Item) -> io::Result<()> {
+        let passphrase = item.breakdown.passphrase.expose();
+        let line = match self.format {
+            OutputFormat::Plain => {
+                let mut line = Zeroizing::new(format!("{}\n", passphrase));
+                if let Some(spelling) = &item.spelling {
+                    line.push_str(&format!("  {}\n", spelling.expose()));
+                }
+                if let Some(hash) = &item.hash {
+                    line.push_str(&format!("  {}\n", hash));
+                }
+                line
+            }
+            OutputFormat::Json => {
+                let separator = if self.written == 0 { "[\n" } else { ",\n" };
+                Zeroizing::new(format!("{}  {}", separator, self.json(item).as_str()))
+            }
+            OutputFormat::Ndjson => Zeroizing::new(format!("{}\n", self.json(item).as_str())),
+            OutputFormat::Csv => {
+                let mut line = Zeroizing::new(String::new());
+                if self.written == 0 {
+                    line.push_str("spec,passphrase,entropy_bits,generated_at");
+                    if item.spelling.is_some() {
+                        line.push_str(",spelling");
+                    }
+                    if item.hash.is_some() {
+                        line.push_str(",hash");
+                    }
+                    for n in 1..=item.breakdown.parts.len() {
+                        line.push_str(&format!(",token_{n},value_{n}"));
+                    }
+                    line.push('\n');
+                }
+                let mut fields = vec![csv_field(&self.spec), csv_field(passphrase), format!("{:.2}", self.entropy),
+                                      timestamp(item.generated_at)];
+                if let Some(spelling) = &item.spelling {
+                    fields.push(csv_field(spelling.expose()));
+                }
+                if let Some(hash) = &item.hash {
+                    fields.push(csv_field(hash));
+                }
+                for (token, text) in &item.breakdown.parts {
+                    fields.push(csv_field(&token.to_string()));
+                    fields.push(csv_field(text.expose()));
+                }
+                line.push_str(&Zeroizing::new(fields.join(",")));
+                line.push('\n');
+                line
+            }
+        };
+
+        self.written += 1;
+        self.out.write_all(line.as_bytes())
+    }
+
+    /// Close any open structure and flush the output.
+    pub fn finish(mut self) -> io::Result<()> {
+        if self.format == OutputFormat::Json {
+            let close = if self.written == 0 { "[]\n" } else { "\n]\n" };
+            self.out.write_all(close.as_bytes())?;
+        }
+        self.out.flush()
+    }
+
+    fn json(&self, item: &Item) -> Zeroizing<String> {
+        let string = |s: &str| format!("\"{}\"", Escape::Json.apply(s));
+        let tokens: Vec<String> = item.breakdown
+                                      .parts
+                                      .iter()
+                                      .map(|(token, text)| format!("{{\"token\":{},\"value\":{}}}",
+                                                                   string(&token.to_string()),
+                                                                   string(text.expose())))
+                                      .collect();
+        let spelling = match &item.spelling {
+            Some(spelling) => format!(",\"spelling\":{}", string(spelling.expose())),
+            None => String::new()
+        };
+        let hash = match &item.hash {
+            Some(hash) => format!(",\"hash\":{}", string(hash)),
+            None => String::new()
+        };
+
+        Zeroizing::new(format!("{{\"spec\":{},\"passphrase\":{},\"entropy_bits\":{:.2}{}{},\"tokens\":[{}],\"generated_at\":{}}}",
+                               string(&self.spec), string(item.breakdown.passphrase.expose()), self.entropy,
+                               Zeroizing::new(spelling).as_str(), hash, Zeroizing::new(tokens.join(",")).as_str(),
+                               string(&timestamp(item.generated_at))))
+    }
+}
+
+/// Quote a CSV field if it needs it.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) || value.starts_with(' ') || value.ends_with(' ') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    }
+    else {
+        value.to_string()
+    }
+}
+
+/// Format a time as an RFC 3339 UTC timestamp with second precision.
+fn timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+
+    //civil date from days since the epoch, after Howard Hinnant's algorithm
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, rem / 3600, rem / 60 % 60, rem % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use mpass::Specifier;
+    use rand_chacha::ChaCha20Rng;
+    use rand_chacha::rand_core::SeedableRng;
+
+    /// Write `count` items, returning the output and the passphrases.
+    fn write(format: OutputFormat, spell: bool, count: usize) -> (String, Vec<String>) {
+        write_hashed(format, spell, false, count)
+    }
+
+    fn write_hashed(format: OutputFormat, spell: bool, hash: bool, count: usize) -> (String, Vec<String>) {
+        let mut specifier = Specifier::try_parse("i #").unwrap();
+        specifier.set_rng(ChaCha20Rng::seed_from_u64(5));
+        let mut out = Vec::new();
+        let mut writer = ItemWriter::new(format, "i #", 12.5, &mut out);
+        let mut passphrases = Vec::new();
+
+        for _ in 0..count {
+            let breakdown = specifier.get_breakdown().unwrap();
+            let spelling = spell.then(|| Passphrase::new(format!("spelled, \"{}\"", breakdown.parts[0].1.expose())));
+            let generated_at = UNIX_EPOCH + Duration::from_secs(1_760_000_000);
+            let hash = hash.then(|| format!("$hash${}", breakdown.parts[0].1.expose()));
+            passphrases.push(breakdown.passphrase.expose().to_string());
+            writer.write(&Item { breakdown, spelling, hash, generated_at }).unwrap();
+        }
+        writer.finish().unwrap();
+        (String::from_utf8(out).unwrap(), passphrases)
+    }
+
+    #[test]
+    fn formats() {
+        let (ndjson, passphrases) = write(OutputFormat::Ndjson, false, 2);
+        let (word, digit) = passphrases[0].split_once(' ').unwrap();
+        let lines: Vec<&str> = ndjson.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], format!("{{\"spec\":\"i #\",\"passphrase\":\"{} {}\",\"entropy_bits\":12.50,\"tokens\":[\
+                                      {{\"token\":\"i\",\"value\":\"{}\"}},{{\"token\":\" \",\"value\":\" \"}},\
+                                      {{\"token\":\"#\",\"value\":\"{}\"}}],\"generated_at\":\"2025-10-09T08:53:20Z\"}}",
+                                     word, digit, word, digit));
+
+        let (json, _) = write(OutputFormat::Json, true, 2);
+        assert!(json.starts_with("[\n  {\"spec\"") && json.ends_with("}\n]\n"));
+        assert_eq!(json.matches(&format!("\"spelling\":\"spelled, \\\"{}\\\"\"", word)).count(), 1);
+        assert_eq!(write(OutputFormat::Json, false, 0).0, "[]\n");
+
+        let (csv, _) = write(OutputFormat::Csv, true, 2);
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], "spec,passphrase,entropy_bits,generated_at,spelling,token_1,value_1,token_2,value_2,token_3,value_3");
+        assert_eq!(rows[1], format!("i #,{} {},12.50,2025-10-09T08:53:20Z,\"spelled, \"\"{}\"\"\",i,{},\" \",\" \",#,{}",
+                                    word, digit, word, word, digit));
+
+        let (plain, _) = write(OutputFormat::Plain, true, 1);
+        assert_eq!(plain, format!("{} {}\n  spelled, \"{}\"\n", word, digit, word));
+    }
+
+    #[test]
+    fn hashes() {
+        let (plain, passphrases) = write_hashed(OutputFormat::Plain, false, true, 1);
+        let word = passphrases[0].split(' ').next().unwrap();
+        assert_eq!(plain, format!("{}\n  $hash${}\n", passphrases[0], word));
+
+        let (ndjson, _) = write_hashed(OutputFormat::Ndjson, true, true, 1);
+        assert!(ndjson.contains(&format!("\"entropy_bits\":12.50,\"spelling\":\"spelled, \\\"{}\\\"\",\
+                                          \"hash\":\"$hash${}\",\"tokens\"", word, word)));
+
+        let (csv, _) = write_hashed(OutputFormat::Csv, false, true, 1);
+        assert!(csv.starts_with("spec,passphrase,entropy_bits,generated_at,hash,token_1"));
+        assert!(csv.contains(&format!("Z,$hash${},i,", word)));
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)), "2000-02-29T00:00:00Z");
+        assert_eq!(timestamp(UNIX_EPOCH + Duration::from_secs(4_102_444_799)), "2099-12-31T23:59:59Z");
+    }
+}
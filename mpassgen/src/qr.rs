@@ -0,0 +1,234 @@
+//! QR codes for generated secrets.
+//!
+//! Codes can be printed to a terminal with block characters or written as
+//! SVG or PNG images. [`wifi_payload`] builds the payload phones read to
+//! join a Wi-Fi network.
+
+use std::path::Path;
+use clap::ValueEnum;
+use qrcode::{Color, EcLevel, QrCode};
+use zeroize::Zeroizing;
+use crate::error::CliError;
+
+/// Modules of blank border around a code, as required by the QR spec.
+pub const QUIET_ZONE: usize = 4;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum QrFormat {
+    /// Block characters for a terminal
+    Terminal,
+    Svg,
+    Png
+}
+
+impl QrFormat {
+    /// Pick the format from a file extension, defaulting to the terminal.
+    pub fn from_path(path: Option<&Path>) -> Self {
+        match path.and_then(|p| p.extension()).and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("svg") => QrFormat::Svg,
+            Some(ext) if ext.eq_ignore_ascii_case("png") => QrFormat::Png,
+            _ => QrFormat::Terminal
+        }
+    }
+}
+
+/// Wi-Fi authentication types understood by phone cameras.
+#[derive(ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WifiSecurity {
+    /// WPA or WPA2 personal
+    Wpa,
+    /// WPA3 personal
+    Sae,
+    Wep
+}
+
+/// Build the `WIFI:` payload for joining a network with a password.
+pub fn wifi_payload(ssid: &str, security: WifiSecurity, password: &str, hidden: bool) -> Zeroizing<String> {
+    let security = match security {
+        WifiSecurity::Wpa => "WPA",
+        WifiSecurity::Sae => "SAE",
+        WifiSecurity::Wep => "WEP"
+    };
+    let mut payload = Zeroizing::new(format!("WIFI:T:{};S:{};P:{};", security, wifi_escape(ssid),
+                                             Zeroizing::new(wifi_escape(password)).as_str()));
+    if hidden {
+        payload.push_str("H:true;");
+    }
+    payload.push(';');
+
+    payload
+}
+
+/// Escape a `WIFI:` field. Values that could be read as hex are quoted so
+/// they are taken as text.
+fn wifi_escape(value: &str) -> String {
+    if !value.is_empty() && value.chars().all(|c| c.is_ascii_hexdigit()) {
+        return format!("\"{}\"", value);
+    }
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        if matches!(ch, '\\' | ';' | ',' | ':' | '"') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+
+    escaped
+}
+
+/// An encoded QR code, as a square grid of dark and light modules.
+pub struct Qr {
+    width: usize,
+    modules: Zeroizing<Vec<bool>>
+}
+
+impl Qr {
+    /// Encode data with medium error correction.
+    pub fn encode(data: &[u8]) -> Result<Qr, CliError> {
+        let code = QrCode::with_error_correction_level(data, EcLevel::M).map_err(CliError::Qr)?;
+        let modules = code.to_colors().into_iter().map(|c| c == Color::Dark).collect();
+
+        Ok(Qr { width: code.width(), modules: Zeroizing::new(modules) })
+    }
+
+    /// Width of the code in modules, without the quiet zone.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn is_dark(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.width + x]
+    }
+
+    /// Check a module by its position including the quiet zone.
+    fn is_dark_padded(&self, x: usize, y: usize) -> bool {
+        let range = QUIET_ZONE..QUIET_ZONE + self.width;
+        range.contains(&x) && range.contains(&y) && self.is_dark(x - QUIET_ZONE, y - QUIET_ZONE)
+    }
+
+    /// Iterate over the positions of the dark modules.
+    pub fn dark_modules(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0..self.width).flat_map(move |y| (0..self.width).map(move |x| (x, y)))
+                       .filter(|&(x, y)| self.is_dark(x, y))
+    }
+
+    /// Render as an SVG image, `size` user units square including the quiet zone.
+    pub fn to_svg(&self, size: usize) -> Zeroizing<String> {
+        let total = self.width + 2 * QUIET_ZONE;
+        let mut svg = Zeroizing::new(format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{size}\" height=\"{size}\" \
+                                              viewBox=\"0 0 {total} {total}\" shape-rendering=\"crispEdges\">\
+                                              <rect width=\"{total}\" height=\"{total}\" fill=\"#fff\"/><path d=\""));
+        for (x, y) in self.dark_modules() {
+            svg.push_str(&format!("M{} {}h1v1h-1z", x + QUIET_ZONE, y + QUIET_ZONE));
+        }
+        svg.push_str("\" fill=\"#000\"/></svg>");
+
+        svg
+    }
+
+    /// Render with half block characters, two rows of modules per line.
+    /// Colours are set explicitly so the code scans on dark terminals too.
+    pub fn to_terminal(&self) -> Zeroizing<String> {
+        let total = self.width + 2 * QUIET_ZONE;
+        let mut text = Zeroizing::new(String::new());
+
+        for y in (0..total).step_by(2) {
+            text.push_str("\x1b[30;107m");
+            for x in 0..total {
+                let top = self.is_dark_padded(x, y);
+                let bottom = y + 1 < total && self.is_dark_padded(x, y + 1);
+                text.push(match (top, bottom) {
+                    (true, true) => '█',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (false, false) => ' '
+                });
+            }
+            text.push_str("\x1b[0m\n");
+        }
+
+        text
+    }
+
+    /// Render as a greyscale PNG image with `scale` pixels per module.
+    pub fn to_png(&self, scale: u32) -> Zeroizing<Vec<u8>> {
+        let total = self.width + 2 * QUIET_ZONE;
+        let size = total as u32 * scale;
+        let mut pixels = Zeroizing::new(Vec::with_capacity((size * size) as usize));
+        for y in 0..size as usize {
+            for x in 0..size as usize {
+                let dark = self.is_dark_padded(x / scale as usize, y / scale as usize);
+                pixels.push(if dark { 0 } else { 255 });
+            }
+        }
+
+        let mut png = Zeroizing::new(Vec::new());
+        let mut encoder = png::Encoder::new(&mut *png, size, size);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        //writing to memory with a valid header can't fail
+        let mut writer = encoder.write_header().expect("valid PNG header");
+        writer.write_image_data(&pixels).expect("image data matches the header");
+        writer.finish().expect("PNG written to memory");
+
+        png
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes() {
+        let qr = Qr::encode(b"Medium test phrase 123!").unwrap();
+
+        assert_eq!(qr.width(), 25);
+        // finder pattern corners
+        assert!(qr.is_dark(0, 0) && qr.is_dark(6, 6) && !qr.is_dark(7, 7));
+        assert!(qr.is_dark(qr.width() - 1, 0));
+
+        let svg = qr.to_svg(100);
+        assert!(svg.starts_with("<svg") && svg.contains("viewBox=\"0 0 33 33\"") && svg.contains("M4 4h1v1h-1z"));
+    }
+
+    #[test]
+    fn terminal_and_png() {
+        let qr = Qr::encode(b"abc").unwrap();
+        let text = qr.to_terminal();
+        let lines: Vec<&str> = text.lines().collect();
+
+        // 21 modules and the quiet zone, two rows per line
+        assert_eq!(lines.len(), 15);
+        assert!(lines[0].starts_with("\x1b[30;107m ") && lines[0].ends_with(" \x1b[0m"));
+        // top edge of the finder patterns after the quiet zone
+        assert!(lines[2].starts_with("\x1b[30;107m    █▀▀▀▀▀█ "));
+
+        let png = qr.to_png(2);
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        // IHDR width and height
+        assert_eq!(&png[16..24], &[0, 0, 0, 58, 0, 0, 0, 58]);
+    }
+
+    #[test]
+    fn wifi() {
+        assert_eq!(*wifi_payload("Guest", WifiSecurity::Wpa, "Medium test 12!", false),
+                   "WIFI:T:WPA;S:Guest;P:Medium test 12!;;");
+        assert_eq!(*wifi_payload("Café;\"Bar\"", WifiSecurity::Sae, "a:b,c\\d", true),
+                   r#"WIFI:T:SAE;S:Café\;\"Bar\";P:a\:b\,c\\d;H:true;;"#);
+        assert_eq!(*wifi_payload("CAFE", WifiSecurity::Wep, "123456", false),
+                   "WIFI:T:WEP;S:\"CAFE\";P:\"123456\";;");
+    }
+
+    #[test]
+    fn format_from_path() {
+        assert_eq!(QrFormat::from_path(Some(Path::new("guest.PNG"))), QrFormat::Png);
+        assert_eq!(QrFormat::from_path(Some(Path::new("guest.svg"))), QrFormat::Svg);
+        assert_eq!(QrFormat::from_path(Some(Path::new("guest.txt"))), QrFormat::Terminal);
+    }
+
+    #[test]
+    fn too_long() {
+        assert!(matches!(Qr::encode(&[b'x'; 4000]), Err(CliError::Qr(_))));
+    }
+}
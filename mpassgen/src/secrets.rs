@@ -0,0 +1,185 @@
+//! Secret files for services, from a mapping of keys to specs.
+//!
+//! The mapping has one `KEY=SPEC` line per secret; a line with only a key
+//! uses the default spec, and blank lines and `#` comments are skipped.
+//! Secrets are written as a Kubernetes `Secret` manifest with base64 `data`,
+//! a docker-compose `.env` file or a systemd `EnvironmentFile`.
+
+use std::path::Path;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use clap::ValueEnum;
+use mpass::Passphrase;
+use zeroize::Zeroizing;
+use crate::cli::DEFAULT_SPEC;
+use crate::error::CliError;
+use crate::escape::Escape;
+
+/// Longest key Kubernetes accepts in a Secret.
+const KUBERNETES_KEY_MAX: usize = 253;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SecretFormat {
+    /// Kubernetes Secret manifest
+    Kubernetes,
+    /// docker-compose .env file
+    Env,
+    /// systemd EnvironmentFile
+    Systemd
+}
+
+impl SecretFormat {
+    /// Pick the format from a file extension, defaulting to a `.env` file.
+    pub fn from_path(path: Option<&Path>) -> Self {
+        match path.and_then(|p| p.extension()).and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("yaml") || ext.eq_ignore_ascii_case("yml") => {
+                SecretFormat::Kubernetes
+            }
+            Some(ext) if ext.eq_ignore_ascii_case("conf") => SecretFormat::Systemd,
+            _ => SecretFormat::Env
+        }
+    }
+
+    /// Check a key can be used in this format: Kubernetes allows letters,
+    /// digits, `-`, `_` and `.`; environment variables must be identifiers.
+    fn accepts_key(self, key: &str) -> bool {
+        match self {
+            SecretFormat::Kubernetes => {
+                !key.is_empty() && key.len() <= KUBERNETES_KEY_MAX
+                    && key.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            }
+            SecretFormat::Env | SecretFormat::Systemd => {
+                key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                    && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }
+        }
+    }
+}
+
+/// Read a mapping file into `(key, spec)` pairs in file order.
+pub fn parse_mapping(input: &str, format: SecretFormat) -> Result<Vec<(String, String)>, CliError> {
+    let mut entries: Vec<(String, String)> = Vec::new();
+    for (n, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, spec) = match line.split_once('=') {
+            Some((key, spec)) => (key.trim(), spec.trim()),
+            None => (line, DEFAULT_SPEC)
+        };
+
+        let error = |message: String| CliError::Line { line: n + 1, error: Box::new(CliError::InvalidMapping(message)) };
+        if !format.accepts_key(key) {
+            return Err(error(format!("invalid key '{}'", key)));
+        }
+        if entries.iter().any(|(existing, _)| existing == key) {
+            return Err(error(format!("key '{}' is listed more than once", key)));
+        }
+        entries.push((key.to_string(), spec.to_string()));
+    }
+
+    Ok(entries)
+}
+
+/// Write a Kubernetes `Secret` manifest of type `Opaque`.
+pub fn to_kubernetes(secrets: &[(String, Passphrase)], name: &str, namespace: Option<&str>) -> Zeroizing<String> {
+    let quote = |s: &str| format!("\"{}\"", Escape::Yaml.apply(s));
+    let mut yaml = Zeroizing::new(format!("apiVersion: v1\nkind: Secret\nmetadata:\n  name: {}\n", quote(name)));
+    if let Some(namespace) = namespace {
+        yaml.push_str(&format!("  namespace: {}\n", quote(namespace)));
+    }
+    yaml.push_str("type: Opaque\ndata:");
+    if secrets.is_empty() {
+        yaml.push_str(" {}");
+    }
+    yaml.push('\n');
+    for (key, value) in secrets {
+        yaml.push_str(&Zeroizing::new(format!("  {}: {}\n", quote(key), STANDARD.encode(value.expose()))));
+    }
+
+    yaml
+}
+
+/// Write `KEY=value` lines for a `.env` file or a systemd `EnvironmentFile`.
+pub fn to_env(secrets: &[(String, Passphrase)]) -> Zeroizing<String> {
+    let mut env = Zeroizing::new(String::new());
+    for (key, value) in secrets {
+        env.push_str(&Zeroizing::new(format!("{}={}\n", key, quote_env(value.expose()))));
+    }
+
+    env
+}
+
+/// Quote a value so docker-compose and systemd both read it literally.
+/// Single quotes need no escaping, but can't hold a single quote; then use
+/// double quotes with the characters that are special in them escaped.
+fn quote_env(value: &str) -> String {
+    if !value.contains('\'') {
+        return format!("'{}'", value);
+    }
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for ch in value.chars() {
+        if matches!(ch, '\\' | '"' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(ch);
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mpass::rtg::default_lists::get_ez_ascii_symbols;
+
+    fn secrets(values: &[(&str, &str)]) -> Vec<(String, Passphrase)> {
+        values.iter().map(|(k, v)| (k.to_string(), Passphrase::new(v.to_string()))).collect()
+    }
+
+    #[test]
+    fn parses_mapping() {
+        let mapping = "# database\nDB_PASSWORD = i w w ##\n\nAPI_TOKEN=xxxxxxxx\nSESSION_KEY\n";
+        let entries = parse_mapping(mapping, SecretFormat::Env).unwrap();
+        assert_eq!(entries, [("DB_PASSWORD".to_string(), "i w w ##".to_string()),
+                             ("API_TOKEN".to_string(), "xxxxxxxx".to_string()),
+                             ("SESSION_KEY".to_string(), DEFAULT_SPEC.to_string())]);
+
+        assert!(parse_mapping("db.password=ww", SecretFormat::Kubernetes).is_ok());
+        let error = parse_mapping("A=ww\ndb.password=ww", SecretFormat::Env).unwrap_err();
+        assert_eq!(error.to_string(), "line 2: invalid key 'db.password'");
+        let error = parse_mapping("A=ww\nA=##", SecretFormat::Systemd).unwrap_err();
+        assert_eq!(error.to_string(), "line 2: key 'A' is listed more than once");
+        assert!(parse_mapping("1A=ww", SecretFormat::Env).is_err());
+        assert!(parse_mapping("=ww", SecretFormat::Kubernetes).is_err());
+    }
+
+    #[test]
+    fn kubernetes_manifest() {
+        let yaml = to_kubernetes(&secrets(&[("db.password", "Medium test 12!"), ("token", "a$b")]), "app", Some("prod"));
+        assert_eq!(*yaml, "apiVersion: v1\nkind: Secret\nmetadata:\n  name: \"app\"\n  namespace: \"prod\"\n\
+                           type: Opaque\ndata:\n  \"db.password\": TWVkaXVtIHRlc3QgMTIh\n  \"token\": YSRi\n");
+
+        let empty = to_kubernetes(&[], "app", None);
+        assert!(empty.ends_with("name: \"app\"\ntype: Opaque\ndata: {}\n"));
+    }
+
+    #[test]
+    fn env_quoting() {
+        let symbols: String = get_ez_ascii_symbols().into_iter().collect();
+        let env = to_env(&secrets(&[("ALL", &symbols), ("QUOTE", "it's $HOME \"x\" `y` \\")]));
+
+        assert_eq!(*env, "ALL='!@#$%&*+-'\nQUOTE=\"it's \\$HOME \\\"x\\\" \\`y\\` \\\\\"\n");
+    }
+
+    #[test]
+    fn format_from_path() {
+        assert_eq!(SecretFormat::from_path(Some(Path::new("secret.YAML"))), SecretFormat::Kubernetes);
+        assert_eq!(SecretFormat::from_path(Some(Path::new("app.conf"))), SecretFormat::Systemd);
+        assert_eq!(SecretFormat::from_path(Some(Path::new(".env"))), SecretFormat::Env);
+        assert_eq!(SecretFormat::from_path(None), SecretFormat::Env);
+    }
+}
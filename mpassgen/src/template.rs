@@ -0,0 +1,428 @@
+//! Render templates containing `<<name:spec>>` placeholders.
+//!
+//! Each placeholder is replaced in place by a passphrase generated from its
+//! spec string. A name is bound once per render: `<<name>>` anywhere in the
+//! template, before or after the definition, repeats the same value. Anything
+//! that is not a well formed placeholder, such as a lone `<<` in a shell
+//! heredoc, is copied through unchanged.
+//!
+//! Filters from a [`FilterRegistry`] can be appended to any placeholder to
+//! transform the bound value where it is inserted, without changing the value
+//! itself: `<<db|sha256>>`, `<<db:i w ##|upper|base64>>`. After filters, each
+//! value is escaped for the output format unless the last filter is `raw`.
+//!
+//! Example:
+//!     "pass: <<admin_pw:i w w ###$>>\nagain: <<admin_pw>>\n" => "pass: Medium test phrase 123!\nagain: Medium test phrase 123!\n"
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use zeroize::Zeroizing;
+use mpass::{Passphrase, Specifier};
+use mpass::filter::{FilterError, FilterRegistry};
+use crate::escape::Escape;
+use mpass::specifier::specifier_error::SpecifierError;
+
+const OPEN: &str = "<<";
+const CLOSE: &str = ">>";
+/// Filter name that skips escaping. It is handled here rather than by the registry.
+const RAW_FILTER: &str = "raw";
+
+/// A `<<name:spec>>` definition or `<<name>>` reference and where it starts in the template.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Placeholder {
+    pub name: String,
+    /// The spec string, or None for a reference to a name defined elsewhere.
+    pub spec: Option<String>,
+    /// Filters applied to the value where this placeholder is inserted, in order.
+    pub filters: Vec<String>,
+    /// Line of the opening `<<`, starting at 1.
+    pub line: usize,
+    /// Column of the opening `<<` in characters, starting at 1.
+    pub column: usize
+}
+
+impl Placeholder {
+    /// Column of the first character of the spec string.
+    fn spec_column(&self) -> usize {
+        self.column + OPEN.len() + self.name.chars().count() + 1
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Text(String),
+    Placeholder(Placeholder)
+}
+
+/// The output of a render along with the values generated for it.
+pub struct Rendered {
+    pub output: Zeroizing<String>,
+    /// Generated values by name, in the order they were defined.
+    pub generated: Vec<(String, Passphrase)>
+}
+
+/// A parsed template.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    segments: Vec<Segment>
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TemplateErrorKind {
+    /// The spec has an invalid character, or no tokens if None.
+    InvalidSpec(Option<char>),
+    /// A name was defined again with a different spec. Holds the name and
+    /// the line and column of the first definition.
+    ConflictingDefinition { name: String, line: usize, column: usize },
+    /// A name was referenced but never defined.
+    Undefined(String),
+    /// A name defined in the template is also supplied as a field.
+    FieldConflict(String),
+    Filter(FilterError),
+    Specifier(SpecifierError)
+}
+
+/// An error in a template, located by line and column.
+#[derive(Debug, PartialEq)]
+pub struct TemplateError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: TemplateErrorKind
+}
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match &self.kind {
+            TemplateErrorKind::InvalidSpec(Some(ch)) => write!(f, "invalid character '{}' in spec", ch),
+            TemplateErrorKind::InvalidSpec(None) => write!(f, "spec contains no tokens"),
+            TemplateErrorKind::ConflictingDefinition { name, line, column } =>
+                write!(f, "'{}' is already defined with a different spec at line {}, column {}", name, line, column),
+            TemplateErrorKind::Undefined(name) => write!(f, "'{}' is not defined", name),
+            TemplateErrorKind::FieldConflict(name) => write!(f, "'{}' is defined in the template and also given as a field", name),
+            TemplateErrorKind::Filter(e) => write!(f, "{}", e),
+            TemplateErrorKind::Specifier(e) => write!(f, "{}", e)
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl Template {
+    /// Parse a template, validating the spec of every placeholder and
+    /// checking that no name is defined with two different specs.
+    pub fn parse(source: &str) -> Result<Self, TemplateError> {
+        let mut definitions: HashMap<String, Placeholder> = HashMap::new();
+        let mut segments = Vec::new();
+        let mut text_start = 0;
+        let mut search = 0;
+
+        while let Some(found) = source[search..].find(OPEN) {
+            let start = search + found;
+            let body_start = start + OPEN.len();
+            // placeholders never span lines
+            let line_end = source[body_start..].find('\n').map_or(source.len(), |i| body_start + i);
+            let body = source[body_start..line_end].find(CLOSE)
+                                                   .map(|len| &source[body_start..body_start + len]);
+
+            let Some((body, (name, spec, filters))) = body.and_then(|b| parse_body(b).map(|p| (b, p))) else {
+                // not a placeholder, keep looking from the next character
+                search = start + 1;
+                continue;
+            };
+
+            let (line, column) = location(source, start);
+            let placeholder = Placeholder {
+                name: name.to_string(),
+                spec: spec.map(|s| s.to_string()),
+                filters: filters.iter().map(|f| f.to_string()).collect(),
+                line,
+                column
+            };
+            if let Some(spec) = spec {
+                if let Err(offset) = Specifier::check_spec_string(spec) {
+                    return Err(TemplateError {
+                        line,
+                        column: placeholder.spec_column() + spec[..offset].chars().count(),
+                        kind: TemplateErrorKind::InvalidSpec(spec[offset..].chars().next())
+                    });
+                }
+
+                match definitions.get(name) {
+                    Some(first) if first.spec != placeholder.spec => {
+                        return Err(TemplateError {
+                            line,
+                            column,
+                            kind: TemplateErrorKind::ConflictingDefinition { name: name.to_string(), line: first.line, column: first.column }
+                        });
+                    }
+                    Some(_) => {}
+                    None => {
+                        definitions.insert(name.to_string(), placeholder.clone());
+                    }
+                }
+            }
+
+            if text_start < start {
+                segments.push(Segment::Text(source[text_start..start].to_string()));
+            }
+            segments.push(Segment::Placeholder(placeholder));
+
+            text_start = body_start + body.len() + CLOSE.len();
+            search = text_start;
+        }
+
+        if text_start < source.len() {
+            segments.push(Segment::Text(source[text_start..].to_string()));
+        }
+
+        Ok(Template { segments })
+    }
+
+    fn placeholders(&self) -> impl Iterator<Item = &Placeholder> {
+        self.segments.iter().filter_map(|s| match s {
+            Segment::Placeholder(p) => Some(p),
+            Segment::Text(_) => None
+        })
+    }
+
+    /// Render the template, generating one passphrase for each defined name.
+    /// The Specifier's spec string is changed to each definition's spec in turn.
+    pub fn render(&self, specifier: &mut Specifier, filters: &FilterRegistry, escape: Escape) -> Result<Zeroizing<String>, TemplateError> {
+        self.render_with(specifier, filters, escape, &HashMap::new())
+            .map(|r| r.output)
+    }
+
+    /// Render the template with extra named values, such as the fields of a
+    /// CSV row, available as `<<field>>` references. Field values are filtered
+    /// and escaped the same as generated ones.
+    pub fn render_with(&self, specifier: &mut Specifier, filters: &FilterRegistry, escape: Escape,
+                       fields: &HashMap<String, String>) -> Result<Rendered, TemplateError> {
+        let mut bindings: HashMap<&str, Passphrase> = fields.iter()
+                                                            .map(|(k, v)| (k.as_str(), Passphrase::from(v.as_str())))
+                                                            .collect();
+        let mut generated = Vec::new();
+
+        for p in self.placeholders() {
+            let Some(spec) = &p.spec else {
+                continue;
+            };
+            if fields.contains_key(&p.name) {
+                return Err(TemplateError { line: p.line, column: p.column, kind: TemplateErrorKind::FieldConflict(p.name.clone()) });
+            }
+            if bindings.contains_key(p.name.as_str()) {
+                continue;
+            }
+
+            let located = |e| TemplateError { line: p.line, column: p.column, kind: TemplateErrorKind::Specifier(e) };
+            specifier.try_change_spec_string(spec).map_err(located)?;
+            let value = specifier.get_passphrase().map_err(located)?;
+            generated.push((p.name.clone(), value.clone()));
+            bindings.insert(&p.name, value);
+        }
+
+        let mut output = Zeroizing::new(String::new());
+        for segment in self.segments.iter() {
+            match segment {
+                Segment::Text(text) => output.push_str(text),
+                Segment::Placeholder(p) => {
+                    let Some(value) = bindings.get(p.name.as_str()) else {
+                        return Err(TemplateError { line: p.line, column: p.column, kind: TemplateErrorKind::Undefined(p.name.clone()) });
+                    };
+                    let raw = p.filters.last().is_some_and(|f| f == RAW_FILTER);
+                    let chain = if raw { &p.filters[..p.filters.len() - 1] } else { &p.filters[..] };
+                    let value = filters.apply_all(chain, value.expose())
+                                       .map_err(|e| TemplateError { line: p.line, column: p.column, kind: TemplateErrorKind::Filter(e) })?;
+                    if raw {
+                        output.push_str(value.expose());
+                    }
+                    else {
+                        output.push_str(&Zeroizing::new(escape.apply(value.expose())));
+                    }
+                }
+            }
+        }
+
+        Ok(Rendered { output, generated })
+    }
+}
+
+/// Split a placeholder body into its name, optional spec and filters.
+/// Names start with a letter or underscore and may contain letters, digits, `_` and `-`.
+/// Filter names may contain letters, digits and `_`.
+fn parse_body(body: &str) -> Option<(&str, Option<&str>, Vec<&str>)> {
+    let mut parts = body.split('|');
+    let head = parts.next()?;
+    let filters: Vec<&str> = parts.collect();
+    let (name, spec) = match head.split_once(':') {
+        Some((name, spec)) => (name, Some(spec)),
+        None => (head, None)
+    };
+    let mut chars = name.chars();
+    let first = chars.next()?;
+
+    let valid_name = (first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    let valid_filters = filters.iter()
+                               .all(|f| !f.is_empty() && f.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+    if !valid_name || !valid_filters {
+        return None;
+    }
+
+    Some((name, spec, filters))
+}
+
+/// Line and column, both starting at 1, of a byte offset.
+fn location(source: &str, offset: usize) -> (usize, usize) {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_chacha::ChaCha20Rng;
+    use rand_chacha::rand_core::SeedableRng;
+
+    fn specifier() -> Specifier {
+        let mut specifier = Specifier::try_parse("w").unwrap();
+        specifier.set_rng(ChaCha20Rng::seed_from_u64(1));
+        specifier
+    }
+
+    #[test]
+    fn renders_in_place() {
+        let template = Template::parse("<html>\n<p><<pw1:i w ##$>></p>\n<p><<pin_2:####>></p>\n</html>").unwrap();
+        assert_eq!(template.segments.len(), 5);
+
+        let output = template.render(&mut specifier(), &FilterRegistry::with_defaults(), Escape::None).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[1].starts_with("<p>") && lines[1].ends_with("</p>"));
+        let pin = lines[2].trim_start_matches("<p>").trim_end_matches("</p>");
+        assert_eq!(pin.len(), 4);
+        assert!(pin.chars().all(|c| c.is_ascii_digit()));
+    }
+
+    #[test]
+    fn full_spec_alphabet() {
+        let template = Template::parse("<<all:?wWiraAxz#$ >>").unwrap();
+
+        assert!(matches!(&template.segments[0], Segment::Placeholder(p) if p.spec.as_deref() == Some("?wWiraAxz#$ ")));
+        assert!(template.render(&mut specifier(), &FilterRegistry::with_defaults(), Escape::None).is_ok());
+    }
+
+    #[test]
+    fn mixes_keys_and_passphrases() {
+        let template = Template::parse("JWT_SECRET=<<jwt_secret:32b>>\nID=<<id:u>>\nADMIN=<<admin:i w ##>>").unwrap();
+        let output = template.render(&mut specifier(), &FilterRegistry::with_defaults(), Escape::None).unwrap();
+        let values: Vec<&str> = output.lines().map(|l| l.split_once('=').unwrap().1).collect();
+
+        assert_eq!(values[0].len(), 43);
+        assert!(values[0].bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'));
+        assert_eq!(values[1].len(), 36);
+        assert_eq!(values[2].split(' ').count(), 3);
+        assert!(Template::parse("<<key:2000h>>").is_err());
+    }
+
+    #[test]
+    fn leaves_non_placeholders() {
+        let source = "cat <<EOF\n<<<x:##>>> a << b >> <<:##>> <<1x>>\nEOF\n";
+        let output = Template::parse(source).unwrap().render(&mut specifier(), &FilterRegistry::with_defaults(), Escape::None).unwrap();
+
+        assert!(output.starts_with("cat <<EOF\n<"));
+        assert!(output.contains("> a << b >> <<:##>> <<1x>>\nEOF\n"));
+        assert!(!output.contains("<<x:"));
+    }
+
+    #[test]
+    fn reports_spec_errors() {
+        let e = Template::parse("one\ntwo <<pw:i wqw>>").unwrap_err();
+        assert_eq!(e, TemplateError { line: 2, column: 13, kind: TemplateErrorKind::InvalidSpec(Some('q')) });
+        assert_eq!(e.to_string(), "line 2, column 13: invalid character 'q' in spec");
+
+        let e = Template::parse("<<pw:??>>").unwrap_err();
+        assert_eq!(e.kind, TemplateErrorKind::InvalidSpec(None));
+    }
+
+    #[test]
+    fn reuses_named_values() {
+        let source = "summary: <<db>>\nbody: <<db:iw##>> <<other:##########>> <<db:iw##>>\nend: <<db>>";
+        let output = Template::parse(source).unwrap().render(&mut specifier(), &FilterRegistry::with_defaults(), Escape::None).unwrap();
+        let values: Vec<&str> = output.lines().map(|l| l.split_once(": ").unwrap().1).collect();
+
+        let body: Vec<&str> = values[1].split(' ').collect();
+        assert_eq!(values[0], body[0]);
+        assert_eq!(values[2], body[0]);
+        assert_eq!(body[2], body[0]);
+        assert_ne!(body[1], body[0]);
+    }
+
+    #[test]
+    fn reports_conflicts_and_undefined() {
+        let e = Template::parse("<<db:i w ##>>\n  <<db:ww>>").unwrap_err();
+        assert_eq!(e, TemplateError {
+            line: 2,
+            column: 3,
+            kind: TemplateErrorKind::ConflictingDefinition { name: "db".to_string(), line: 1, column: 1 }
+        });
+        assert_eq!(e.to_string(), "line 2, column 3: 'db' is already defined with a different spec at line 1, column 1");
+
+        let e = Template::parse("<<db:ww>>\nx <<pw>>").unwrap().render(&mut specifier(), &FilterRegistry::with_defaults(), Escape::None).unwrap_err();
+        assert_eq!(e, TemplateError { line: 2, column: 3, kind: TemplateErrorKind::Undefined("pw".to_string()) });
+        assert_eq!(e.to_string(), "line 2, column 3: 'pw' is not defined");
+    }
+
+    #[test]
+    fn applies_filters() {
+        let source = "<<db:iw##|upper>>\n<<db>>\n<<db|base64|json>>\n<<db|sha256>>";
+        let output = Template::parse(source).unwrap().render(&mut specifier(), &FilterRegistry::with_defaults(), Escape::None).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+
+        let registry = FilterRegistry::with_defaults();
+        assert_eq!(lines[0], lines[1].to_uppercase());
+        assert_eq!(lines[2], registry.apply_all(&["base64", "json"], lines[1]).unwrap().expose());
+        assert_eq!(lines[3], registry.apply("sha256", lines[1]).unwrap().expose());
+    }
+
+    #[test]
+    fn reports_unknown_filters() {
+        let e = Template::parse("x\n <<db:ww|rot13>>").unwrap().render(&mut specifier(), &FilterRegistry::with_defaults(), Escape::None).unwrap_err();
+
+        assert_eq!(e.to_string(), "line 2, column 2: unknown filter 'rot13'");
+        assert_eq!(Template::parse("<<db:ww|>> <<db:ww|a-b>>").unwrap().segments.len(), 1);
+    }
+
+    #[test]
+    fn escapes_values() {
+        let mut specifier = Specifier::try_parse_custom("w", vec!["a&b".to_string()], vec!["\"'".to_string()]).unwrap();
+        let template = Template::parse("<<pw:w$>> <<pw|raw>> <<pw|json|raw>>").unwrap();
+
+        let output = template.render(&mut specifier, &FilterRegistry::with_defaults(), Escape::Html).unwrap();
+        assert_eq!(output.as_str(), "a&amp;b&quot;&#39; a&b\"' \"a&b\\\"'\"");
+
+        let output = template.render(&mut specifier, &FilterRegistry::with_defaults(), Escape::Sql).unwrap();
+        assert!(output.starts_with("a&b\"'' "));
+    }
+
+    #[test]
+    fn renders_with_fields() {
+        let mut fields = HashMap::new();
+        fields.insert("user".to_string(), "O'Brien & Co".to_string());
+        let template = Template::parse("<<user>>|<<user|upper>>|<<pw:####>>|<<pw>>").unwrap();
+
+        let rendered = template.render_with(&mut specifier(), &FilterRegistry::with_defaults(), Escape::Html, &fields).unwrap();
+        let parts: Vec<&str> = rendered.output.split('|').collect();
+        assert_eq!(parts[0], "O&#39;Brien &amp; Co");
+        assert_eq!(parts[1], "O&#39;BRIEN &amp; CO");
+        assert_eq!(rendered.generated.len(), 1);
+        assert_eq!(rendered.generated[0].0, "pw");
+        assert_eq!(rendered.generated[0].1.expose(), parts[2]);
+
+        let template = Template::parse("\n <<user:ww>>").unwrap();
+        let e = template.render_with(&mut specifier(), &FilterRegistry::with_defaults(), Escape::None, &fields).err().unwrap();
+        assert_eq!(e, TemplateError { line: 2, column: 2, kind: TemplateErrorKind::FieldConflict("user".to_string()) });
+    }
+}